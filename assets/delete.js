@@ -0,0 +1,21 @@
+// the trash buttons of the rows, the entry is deleted once the visitor confirmed it
+document.querySelectorAll("[data-delete]").forEach((button) =>
+    button.addEventListener("click", async () => {
+        const row = button.closest("[data-name]");
+        const name = row.dataset.name;
+        if (!confirm(`Delete ${name}? This cannot be undone.`)) {
+            return;
+        }
+        button.disabled = true;
+        const res = await fetch(button.dataset.delete, {method: "DELETE"}).catch(() => null);
+        if (res && res.ok) {
+            row.remove();
+            return;
+        }
+        button.disabled = false;
+        const reason = !res ? "the server cannot be reached"
+            : res.status === 409 ? "the folder is not empty"
+            : `the server answered ${res.status}`;
+        alert(`${name} was not deleted: ${reason}.`);
+    })
+);
@@ -0,0 +1,27 @@
+// hides the rows or gallery tiles of the listing whose name does not contain the text of the filter box
+const filter = document.getElementById("filter");
+if (filter) {
+    const rows = document.querySelectorAll("[data-name]");
+    const noMatch = document.getElementById("no-match");
+    const apply = () => {
+        const text = filter.value.trim().toLowerCase();
+        let shown = 0;
+        rows.forEach((row) => {
+            row.hidden = text !== "" && !row.dataset.name.toLowerCase().includes(text);
+            if (!row.hidden) {
+                shown++;
+            }
+        });
+        noMatch.hidden = shown > 0 || rows.length === 0;
+        filter.dispatchEvent(new Event("filtered"));
+    };
+    filter.addEventListener("input", apply);
+    filter.addEventListener("keydown", (event) => {
+        if (event.key === "Escape") {
+            filter.value = "";
+            apply();
+        }
+    });
+    // the browser keeps the text when going back to the page
+    apply();
+}
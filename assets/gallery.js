@@ -0,0 +1,55 @@
+// opens the images of the gallery in a lightbox, the arrow keys and buttons go through the
+// tiles left by the filter and Escape closes it. the details of the photo are
+// shown in a corner
+const lightbox = document.getElementById("lightbox");
+if (lightbox) {
+    const links = [...document.querySelectorAll("a[data-lightbox]")];
+    const image = lightbox.querySelector("img");
+    const details = lightbox.querySelector(".meta");
+    let current = -1;
+    const shown = () => links.filter((link) => !link.closest("[data-name]").hidden);
+    const open = (link) => {
+        current = shown().indexOf(link);
+        image.src = link.href;
+        image.alt = link.closest("[data-name]").dataset.name;
+        lightbox.hidden = false;
+        showMeta(link.pathname.replace(/\/view\//, "/api/meta/"), details);
+    };
+    const step = (by) => {
+        const tiles = shown();
+        if (tiles.length > 0) {
+            open(tiles[(current + by + tiles.length) % tiles.length]);
+        }
+    };
+    const close = () => {
+        lightbox.hidden = true;
+        image.removeAttribute("src");
+    };
+    links.forEach((link) =>
+        link.addEventListener("click", (event) => {
+            event.preventDefault();
+            open(link);
+        })
+    );
+    lightbox.querySelector(".prev").addEventListener("click", () => step(-1));
+    lightbox.querySelector(".next").addEventListener("click", () => step(1));
+    lightbox.querySelector(".close").addEventListener("click", close);
+    // a click next to the image closes it too
+    lightbox.addEventListener("click", (event) => {
+        if (event.target === lightbox) {
+            close();
+        }
+    });
+    document.addEventListener("keydown", (event) => {
+        if (lightbox.hidden) {
+            return;
+        }
+        if (event.key === "ArrowLeft") {
+            step(-1);
+        } else if (event.key === "ArrowRight") {
+            step(1);
+        } else if (event.key === "Escape") {
+            close();
+        }
+    });
+}
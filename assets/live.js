@@ -0,0 +1,21 @@
+// reloads the listing when files are added to or removed from its folder. while the page is in
+// use, something selected, filtered, playing or shown, a note offers the reload instead
+const note = document.getElementById("live-note");
+if (note && window.EventSource) {
+    const busy = () =>
+        document.querySelector("input[form=selection]:checked") !== null ||
+        (document.getElementById("filter")?.value ?? "") !== "" ||
+        [...document.querySelectorAll("audio, video")].some((media) => !media.paused) ||
+        [...document.querySelectorAll(".lightbox")].some((box) => !box.hidden);
+    let pending = null;
+    const events = new EventSource(note.dataset.live);
+    events.addEventListener("change", () => {
+        if (busy()) {
+            note.hidden = false;
+            return;
+        }
+        // a file being copied changes many times, the page is loaded once it settled
+        clearTimeout(pending);
+        pending = setTimeout(() => location.reload(), 1000);
+    });
+}
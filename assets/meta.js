@@ -0,0 +1,43 @@
+// fills a <dl> with the details /api/meta tells about a file, fields it left out are skipped
+const META_LABELS = {
+    width: "Width",
+    height: "Height",
+    duration: "Duration",
+    codec: "Codec",
+    camera: "Camera",
+    lens: "Lens",
+    taken: "Taken",
+    exposure: "Exposure",
+    aperture: "Aperture",
+    iso: "ISO",
+    focal_length: "Focal length",
+    gps: "Position",
+};
+
+function showMeta(url, list) {
+    list.replaceChildren();
+    fetch(url, { headers: { Accept: "application/json" } })
+        .then((res) => (res.ok ? res.json() : {}))
+        .then((meta) => {
+            for (const [key, label] of Object.entries(META_LABELS)) {
+                let value = meta[key];
+                if (value === undefined || value === null) {
+                    continue;
+                }
+                if (key === "width" || key === "height") {
+                    value = `${value} px`;
+                } else if (key === "duration") {
+                    value = `${Math.floor(value / 60)}:${String(Math.floor(value % 60)).padStart(2, "0")}`;
+                } else if (key === "gps") {
+                    value = `${value.latitude.toFixed(5)}, ${value.longitude.toFixed(5)}`;
+                }
+                const term = document.createElement("dt");
+                term.textContent = label;
+                const detail = document.createElement("dd");
+                detail.textContent = value;
+                list.append(term, detail);
+            }
+            list.hidden = list.children.length === 0;
+        })
+        .catch(() => (list.hidden = true));
+}
@@ -0,0 +1,37 @@
+// plays the music of the listing, a track goes on with the next one left by the filter once
+// it ends
+const player = document.getElementById("player");
+if (player) {
+    const audio = player.querySelector("audio");
+    const label = player.querySelector(".track");
+    const tracks = [...document.querySelectorAll("[data-track]")];
+    let current = null;
+    const shown = () => tracks.filter((track) => !track.closest("[data-name]").hidden);
+    const play = (track) => {
+        if (current) {
+            current.closest("[data-name]").classList.remove("playing");
+        }
+        current = track;
+        if (!track) {
+            audio.removeAttribute("src");
+            label.textContent = "No track playing.";
+            return;
+        }
+        const row = track.closest("[data-name]");
+        row.classList.add("playing");
+        label.textContent = row.dataset.name;
+        audio.src = track.dataset.track;
+        audio.play();
+    };
+    // the track next to the current one, none past the end of the folder
+    const step = (by) => {
+        const list = shown();
+        const at = list.indexOf(current);
+        return list[at === -1 ? 0 : at + by] || null;
+    };
+    tracks.forEach((track) => track.addEventListener("click", () => play(track)));
+    player.querySelector("[data-play=all]").addEventListener("click", () => play(shown()[0] || null));
+    player.querySelector("[data-play=prev]").addEventListener("click", () => play(step(-1)));
+    player.querySelector("[data-play=next]").addEventListener("click", () => play(step(1)));
+    audio.addEventListener("ended", () => play(step(1)));
+}
@@ -0,0 +1,26 @@
+// the buttons with a QR code show it over the page, to open the link on another device
+const qr = document.getElementById("qr");
+if (qr) {
+    const image = qr.querySelector("img");
+    const close = () => {
+        qr.hidden = true;
+        image.removeAttribute("src");
+    };
+    document.querySelectorAll("[data-qr]").forEach((button) =>
+        button.addEventListener("click", () => {
+            image.src = button.dataset.qr;
+            qr.hidden = false;
+        })
+    );
+    qr.querySelector(".close").addEventListener("click", close);
+    qr.addEventListener("click", (event) => {
+        if (event.target === qr) {
+            close();
+        }
+    });
+    document.addEventListener("keydown", (event) => {
+        if (event.key === "Escape" && !qr.hidden) {
+            close();
+        }
+    });
+}
@@ -0,0 +1,12 @@
+// the theme button cycles through the themes, the cookie keeps the choice for the other pages
+const toggle = document.getElementById("theme");
+if (toggle) {
+    toggle.addEventListener("click", () => {
+        const themes = ["auto", "light", "dark"];
+        const current = document.documentElement.className.replace("theme-", "");
+        const next = themes[(themes.indexOf(current) + 1) % themes.length];
+        document.documentElement.className = "theme-" + next;
+        document.cookie = "file_serve_theme=" + next + "; Path=/; Max-Age=31536000; SameSite=Lax";
+        toggle.textContent = "Theme: " + next;
+    });
+}
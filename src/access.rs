@@ -0,0 +1,297 @@
+use crate::errors::error_response;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// clients kept before the ones with a full bucket again are forgotten
+const MAX_TRACKED: usize = 10_000;
+
+// private (RFC1918 / unique local), link-local or loopback addresses
+pub fn is_lan_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_link_local() || v4.is_loopback(),
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_lan_address(IpAddr::V4(v4));
+            }
+            v6.is_loopback() || v6.is_unique_local() || v6.is_unicast_link_local()
+        }
+    }
+}
+
+// rejects clients outside the local network, used with --lan-only
+pub async fn lan_only(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_lan_address(addr.ip()) {
+        log::warn!("[LAN-ONLY] rejected request from {}", addr);
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Only clients on the local network may access this server.",
+        );
+    }
+    next.run(req).await
+}
+
+// counts the in-flight downloads of every client, used with --max-streams-per-ip
+pub struct StreamLimiter {
+    max_per_ip: usize,
+    active: Mutex<HashMap<IpAddr, usize>>,
+}
+
+// a download slot, released when the response body is dropped
+pub struct StreamGuard {
+    limiter: Arc<StreamLimiter>,
+    ip: IpAddr,
+}
+
+impl StreamLimiter {
+    pub fn new(max_per_ip: usize) -> Self {
+        StreamLimiter {
+            max_per_ip,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<StreamGuard> {
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(ip).or_insert(0);
+        if *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(StreamGuard {
+            limiter: Arc::clone(self),
+            ip,
+        })
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        let mut active = self.limiter.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// the requests of every client as a token bucket, used with --rate-limit. a client can send a
+// second worth of requests at once, then as many per second as the rate allows
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64) -> Self {
+        RateLimiter {
+            rate,
+            burst: rate.max(1.0),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // takes a token of the client, or tells how many seconds until the next one
+    fn take(&self, ip: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED {
+            let (rate, burst) = (self.rate, self.burst);
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.at).as_secs_f64() * rate < burst
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            at: now,
+        });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.at).as_secs_f64() * self.rate)
+            .min(self.burst);
+        bucket.at = now;
+        if bucket.tokens < 1.0 {
+            return Err(((1.0 - bucket.tokens) / self.rate).ceil().max(1.0) as u64);
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+// answers 429 to clients sending more requests than --rate-limit allows
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if let Err(wait) = limiter.take(addr.ip()) {
+        log::warn!("[LIMIT] too many requests from {}", addr.ip());
+        let mut res = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many requests from your address.",
+        );
+        res.headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(wait));
+        return res;
+    }
+    next.run(req).await
+}
+
+// an address range like 10.0.0.0/8 or fd00::/8, a single address without the length
+struct IpRange {
+    net: IpAddr,
+    bits: u32,
+}
+
+impl IpRange {
+    fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid address range {}", s);
+        let (net, bits) = match s.trim().split_once('/') {
+            Some((net, bits)) => (net, Some(bits.parse::<u32>().map_err(|_| invalid())?)),
+            None => (s.trim(), None),
+        };
+        let net = net.parse::<IpAddr>().map_err(|_| invalid())?;
+        let max = if net.is_ipv4() { 32 } else { 128 };
+        let bits = bits.unwrap_or(max);
+        if bits > max {
+            return Err(invalid());
+        }
+        Ok(IpRange { net, bits })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        match (self.net, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.bits).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.bits).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// the reverse proxies of --trusted-proxy, a request they pass on comes from the client named
+// in Forwarded or X-Forwarded-For
+pub struct TrustedProxies {
+    ranges: Vec<IpRange>,
+}
+
+impl TrustedProxies {
+    pub fn parse<'a>(ranges: impl IntoIterator<Item = &'a String>) -> Result<Self, String> {
+        let ranges = ranges
+            .into_iter()
+            .map(|range| IpRange::parse(range))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TrustedProxies { ranges })
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    // the address before the last trusted proxy. the headers are read from their end, as every
+    // proxy appends the address it got the request from, and anything before an untrusted hop
+    // could have been made up by the client
+    fn client(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let hops = forwarded_for(headers);
+        let mut client = peer;
+        for hop in hops.iter().rev() {
+            if !self.trusts(client) {
+                break;
+            }
+            match hop {
+                Some(ip) => client = *ip,
+                // unknown or hidden, nothing further can be told
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+// the for= addresses of Forwarded, or else the ones of X-Forwarded-For, in their order. none
+// for the ones that are no address
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let forwarded = values(header::FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for").then(|| node_ip(value))
+                })
+            })
+            .collect();
+    }
+    values(HeaderName::from_static("x-forwarded-for"))
+        .iter()
+        .map(|value| node_ip(value))
+        .collect()
+}
+
+// 192.0.2.1, 192.0.2.1:4711, "[2001:db8::1]:4711" or 2001:db8::1
+fn node_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    value.parse().ok().or_else(|| {
+        let (ip, _port) = value.rsplit_once(':')?;
+        ip.parse::<std::net::Ipv4Addr>().ok().map(IpAddr::V4)
+    })
+}
+
+// requests of a trusted proxy get the address of the client behind it, for the logs, the
+// limits and --lan-only
+pub async fn forwarded(
+    State(proxies): State<Arc<TrustedProxies>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if proxies.trusts(addr.ip()) {
+        let client = proxies.client(addr.ip(), req.headers());
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::new(client, 0)));
+    }
+    next.run(req).await
+}
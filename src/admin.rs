@@ -0,0 +1,298 @@
+use crate::{
+    auth, auth_log, base_path,
+    errors::error_response,
+    share,
+    templates::{self, Theme},
+    transfers,
+    users::Users,
+    utils,
+    webhook::{self, Kind},
+    AppState,
+};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    sync::OnceLock,
+    time::{Duration, UNIX_EPOCH},
+};
+
+// --admin USER:PASS, the /admin panel of whoever runs the server. its accounts are apart from
+// the ones of the listing, a visitor who can log in there cannot change the server
+struct Admin {
+    users: Users,
+    // every form of the panel carries it, so a page of another site cannot post to the panel
+    // with the credentials the browser keeps
+    token: String,
+}
+
+static ADMIN: OnceLock<Admin> = OnceLock::new();
+
+pub fn enable(users: Users) {
+    let _ = ADMIN.set(Admin {
+        users,
+        token: utils::random_token(),
+    });
+}
+
+pub fn enabled() -> bool {
+    ADMIN.get().is_some()
+}
+
+// merged next to the login routes, the panel asks for its own credentials
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin", get(page))
+        .route("/admin/uploads", post(uploads))
+        .route("/admin/read-only", post(read_only))
+        .route("/admin/revoke", post(revoke))
+        .route("/admin/stop", post(stop))
+        .route_layer(middleware::from_fn(require_admin))
+}
+
+// HTTP Basic on every request, with a realm of its own so browsers keep the two logins apart
+async fn require_admin(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(admin) = ADMIN.get() else {
+        return error_response(StatusCode::NOT_FOUND, "The admin panel is off");
+    };
+    let Some((name, password)) = auth::basic_credentials(req.headers()) else {
+        return challenge("Login required.");
+    };
+    if let Err(err) = admin.users.authenticate(&name, &password) {
+        log::warn!("[ADMIN] login of {} from {} failed: {}", name, addr, err);
+        auth_log::failed(addr.ip(), Some(&name));
+        webhook::notify(Kind::AuthFailed, addr.ip(), None, None, Some(&name));
+        return challenge("Invalid user name or password.");
+    }
+    next.run(req).await
+}
+
+fn challenge(message: &str) -> Response {
+    let mut res = error_response(StatusCode::UNAUTHORIZED, message);
+    res.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"file-serve admin\""),
+    );
+    res
+}
+
+// a form of the panel, or one posted from somewhere else
+fn check_token(token: &str) -> Option<Response> {
+    match ADMIN.get() {
+        Some(admin) if admin.token == token => None,
+        _ => Some(error_response(
+            StatusCode::FORBIDDEN,
+            "The form has expired, reload the admin panel",
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct ToggleForm {
+    token: String,
+    on: bool,
+}
+
+#[derive(Deserialize)]
+struct RevokeForm {
+    token: String,
+    link: String,
+}
+
+#[derive(Deserialize)]
+struct StopForm {
+    token: String,
+}
+
+// a download running
+#[derive(Serialize)]
+struct TransferRow {
+    client: String,
+    path: String,
+    sent: String,
+    total: Option<String>,
+    seconds: u64,
+}
+
+// a share link handed out since the start
+#[derive(Serialize)]
+struct ShareRow {
+    path: String,
+    link: String,
+    expires: String,
+    limit: String,
+    revoked: bool,
+}
+
+// fields of the admin panel
+#[derive(Serialize)]
+struct AdminPage {
+    transfers: Vec<TransferRow>,
+    upload: bool,
+    read_only: bool,
+    // --allow-write, WebDAV writes are not turned on at runtime
+    write: bool,
+    // --url-secret, without it no links can be handed out nor revoked
+    sharing: bool,
+    shares: Vec<ShareRow>,
+    token: String,
+    action: String,
+    stopping: bool,
+}
+
+async fn page(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    render(&state, &headers, false)
+}
+
+fn render(state: &AppState, headers: &HeaderMap, stopping: bool) -> Response {
+    let transfers = transfers::active()
+        .into_iter()
+        .map(|transfer| TransferRow {
+            client: transfer.client.to_string(),
+            path: transfer.path,
+            sent: utils::bytes_to_human_size(transfer.sent),
+            total: transfer.total.map(utils::bytes_to_human_size),
+            seconds: transfer.started.elapsed().as_secs(),
+        })
+        .collect();
+    let shares = state.shares.as_ref().map_or(Vec::new(), |shares| {
+        shares
+            .issued()
+            .into_iter()
+            .map(|issued| ShareRow {
+                expires: DateTime::<Local>::from(UNIX_EPOCH + Duration::from_secs(issued.expires))
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                limit: {
+                    let what = match issued.kind {
+                        share::Kind::Share => "downloads",
+                        share::Kind::Upload => "uploads",
+                    };
+                    match issued.max_downloads {
+                        Some(max) => format!("{} of {} {}", shares.downloads(&issued), max, what),
+                        None => format!("unlimited {}", what),
+                    }
+                },
+                revoked: shares.is_revoked(&issued),
+                path: issued.path,
+                link: issued.link,
+            })
+            .collect()
+    });
+    let page = AdminPage {
+        transfers,
+        upload: state.settings.upload_allowed(),
+        read_only: state.settings.read_only(),
+        write: state.settings.write_allowed(),
+        sharing: state.shares.is_some(),
+        shares,
+        token: ADMIN
+            .get()
+            .map_or(String::new(), |admin| admin.token.clone()),
+        action: base_path::link("/admin"),
+        stopping,
+    };
+    match templates::render("admin.html", Theme::of(headers), &page) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            log::error!("Error loading admin template: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load admin template",
+            )
+        }
+    }
+}
+
+// back to the panel after a change, a reload does not post the form again
+fn back() -> Response {
+    Redirect::to(&base_path::link("/admin")).into_response()
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+async fn uploads(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(form): Form<ToggleForm>,
+) -> Response {
+    if let Some(res) = check_token(&form.token) {
+        return res;
+    }
+    state.settings.set_upload(form.on);
+    log::info!(
+        "[ADMIN] Client: {} | uploads turned {}",
+        addr,
+        on_off(form.on)
+    );
+    back()
+}
+
+async fn read_only(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(form): Form<ToggleForm>,
+) -> Response {
+    if let Some(res) = check_token(&form.token) {
+        return res;
+    }
+    state.settings.set_read_only(form.on);
+    log::info!(
+        "[ADMIN] Client: {} | read-only mode turned {}",
+        addr,
+        on_off(form.on)
+    );
+    back()
+}
+
+async fn revoke(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(form): Form<RevokeForm>,
+) -> Response {
+    if let Some(res) = check_token(&form.token) {
+        return res;
+    }
+    let Some(shares) = &state.shares else {
+        return error_response(StatusCode::NOT_FOUND, "Sharing is not enabled");
+    };
+    match shares.revoke(&form.link) {
+        Ok(path) => {
+            log::info!("[ADMIN] Client: {} | revoked the share of {}", addr, path);
+            back()
+        }
+        Err(err) => error_response(StatusCode::BAD_REQUEST, &format!("Cannot revoke: {}", err)),
+    }
+}
+
+async fn stop(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<StopForm>,
+) -> Response {
+    if let Some(res) = check_token(&form.token) {
+        return res;
+    }
+    log::info!("[ADMIN] Client: {} | stopping the server", addr);
+    state.shutdown.stop("Stopped from the admin panel");
+    render(&state, &headers, true)
+}
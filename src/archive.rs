@@ -0,0 +1,489 @@
+use crate::{
+    access::StreamGuard, auth::User, errors::error_response, guarded_body, mounts::Mounts,
+    read_rows, safe_dir, symlinks, visibility::Anonymous, AppState, FileRow, Located,
+};
+use axum::{
+    extract::{ConnectInfo, Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+    Extension,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Local};
+use percent_encoding::percent_decode_str;
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+// chunks written ahead of the client, the zip writer blocks once they are queued
+const WRITE_AHEAD: usize = 4;
+
+// streams the folder as a zip, the archive is written while the client downloads it
+pub async fn download_archive(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    let source = match archive_source(&state, &headers, user.as_deref(), &path) {
+        Ok(source) => source,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let guard = match state.stream_guard(addr) {
+        Ok(guard) => guard,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+
+    log::info!(
+        "[ARCHIVE] Client: {} | User: {} | {}",
+        addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
+        source
+    );
+    let name = source.name().to_string();
+    stream_archive(state, source, path, None, anonymous.is_some(), &name, guard)
+}
+
+// streams the entries of the folder selected in the listing, sent as repeated name fields
+pub async fn download_selection(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+    body: String,
+) -> Response {
+    let folder = path.map_or(String::new(), |AxumPath(path)| path);
+    // names are matched against the listing, anything else is ignored
+    let names: Vec<String> = form_values(&body, "name");
+    if names.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "No file selected");
+    }
+
+    let source = match archive_source(&state, &headers, user.as_deref(), &folder) {
+        Ok(source) => source,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let guard = match state.stream_guard(addr) {
+        Ok(guard) => guard,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+
+    log::info!(
+        "[ARCHIVE] Client: {} | User: {} | {} entries of {}",
+        addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
+        names.len(),
+        source
+    );
+    let name = format!("{}-selection", source.name());
+    stream_archive(
+        state,
+        source,
+        folder,
+        Some(names),
+        anonymous.is_some(),
+        &name,
+        guard,
+    )
+}
+
+// what an archive request packs
+enum Source {
+    // the canonical root and folder
+    Folder(PathBuf, PathBuf),
+    // the --folder mounts, each packed as a folder below its own root
+    Mounts(Arc<Mounts>),
+}
+
+impl Source {
+    // the name of the zip
+    fn name(&self) -> &str {
+        match self {
+            Source::Folder(_, dir) => dir.file_name().and_then(|n| n.to_str()).unwrap_or("files"),
+            Source::Mounts(_) => "files",
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Folder(_, dir) => write!(f, "{}", dir.display()),
+            Source::Mounts(_) => write!(f, "all folders"),
+        }
+    }
+}
+
+fn archive_source(
+    state: &AppState,
+    headers: &HeaderMap,
+    user: Option<&User>,
+    path: &str,
+) -> Result<Source, (StatusCode, String)> {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return Err((StatusCode::BAD_REQUEST, "Invalid folder path".to_string()));
+    }
+    if !state.within_depth(path, 0) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Folder is deeper than the allowed browse depth".to_string(),
+        ));
+    }
+    if state.exclude.matches(path) {
+        return Err((StatusCode::NOT_FOUND, "Folder not found".to_string()));
+    }
+
+    let (root, below) = match state.locate(headers, user, path)? {
+        Located::Disk(root, below) => (root, below),
+        Located::Mounts(_) => {
+            return Ok(Source::Mounts(state.mounts.clone().unwrap_or_default()));
+        }
+        Located::Packed(..) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                "Folders of the archive cannot be downloaded as a zip".to_string(),
+            ));
+        }
+    };
+    let root = root.canonicalize().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to access root".to_string(),
+        )
+    })?;
+    let dir = safe_dir(&root, &root.join(below))?;
+    Ok(Source::Folder(root, dir))
+}
+
+// decoded values of a urlencoded form field that can be repeated
+fn form_values(body: &str, field: &str) -> Vec<String> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(name, _)| *name == field)
+        .map(|(_, value)| {
+            percent_decode_str(&value.replace('+', " "))
+                .decode_utf8_lossy()
+                .to_string()
+        })
+        .collect()
+}
+
+fn stream_archive(
+    state: AppState,
+    source: Source,
+    folder: String,
+    selection: Option<Vec<String>>,
+    // only the public entries go into the zip of a visitor without a login
+    anonymous: bool,
+    name: &str,
+    guard: Option<StreamGuard>,
+) -> Response {
+    let shutdown = state.shutdown.clone();
+    let (tx, rx) = mpsc::channel(WRITE_AHEAD);
+    tokio::task::spawn_blocking(move || {
+        let errors = tx.clone();
+        let writer = ChannelWriter::new(tx);
+        let result = write_archive(&state, &source, &folder, selection, anonymous, writer);
+        if let Err(err) = result {
+            // the client going away shows up as a closed channel, nothing to report then
+            if !errors.is_closed() {
+                log::error!("cannot write archive of {}\n{}", source, err);
+                let _ = errors.blocking_send(Err(io::Error::other(err)));
+            }
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    let mut res = Response::new(guarded_body(stream, guard));
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    let disposition = format!("attachment; filename=\"{}.zip\"", name);
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    shutdown.counted(res)
+}
+
+fn write_archive(
+    state: &AppState,
+    source: &Source,
+    folder: &str,
+    selection: Option<Vec<String>>,
+    anonymous: bool,
+    writer: ChannelWriter,
+) -> zip::result::ZipResult<()> {
+    let mut rows = match source {
+        Source::Folder(root, dir) => read_rows(root, dir)?,
+        Source::Mounts(mounts) => mounts.rows(),
+    };
+    state.shared_rows(&mut rows, folder, anonymous);
+    if let Some(names) = selection {
+        rows.retain(|row| names.contains(&row.name));
+    }
+
+    let mut zip = ZipWriter::new_stream(writer);
+    match source {
+        Source::Folder(root, dir) => {
+            let mut walk = Walk {
+                state,
+                root,
+                seen: HashSet::from([dir.clone()]),
+                anonymous,
+            };
+            walk.add_rows(&mut zip, dir, folder, "", rows)?;
+        }
+        Source::Mounts(mounts) => {
+            let mut walk = Walk {
+                state,
+                root: Path::new(""),
+                seen: HashSet::new(),
+                anonymous,
+            };
+            for row in rows {
+                let Some(mount) = mounts.get(&row.name) else {
+                    continue;
+                };
+                walk.root = mount;
+                walk.add_folder(&mut zip, mount, &row.name, &row.name, options(&row))?;
+            }
+        }
+    }
+    zip.finish()?.into_inner().flush()?;
+    Ok(())
+}
+
+// the modification time of the entry, where zip can hold it
+fn options(row: &FileRow) -> SimpleFileOptions {
+    let options = SimpleFileOptions::default();
+    match row.modified {
+        Some(modified) => match DateTime::<Local>::from(modified).naive_local().try_into() {
+            Ok(time) => options.last_modified_time(time),
+            Err(_) => options,
+        },
+        None => options,
+    }
+}
+
+struct Walk<'a> {
+    state: &'a AppState,
+    root: &'a Path,
+    // folders reached again through a link are added once
+    seen: HashSet<PathBuf>,
+    anonymous: bool,
+}
+
+impl Walk<'_> {
+    // adds the shared entries of a folder, the same ones the listing shows
+    fn add_rows<W: Write>(
+        &mut self,
+        zip: &mut ZipWriter<zip::write::StreamWriter<W>>,
+        dir: &Path,
+        folder: &str,
+        prefix: &str,
+        rows: Vec<FileRow>,
+    ) -> zip::result::ZipResult<()> {
+        for row in rows {
+            let source = dir.join(&row.name);
+            let entry = format!("{}{}", prefix, row.name);
+            let options = options(&row);
+
+            if row.is_dir {
+                let sub_folder = format!("{}/{}", folder, row.name);
+                self.add_folder(zip, &source, &sub_folder, &entry, options)?;
+                continue;
+            }
+
+            // links are only followed as --follow-symlinks allows
+            let Some(file_path) = shared_file(self.root, &source) else {
+                continue;
+            };
+            let mut file = match fs::File::open(&file_path) {
+                Ok(file) => file,
+                Err(err) => {
+                    log::warn!("[ARCHIVE] skipping {}: {}", file_path.display(), err);
+                    continue;
+                }
+            };
+            let options = options
+                .compression_method(compression_for(&file_path))
+                .large_file(row.size >= u32::MAX as u64);
+            zip.start_file(entry, options)?;
+            io::copy(&mut file, zip)?;
+        }
+        Ok(())
+    }
+
+    // adds a folder as `entry` with its shared entries, `folder` is its path below the root
+    fn add_folder<W: Write>(
+        &mut self,
+        zip: &mut ZipWriter<zip::write::StreamWriter<W>>,
+        source: &Path,
+        folder: &str,
+        entry: &str,
+        options: SimpleFileOptions,
+    ) -> zip::result::ZipResult<()> {
+        let Ok(source) = source.canonicalize() else {
+            return Ok(());
+        };
+        if !self.seen.insert(source.clone()) {
+            log::warn!("[ARCHIVE] skipping {}, already added", source.display());
+            return Ok(());
+        }
+        zip.add_directory(format!("{}/", entry), options)?;
+        let mut rows = read_rows(self.root, &source)?;
+        self.state.shared_rows(&mut rows, folder, self.anonymous);
+        self.add_rows(zip, &source, folder, &format!("{}/", entry), rows)
+    }
+}
+
+fn shared_file(root: &Path, path: &Path) -> Option<PathBuf> {
+    let canonical = symlinks::resolve(root, path, "File").ok()?;
+    canonical.is_file().then_some(canonical)
+}
+
+// media and archives are already compressed, deflating them only costs time
+fn compression_for(path: &Path) -> CompressionMethod {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let compressed = matches!(mime.type_().as_str(), "image" | "video" | "audio")
+        || ["zip", "gzip", "x-7z-compressed", "x-xz", "x-bzip2", "zstd"]
+            .contains(&mime.subtype().as_str());
+    if compressed {
+        CompressionMethod::Stored
+    } else {
+        CompressionMethod::Deflated
+    }
+}
+
+// collects the zip output in chunks and hands them to the response body
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn new(tx: mpsc::Sender<io::Result<Bytes>>) -> Self {
+        ChannelWriter {
+            tx,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(Bytes::from(chunk)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    // waits for the writer thread to get to `count` chunks, false when it does not in time
+    fn reaches(written: &AtomicUsize, count: usize) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while written.load(Ordering::SeqCst) < count {
+            if Instant::now() > deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        true
+    }
+
+    #[test]
+    fn writer_waits_for_a_slow_client() {
+        let (tx, mut rx) = mpsc::channel(WRITE_AHEAD);
+        let written = Arc::new(AtomicUsize::new(0));
+        let total = WRITE_AHEAD * 3;
+        let writer = {
+            let written = written.clone();
+            thread::spawn(move || {
+                let mut writer = ChannelWriter::new(tx);
+                for _ in 0..total {
+                    writer.write_all(&[7; CHUNK_SIZE])?;
+                    written.fetch_add(1, Ordering::SeqCst);
+                }
+                writer.flush()
+            })
+        };
+
+        // nothing is read, the writer stops once the channel holds WRITE_AHEAD chunks
+        assert!(reaches(&written, WRITE_AHEAD));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(written.load(Ordering::SeqCst), WRITE_AHEAD);
+
+        // every chunk taken lets one more be written
+        let chunk = rx.blocking_recv().unwrap().unwrap();
+        assert_eq!(chunk.len(), CHUNK_SIZE);
+        assert!(reaches(&written, WRITE_AHEAD + 1));
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(written.load(Ordering::SeqCst), WRITE_AHEAD + 1);
+
+        let mut received = chunk.len();
+        while let Some(chunk) = rx.blocking_recv() {
+            received += chunk.unwrap().len();
+        }
+        writer.join().unwrap().unwrap();
+        assert_eq!(received, total * CHUNK_SIZE);
+    }
+
+    #[test]
+    fn writer_stops_when_the_client_goes_away() {
+        let (tx, rx) = mpsc::channel(WRITE_AHEAD);
+        let writer = thread::spawn(move || {
+            let mut writer = ChannelWriter::new(tx);
+            loop {
+                if let Err(err) = writer.write_all(&[7; CHUNK_SIZE]) {
+                    return err;
+                }
+            }
+        });
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        assert_eq!(writer.join().unwrap().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{errors::error_response, templates};
+use axum::{
+    extract::Path as AxumPath,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use std::{fs, io, path::Path};
+
+// stylesheet, scripts and icon of the pages under /assets, built into the binary like the
+// templates
+pub const BUILT_IN: [(&str, &[u8]); 10] = [
+    ("style.css", include_bytes!("../assets/style.css")),
+    ("theme.js", include_bytes!("../assets/theme.js")),
+    ("filter.js", include_bytes!("../assets/filter.js")),
+    ("gallery.js", include_bytes!("../assets/gallery.js")),
+    ("player.js", include_bytes!("../assets/player.js")),
+    ("meta.js", include_bytes!("../assets/meta.js")),
+    ("qr.js", include_bytes!("../assets/qr.js")),
+    ("live.js", include_bytes!("../assets/live.js")),
+    ("delete.js", include_bytes!("../assets/delete.js")),
+    ("favicon.svg", include_bytes!("../assets/favicon.svg")),
+];
+
+// the folder exported pages load their assets from, next to the top index.html
+pub const EXPORT_DIR: &str = "_assets";
+
+// served without a login, the login page needs them too
+pub fn routes<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new().route("/assets/{*name}", get(asset))
+}
+
+pub async fn asset(AxumPath(name): AxumPath<String>) -> Response {
+    let Some(content) = read(&name) else {
+        return error_response(StatusCode::NOT_FOUND, "Asset not found");
+    };
+    let mime = mime_guess::from_path(&name).first_or_octet_stream();
+    let mut res = content.into_response();
+    if let Ok(value) = HeaderValue::from_str(mime.as_ref()) {
+        res.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    // overrides can change while the server runs, so caches check back after a while
+    res.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=300"),
+    );
+    res
+}
+
+// a file in the assets folder of --templates replaces the built-in one and can add new ones,
+// only plain file names are looked up there
+fn read(name: &str) -> Option<Vec<u8>> {
+    let plain = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+        && !name.contains("..");
+    if plain && let Ok(content) = fs::read(templates::dir().join("assets").join(name)) {
+        return Some(content);
+    }
+    BUILT_IN
+        .iter()
+        .find(|(built_in, _)| *built_in == name)
+        .map(|(_, content)| content.to_vec())
+}
+
+pub fn exists(name: &str) -> bool {
+    read(name).is_some()
+}
+
+// writes the assets next to an exported site, the overrides and added files included
+pub fn export(out: &Path) -> io::Result<()> {
+    let dir = out.join(EXPORT_DIR);
+    fs::create_dir_all(&dir)?;
+    let mut names: Vec<String> = BUILT_IN.iter().map(|(name, _)| name.to_string()).collect();
+    if let Ok(entries) = fs::read_dir(templates::dir().join("assets")) {
+        names.extend(
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().into_string().ok()),
+        );
+    }
+    for name in names {
+        if let Some(content) = read(&name) {
+            fs::write(dir.join(&name), content)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use std::path::Path;
+
+// the types browsers play, mime_guess has no name for some of them
+pub fn mime(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "mp3" => Some("audio/mpeg"),
+        "m4a" | "aac" => Some("audio/mp4"),
+        "ogg" | "oga" => Some("audio/ogg"),
+        "opus" => Some("audio/opus"),
+        "flac" => Some("audio/flac"),
+        "wav" => Some("audio/wav"),
+        _ => None,
+    }
+}
+
+// the tracks the player of a listing goes through
+pub fn is_audio(name: &str) -> bool {
+    mime(Path::new(name)).is_some()
+}
@@ -0,0 +1,478 @@
+#[cfg(all(unix, feature = "pam"))]
+use crate::system_auth::SystemAuth;
+use crate::{
+    auth_log, base_path,
+    errors::error_response,
+    htpasswd::Htpasswd,
+    ldap::LdapAuth,
+    oidc::Oidc,
+    signed_url::UrlSigner,
+    templates::{self, Theme},
+    users::Users,
+    utils,
+    visibility::{Anonymous, PublicAccess},
+    webhook::{self, Kind},
+};
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Form, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const SESSION_COOKIE: &str = "file_serve_session";
+const SESSION_LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+struct Session {
+    user: User,
+    started: Instant,
+}
+
+// login state shared by the auth middleware and the /auth routes
+pub struct Auth {
+    oidc: Option<Oidc>,
+    ldap: Option<LdapAuth>,
+    htpasswd: Option<Htpasswd>,
+    users: Option<Users>,
+    #[cfg(all(unix, feature = "pam"))]
+    system: Option<SystemAuth>,
+    url_signer: Option<UrlSigner>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+// user authenticated for the request, available as a request extension
+#[derive(Clone)]
+pub struct User {
+    pub name: String,
+    pub roles: Vec<String>,
+    // folder served to this user instead of the shared root
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct LoginParams {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.roles.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} [{}]", self.name, self.roles.join(","))
+        }
+    }
+}
+
+impl Auth {
+    pub fn new(oidc: Option<Oidc>, ldap: Option<LdapAuth>, htpasswd: Option<Htpasswd>) -> Self {
+        Auth {
+            oidc,
+            ldap,
+            htpasswd,
+            users: None,
+            #[cfg(all(unix, feature = "pam"))]
+            system: None,
+            url_signer: None,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[cfg(all(unix, feature = "pam"))]
+    pub fn with_system_auth(mut self, system: SystemAuth) -> Self {
+        self.system = Some(system);
+        self
+    }
+
+    // accounts of --auth, clients without credentials get a Basic challenge
+    pub fn with_users(mut self, users: Users) -> Self {
+        self.users = Some(users);
+        self
+    }
+
+    // signed download links are let through without a session
+    pub fn with_url_signer(mut self, signer: UrlSigner) -> Self {
+        self.url_signer = Some(signer);
+        self
+    }
+
+    fn has_password_login(&self) -> bool {
+        #[cfg(all(unix, feature = "pam"))]
+        if self.system.is_some() {
+            return true;
+        }
+        self.ldap.is_some() || self.htpasswd.is_some() || self.users.is_some()
+    }
+
+    // the browser asks for the --auth credentials itself, unless single sign-on is offered
+    fn basic_challenge(&self) -> bool {
+        self.users.is_some() && self.oidc.is_none()
+    }
+
+    // checks the credentials with the configured password backend
+    async fn check_password(&self, name: &str, password: &str) -> Result<User, String> {
+        if let Some(ldap) = &self.ldap {
+            let roles = ldap.authenticate(name, password).await?;
+            return Ok(User {
+                name: name.to_string(),
+                roles,
+                root: None,
+            });
+        }
+        if let Some(htpasswd) = &self.htpasswd {
+            htpasswd.authenticate(name, password).await?;
+            return Ok(User {
+                name: name.to_string(),
+                roles: Vec::new(),
+                root: None,
+            });
+        }
+        if let Some(users) = &self.users {
+            users.authenticate(name, password)?;
+            return Ok(User {
+                name: name.to_string(),
+                roles: Vec::new(),
+                root: None,
+            });
+        }
+        #[cfg(all(unix, feature = "pam"))]
+        if let Some(system) = &self.system {
+            let root = system.authenticate(name, password).await?;
+            return Ok(User {
+                name: name.to_string(),
+                roles: Vec::new(),
+                root,
+            });
+        }
+        Err("password login is not enabled".to_string())
+    }
+
+    fn session_user(&self, headers: &HeaderMap) -> Option<User> {
+        let id = session_id(headers)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.started.elapsed() < SESSION_LIFETIME);
+        sessions.get(id).map(|session| session.user.clone())
+    }
+
+    // creates a session for the user, returns the Set-Cookie value
+    fn start_session(&self, user: User) -> String {
+        let id = utils::random_token();
+        self.sessions.lock().unwrap().insert(
+            id.clone(),
+            Session {
+                user,
+                started: Instant::now(),
+            },
+        );
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+            SESSION_COOKIE,
+            id,
+            SESSION_LIFETIME.as_secs()
+        )
+    }
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+// only local absolute paths are accepted as return pages, anything else goes home
+fn local_path(next: Option<String>) -> String {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") => next,
+        _ => "/".to_string(),
+    }
+}
+
+pub fn routes<S: Clone + Send + Sync + 'static>(auth: Arc<Auth>) -> Router<S> {
+    Router::new()
+        .route("/auth/login", get(login).post(password_login))
+        .route("/auth/sso", get(sso_login))
+        .route("/auth/callback", get(callback))
+        .route("/auth/logout", get(logout))
+        .with_state(auth)
+}
+
+// user name and password of an Authorization: Basic header
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    Some((name.to_string(), password.to_string()))
+}
+
+fn basic_challenge(message: &str) -> Response {
+    let mut res = error_response(StatusCode::UNAUTHORIZED, message);
+    res.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"file-serve\""),
+    );
+    res
+}
+
+// sends clients without a session to the login page, scripts can send Basic credentials instead
+pub async fn require_login(
+    State(auth): State<Arc<Auth>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if let Some(signer) = &auth.url_signer {
+        let path = percent_decode_str(req.uri().path()).decode_utf8_lossy();
+        if path.starts_with("/download/") && signer.verify(&path, req.uri().query()) {
+            return next.run(req).await;
+        }
+    }
+    // public folders, a logged in user still gets its own root
+    if req.extensions().get::<PublicAccess>().is_some() {
+        match auth.session_user(req.headers()) {
+            Some(user) => {
+                req.extensions_mut().insert(user);
+            }
+            None => {
+                req.extensions_mut().insert(Anonymous);
+            }
+        }
+        return next.run(req).await;
+    }
+
+    let credentials = auth
+        .has_password_login()
+        .then(|| basic_credentials(req.headers()))
+        .flatten();
+    let user = match (auth.session_user(req.headers()), credentials) {
+        (Some(user), _) => Some(user),
+        (None, Some((name, password))) => match auth.check_password(&name, &password).await {
+            Ok(user) => Some(user),
+            Err(err) => {
+                log::warn!("[AUTH] login of {} from {} failed: {}", name, addr, err);
+                auth_log::failed(addr.ip(), Some(&name));
+                webhook::notify(Kind::AuthFailed, addr.ip(), None, None, Some(&name));
+                return basic_challenge("Invalid user name or password.");
+            }
+        },
+        (None, None) => None,
+    };
+
+    match user {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        None if auth.basic_challenge() => basic_challenge("Login required."),
+        // file managers mounting /dav cannot fill in the login form
+        None if auth.has_password_login() && req.uri().path().starts_with("/dav") => {
+            basic_challenge("Login required.")
+        }
+        None => {
+            let target = req
+                .uri()
+                .path_and_query()
+                .map_or("/", |p| p.as_str())
+                .to_string();
+            let login = base_path::link(&format!(
+                "/auth/login?next={}",
+                utf8_percent_encode(&target, NON_ALPHANUMERIC)
+            ));
+            Redirect::to(&login).into_response()
+        }
+    }
+}
+
+// fields of the login page
+#[derive(Serialize)]
+struct LoginPage<'a> {
+    message: &'a str,
+    next: &'a str,
+    // link to the single sign-on provider, when one is configured
+    sso_href: Option<String>,
+}
+
+fn login_page(auth: &Auth, next: &str, message: &str, theme: Theme) -> Html<String> {
+    let page = LoginPage {
+        message,
+        next,
+        sso_href: auth.oidc.as_ref().map(|_| {
+            base_path::link(&format!(
+                "/auth/sso?next={}",
+                utf8_percent_encode(next, NON_ALPHANUMERIC)
+            ))
+        }),
+    };
+    match templates::render("login.html", theme, &page) {
+        Ok(html) => Html(html),
+        Err(e) => {
+            log::error!("Error loading login template: {}", e);
+            Html(format!(
+                "<h1>Error</h1><p>Failed to load login template: {}</p>",
+                utils::html_escape(&e.to_string())
+            ))
+        }
+    }
+}
+
+// password form when a password backend is configured, the provider login otherwise
+async fn login(
+    State(auth): State<Arc<Auth>>,
+    headers: HeaderMap,
+    Query(params): Query<LoginParams>,
+) -> Response {
+    let next = local_path(params.next);
+    match &auth.oidc {
+        Some(oidc) if !auth.has_password_login() => {
+            Redirect::to(&oidc.authorization_url(next)).into_response()
+        }
+        _ => login_page(&auth, &next, "", Theme::of(&headers)).into_response(),
+    }
+}
+
+async fn sso_login(State(auth): State<Arc<Auth>>, Query(params): Query<LoginParams>) -> Response {
+    match &auth.oidc {
+        Some(oidc) => {
+            Redirect::to(&oidc.authorization_url(local_path(params.next))).into_response()
+        }
+        None => error_response(StatusCode::NOT_FOUND, "Single sign-on is not enabled."),
+    }
+}
+
+async fn password_login(
+    State(auth): State<Arc<Auth>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let next = local_path(form.next);
+    if !auth.has_password_login() {
+        return error_response(StatusCode::NOT_FOUND, "Password login is not enabled.");
+    }
+
+    match auth.check_password(&form.username, &form.password).await {
+        Ok(user) => {
+            log::info!("[AUTH] {} logged in from {}", user, addr);
+            session_redirect(&auth, user, &next)
+        }
+        Err(err) => {
+            log::warn!(
+                "[AUTH] login of {} from {} failed: {}",
+                form.username,
+                addr,
+                err
+            );
+            auth_log::failed(addr.ip(), Some(&form.username));
+            webhook::notify(
+                Kind::AuthFailed,
+                addr.ip(),
+                None,
+                None,
+                Some(&form.username),
+            );
+            (
+                StatusCode::UNAUTHORIZED,
+                login_page(
+                    &auth,
+                    &next,
+                    "Invalid user name or password.",
+                    Theme::of(&headers),
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+// starts the session and sends the client back to the page it asked for, `next` is a path of
+// the app below --base-path
+fn session_redirect(auth: &Auth, user: User, next: &str) -> Response {
+    let cookie = auth.start_session(user);
+    let mut res = Redirect::to(&base_path::link(next)).into_response();
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        res.headers_mut().insert(header::SET_COOKIE, value);
+    }
+    res
+}
+
+async fn callback(
+    State(auth): State<Arc<Auth>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    if let Some(error) = params.error {
+        log::warn!("[AUTH] provider refused login from {}: {}", addr, error);
+        return error_response(StatusCode::UNAUTHORIZED, "The login was refused.");
+    }
+    let (Some(code), Some(state)) = (params.code, params.state) else {
+        return error_response(StatusCode::BAD_REQUEST, "Missing login code.");
+    };
+
+    let Some(oidc) = &auth.oidc else {
+        return error_response(StatusCode::NOT_FOUND, "Single sign-on is not enabled.");
+    };
+
+    match oidc.complete(&code, &state).await {
+        Ok((name, next)) => {
+            log::info!("[AUTH] {} logged in from {}", name, addr);
+            let user = User {
+                name,
+                roles: Vec::new(),
+                root: None,
+            };
+            session_redirect(&auth, user, &next)
+        }
+        Err(err) => {
+            log::warn!("[AUTH] login from {} failed: {}", addr, err);
+            auth_log::failed(addr.ip(), None);
+            error_response(
+                StatusCode::UNAUTHORIZED,
+                "The login could not be completed.",
+            )
+        }
+    }
+}
+
+async fn logout(State(auth): State<Arc<Auth>>, headers: HeaderMap) -> Response {
+    if let Some(id) = session_id(&headers) {
+        auth.sessions.lock().unwrap().remove(id);
+    }
+    let mut res = Redirect::to(&base_path::link("/")).into_response();
+    res.headers_mut().insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&format!("{}=; Path=/; Max-Age=0", SESSION_COOKIE)).unwrap(),
+    );
+    res
+}
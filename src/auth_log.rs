@@ -0,0 +1,41 @@
+use chrono::{Local, SecondsFormat};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    net::IpAddr,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+// --auth-log FILE, a line per failed login for fail2ban or crowdsec to ban the address:
+//   2026-10-16T14:03:11+02:00 file-serve: authentication failure from 192.0.2.7 user="alice"
+// the format does not change between versions, the jails match it with a single regex
+static FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+// the file is only appended to, logrotate can move it with copytruncate
+pub fn enable(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let _ = FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+// `user` is the name that was tried, None where there is none like a token or a refused single
+// sign-on. quotes and control characters in it are escaped so a name cannot add a line
+pub fn failed(client: IpAddr, user: Option<&str>) {
+    let Some(file) = FILE.get() else {
+        return;
+    };
+    let line = format!(
+        "{} file-serve: authentication failure from {} user=\"{}\"\n",
+        Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+        client,
+        user.unwrap_or("-").escape_debug()
+    );
+    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(err) = file.write_all(line.as_bytes()) {
+        log::warn!("[AUTH] cannot write the auth log: {}", err);
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::OnceLock;
+
+// --base-path, the path a reverse proxy serves the app below, e.g. /files for
+// `location /files/`. routes are nested below it and every link starts with it
+static BASE_PATH: OnceLock<String> = OnceLock::new();
+
+// /files, files/ and /files/ all become /files, / alone is no prefix at all. the segments take
+// the characters URLs need no escape for
+pub fn set(path: &str) -> Result<(), String> {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(());
+    }
+    let valid = trimmed.split('/').all(|part| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+    });
+    if !valid {
+        return Err(format!("invalid base path {}", path));
+    }
+    let _ = BASE_PATH.set(format!("/{}", trimmed));
+    Ok(())
+}
+
+// the prefix without a trailing slash, empty without --base-path
+pub fn get() -> &'static str {
+    BASE_PATH.get().map_or("", String::as_str)
+}
+
+// an absolute path of the app as the browser has to ask for it
+pub fn link(path: &str) -> String {
+    format!("{}{}", get(), path)
+}
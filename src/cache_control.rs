@@ -0,0 +1,55 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+// marks a response sending the content of a served file, the others are pages of the server
+#[derive(Clone)]
+pub struct FileContent;
+
+// files are kept by the browser for a while and checked with their ETag afterwards
+const FILES: &str = "private, max-age=300";
+// listings change as files come and go, they are checked every time
+const PAGES: &str = "no-cache";
+
+// the Cache-Control of the responses without one of their own, --cache-control replaces the
+// one of the files
+pub struct CachePolicy {
+    files: HeaderValue,
+    pages: HeaderValue,
+}
+
+impl CachePolicy {
+    pub fn new(files: Option<&str>) -> Result<Self, String> {
+        let files = match files {
+            Some(value) => HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid Cache-Control value {}", value))?,
+            None => HeaderValue::from_static(FILES),
+        };
+        Ok(CachePolicy {
+            files,
+            pages: HeaderValue::from_static(PAGES),
+        })
+    }
+}
+
+// errors are left alone, so is a header the handler set itself like the one of the assets
+pub async fn apply(State(policy): State<Arc<CachePolicy>>, req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+    let status = res.status();
+    if !(status.is_success() || status.is_redirection())
+        || res.headers().contains_key(header::CACHE_CONTROL)
+    {
+        return res;
+    }
+    let value = if res.extensions().get::<FileContent>().is_some() {
+        policy.files.clone()
+    } else {
+        policy.pages.clone()
+    };
+    res.headers_mut().insert(header::CACHE_CONTROL, value);
+    res
+}
@@ -0,0 +1,123 @@
+use crate::symlinks;
+use axum::{
+    body::HttpBody,
+    http::{header, HeaderMap, HeaderValue, Response, StatusCode},
+};
+use std::{
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate},
+    CompressionLayer,
+};
+
+// the copies looked for next to a file, in the order they are preferred
+const COPIES: [(&str, &str); 2] = [("br", "br"), ("gz", "gzip")];
+
+// gzip or brotli, whichever the client prefers, for the pages, the JSON of the API and text
+// files. everything else is mostly compressed already, --no-compression turns it off
+pub fn layer() -> CompressionLayer<Compressible> {
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(Compressible)
+}
+
+#[derive(Clone, Copy)]
+pub struct Compressible;
+
+impl Predicate for Compressible {
+    fn should_compress<B>(&self, res: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        // the offsets of a part are the ones of the file, not of a compressed copy
+        if res.status() == StatusCode::PARTIAL_CONTENT
+            || res.headers().contains_key(header::CONTENT_RANGE)
+        {
+            return false;
+        }
+        let Some(mime) = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+        else {
+            return false;
+        };
+        let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+        let text = mime.starts_with("text/")
+            || mime.ends_with("+xml")
+            || mime.ends_with("+json")
+            || matches!(
+                mime.as_str(),
+                "application/json"
+                    | "application/javascript"
+                    | "application/xml"
+                    | "application/wasm"
+                    | "application/vnd.apple.mpegurl"
+            );
+        // leaves out tiny bodies and event streams
+        text && DefaultPredicate::new().should_compress(res)
+    }
+}
+
+// a compressed copy of a file made beforehand, app.js.br or app.js.gz next to app.js
+pub struct Precompressed {
+    pub path: PathBuf,
+    pub meta: Metadata,
+    pub encoding: &'static str,
+}
+
+// the copies of `file` inside the root, brotli first. a copy older than the file is left out,
+// it would send a previous version
+pub fn copies(root: &Path, file: &Path, meta: &Metadata) -> Vec<Precompressed> {
+    let modified = meta.modified().ok();
+    COPIES
+        .iter()
+        .filter_map(|&(extension, encoding)| {
+            let mut name = file.as_os_str().to_owned();
+            name.push(".");
+            name.push(extension);
+            let path = symlinks::resolve(root, Path::new(&name), "File").ok()?;
+            let meta = fs::metadata(&path).ok()?;
+            (meta.is_file() && meta.modified().ok() >= modified).then_some(Precompressed {
+                path,
+                meta,
+                encoding,
+            })
+        })
+        .collect()
+}
+
+// whether Accept-Encoding lists the encoding without q=0
+pub fn accepts(headers: &HeaderMap, encoding: &str) -> bool {
+    let Some(accepted) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|h| h.to_str().ok())
+    else {
+        return false;
+    };
+    accepted.split(',').any(|item| {
+        let mut params = item.split(';').map(str::trim);
+        let name = params.next().unwrap_or("");
+        let refused = params.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q <= 0.0)
+        });
+        (name.eq_ignore_ascii_case(encoding) || name == "*") && !refused
+    })
+}
+
+// the headers of a file sent as a copy, `vary` when copies exist so caches keep one per encoding
+pub fn mark<B>(res: &mut Response<B>, encoding: Option<&'static str>, vary: bool) {
+    let headers = res.headers_mut();
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    if vary {
+        headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cache_control::FileContent;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::Metadata,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// the ETag and Last-Modified of a file, sent with it so browsers can ask whether the copy they
+// have is still current instead of downloading it again
+pub struct Validators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    // the tag changes with the inode, the size and the modification time of the file
+    pub fn of(meta: &Metadata) -> Self {
+        let modified = meta.modified().ok();
+        let nanos = modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        Validators {
+            etag: format!("\"{:x}-{:x}-{:x}\"", inode(meta), meta.len(), nanos),
+            last_modified: modified,
+        }
+    }
+
+    // whether the copy of the client is current, If-None-Match wins over If-Modified-Since
+    pub fn fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(tags) = header_str(headers, header::IF_NONE_MATCH) {
+            // the weak comparison, a W/ tag matches too
+            return tags
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag);
+        }
+        match (
+            self.last_modified,
+            since(headers, header::IF_MODIFIED_SINCE),
+        ) {
+            (Some(modified), Some(since)) => seconds(modified) <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    // whether a Range asks for a part of this version of the file, with If-Range naming another
+    // one the whole file is sent
+    pub fn range_applies(&self, headers: &HeaderMap) -> bool {
+        let Some(if_range) = header_str(headers, header::IF_RANGE) else {
+            return true;
+        };
+        if if_range.starts_with('"') {
+            return if_range == self.etag;
+        }
+        match (self.last_modified, since(headers, header::IF_RANGE)) {
+            (Some(modified), Some(since)) => seconds(modified) == since.timestamp(),
+            _ => false,
+        }
+    }
+
+    pub fn apply(&self, res: &mut Response) {
+        let headers = res.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .and_then(|modified| HeaderValue::from_str(&http_date(modified)).ok())
+        {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+
+    // 304, the client keeps its copy
+    pub fn not_modified(&self) -> Response {
+        let mut res = StatusCode::NOT_MODIFIED.into_response();
+        res.extensions_mut().insert(FileContent);
+        self.apply(&mut res);
+        res
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+}
+
+fn since(headers: &HeaderMap, name: header::HeaderName) -> Option<DateTime<chrono::FixedOffset>> {
+    DateTime::parse_from_rfc2822(header_str(headers, name)?).ok()
+}
+
+// HTTP dates have no fractions of a second
+fn seconds(time: SystemTime) -> i64 {
+    DateTime::<Utc>::from(time).timestamp()
+}
+
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+#[cfg(unix)]
+fn inode(meta: &Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(meta)
+}
+
+// other systems have no inode in the metadata, the size and time tell versions apart
+#[cfg(not(unix))]
+fn inode(_meta: &Metadata) -> u64 {
+    0
+}
@@ -0,0 +1,133 @@
+use clap::{Arg, Command};
+use std::{env, error::Error, ffi::OsString, fs, path::PathBuf};
+use toml::Value;
+
+// the command line with the options of the config file in front, so flags given on the
+// command line win: --config FILE, or file-serve/config.toml below $XDG_CONFIG_HOME. the file
+// comes back as well when there was one, the log is not set up yet to tell about it
+pub fn args(
+    cli: &Command,
+    argv: Vec<OsString>,
+) -> Result<(Vec<OsString>, Option<PathBuf>), Box<dyn Error>> {
+    let (path, explicit) = match config_flag(&argv) {
+        Some(path) => (path, true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok((argv, None)),
+        },
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if !explicit && err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok((argv, None))
+        }
+        Err(err) => return Err(format!("cannot read {}: {}", path.display(), err).into()),
+    };
+    let table: toml::Table = text
+        .parse()
+        .map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+
+    let mut args = vec![argv[0].clone()];
+    for (key, value) in table {
+        let arg = cli
+            .get_arguments()
+            .filter(|arg| arg.get_id() != "config")
+            .find(|arg| named(arg, &key))
+            .ok_or_else(|| format!("unknown option {} in {}", key, path.display()))?;
+        // the command line replaces the file, repeated options and conflicting ones included
+        let overridden = given(arg, &argv[1..])
+            || cli
+                .get_arguments()
+                .any(|other| given(other, &argv[1..]) && conflicting(cli, arg, other));
+        if overridden {
+            continue;
+        }
+        let long = arg.get_long().unwrap_or(&key);
+        for value in values(long, value, arg.get_action().takes_values())? {
+            args.push(value.into());
+        }
+    }
+
+    args.extend(argv.into_iter().skip(1));
+    Ok((args, Some(path)))
+}
+
+fn config_flag(argv: &[OsString]) -> Option<PathBuf> {
+    let mut argv = argv.iter().skip(1);
+    while let Some(arg) = argv.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return argv.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("file-serve").join("config.toml"))
+}
+
+// whether the option is on the command line, by its long name, an alias or the short flag
+fn given(arg: &Arg, argv: &[OsString]) -> bool {
+    argv.iter().any(|token| {
+        let token = token.to_string_lossy();
+        if let Some(long) = token.strip_prefix("--") {
+            named(arg, long.split('=').next().unwrap_or(long))
+        } else if let Some(short) = token.strip_prefix('-') {
+            arg.get_short()
+                .is_some_and(|s| short.starts_with(s) && !short.is_empty())
+        } else {
+            false
+        }
+    })
+}
+
+// conflicts are declared on one of the two options only
+fn conflicting(cli: &Command, a: &Arg, b: &Arg) -> bool {
+    let declared = |x: &Arg, y: &Arg| {
+        cli.get_arg_conflicts_with(x)
+            .iter()
+            .any(|other| other.get_id() == y.get_id())
+    };
+    declared(a, b) || declared(b, a)
+}
+
+fn named(arg: &Arg, name: &str) -> bool {
+    arg.get_long() == Some(name)
+        || arg
+            .get_all_aliases()
+            .is_some_and(|aliases| aliases.contains(&name))
+}
+
+// a value becomes --key=value, an array one of them per element and true a bare flag
+fn values(key: &str, value: Value, takes_values: bool) -> Result<Vec<String>, String> {
+    let value = match value {
+        Value::Array(items) if takes_values => {
+            let mut args = Vec::new();
+            for item in items {
+                args.extend(values(key, item, takes_values)?);
+            }
+            return Ok(args);
+        }
+        Value::Boolean(set) if !takes_values => {
+            return Ok(if set {
+                vec![format!("--{}", key)]
+            } else {
+                Vec::new()
+            });
+        }
+        Value::String(value) if takes_values => value,
+        Value::Integer(value) if takes_values => value.to_string(),
+        Value::Float(value) if takes_values => value.to_string(),
+        _ if takes_values => return Err(format!("{} expects a value or a list of values", key)),
+        _ => return Err(format!("{} is a flag and expects true or false", key)),
+    };
+    Ok(vec![format!("--{}={}", key, value)])
+}
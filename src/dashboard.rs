@@ -0,0 +1,209 @@
+use crate::{logging, shutdown::Shutdown, transfers, utils};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+// how often the screen is drawn again
+const TICK: Duration = Duration::from_millis(500);
+// width of the progress bars
+const BAR: usize = 20;
+
+// --tui, a screen of the downloads running, their progress and speed, and the last lines of
+// the log in place of the banner. it runs on a thread of its own until the server stopped
+pub struct Dashboard {
+    done: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+pub fn start(serving: String, link: String, shutdown: Arc<Shutdown>) -> Dashboard {
+    let done = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let done = done.clone();
+        std::thread::spawn(move || {
+            let mut terminal = ratatui::init();
+            let result = run(&mut terminal, &serving, &link, &shutdown, &done);
+            ratatui::restore();
+            if let Err(err) = result {
+                log::error!("[TUI] the dashboard stopped: {}", err);
+            }
+        })
+    };
+    Dashboard { done, thread }
+}
+
+impl Dashboard {
+    // gives the terminal back once the server stopped
+    pub fn finish(self) {
+        self.done.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    serving: &str,
+    link: &str,
+    shutdown: &Shutdown,
+    done: &AtomicBool,
+) -> io::Result<()> {
+    let mut speeds = Speeds::default();
+    while !done.load(Ordering::Relaxed) {
+        let transfers = transfers::active();
+        let rates = speeds.update(&transfers);
+        terminal.draw(|frame| draw(frame, serving, link, &transfers, &rates))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        // the terminal is in raw mode, Ctrl+C arrives as a key instead of a signal
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && (key.code == KeyCode::Char('q')
+                || (key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)))
+        {
+            shutdown.stop("Stopped from the dashboard");
+        }
+    }
+    Ok(())
+}
+
+// bytes per second of every download since the screen was drawn last
+#[derive(Default)]
+struct Speeds {
+    sent: HashMap<u64, u64>,
+    at: Option<Instant>,
+}
+
+impl Speeds {
+    fn update(&mut self, transfers: &[transfers::Transfer]) -> HashMap<u64, u64> {
+        let now = Instant::now();
+        let elapsed = self
+            .at
+            .map_or(0.0, |at| now.duration_since(at).as_secs_f64());
+        let rates = transfers
+            .iter()
+            .map(|transfer| {
+                let before = self.sent.get(&transfer.id).copied().unwrap_or(0);
+                let rate = if elapsed > 0.0 {
+                    (transfer.sent.saturating_sub(before) as f64 / elapsed) as u64
+                } else {
+                    0
+                };
+                (transfer.id, rate)
+            })
+            .collect();
+        self.sent = transfers
+            .iter()
+            .map(|transfer| (transfer.id, transfer.sent))
+            .collect();
+        self.at = Some(now);
+        rates
+    }
+}
+
+fn draw(
+    frame: &mut Frame,
+    serving: &str,
+    link: &str,
+    transfers: &[transfers::Transfer],
+    rates: &HashMap<u64, u64>,
+) {
+    let [header, totals, downloads, log] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(12),
+    ])
+    .areas(frame.area());
+
+    let title = Paragraph::new(vec![
+        Line::from(serving.to_string()),
+        Line::from(link.to_string()).bold(),
+    ])
+    .block(Block::bordered().title(" file-serve, q to stop "));
+    frame.render_widget(title, header);
+
+    let clients = {
+        let mut clients: Vec<_> = transfers.iter().map(|t| t.client).collect();
+        clients.sort();
+        clients.dedup();
+        clients.len()
+    };
+    let speed: u64 = rates.values().sum();
+    let summary = Paragraph::new(Line::from(format!(
+        "{} downloads from {} clients, {} requests in progress, {}/s, {} served",
+        transfers.len(),
+        clients,
+        transfers::in_flight(),
+        utils::bytes_to_human_size(speed),
+        utils::bytes_to_human_size(transfers::served()),
+    )))
+    .block(Block::bordered());
+    frame.render_widget(summary, totals);
+
+    let rows = transfers.iter().map(|transfer| {
+        let progress = match transfer.total {
+            Some(total) if total > 0 => {
+                let done = (transfer.sent.min(total) as f64 / total as f64).clamp(0.0, 1.0);
+                let filled = (done * BAR as f64).round() as usize;
+                format!(
+                    "{}{} {:>3.0}%",
+                    "█".repeat(filled),
+                    "░".repeat(BAR - filled),
+                    done * 100.0
+                )
+            }
+            _ => utils::bytes_to_human_size(transfer.sent),
+        };
+        Row::new(vec![
+            transfer.client.to_string(),
+            transfer.path.clone(),
+            progress,
+            format!(
+                "{}/s",
+                utils::bytes_to_human_size(rates.get(&transfer.id).copied().unwrap_or(0))
+            ),
+            format!("{}s", transfer.started.elapsed().as_secs()),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Fill(1),
+            Constraint::Length(BAR as u16 + 5),
+            Constraint::Length(12),
+            Constraint::Length(7),
+        ],
+    )
+    .header(
+        Row::new(vec!["Client", "File", "Progress", "Speed", "Time"]).style(Style::new().bold()),
+    )
+    .block(Block::bordered().title(" Downloads "));
+    frame.render_widget(table, downloads);
+
+    // the lines that fit inside the border
+    let lines = logging::recent(log.height.saturating_sub(2) as usize)
+        .into_iter()
+        .map(Line::from)
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Log ")),
+        log,
+    );
+}
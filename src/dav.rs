@@ -0,0 +1,696 @@
+use crate::{
+    auth::User,
+    base_path, download_file,
+    errors::{error_response, io_error_status},
+    exclude,
+    mounts::Mounts,
+    packed::Packed,
+    read_rows, safe_dir, symlinks, utils,
+    visibility::Anonymous,
+    AppState, FileRow, Located,
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use percent_encoding::percent_decode_str;
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND";
+const ALLOW_WRITE: &str =
+    "OPTIONS, GET, HEAD, PROPFIND, PUT, MKCOL, DELETE, MOVE, COPY, LOCK, UNLOCK";
+
+// methods that only read, anything else changes the share
+pub fn is_read(method: &Method) -> bool {
+    matches!(method.as_str(), "OPTIONS" | "GET" | "HEAD" | "PROPFIND")
+}
+
+// WebDAV below /dav so file managers can mount the share, writes need --allow-write
+#[allow(clippy::too_many_arguments)]
+pub async fn handle(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
+    method: Method,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+    body: Body,
+) -> Response {
+    let path = path.map_or(String::new(), |AxumPath(path)| {
+        path.trim_end_matches('/').to_string()
+    });
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+    if state.exclude.matches(&path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+
+    let allow = if state.settings.write() {
+        ALLOW_WRITE
+    } else {
+        ALLOW
+    };
+    if !is_read(&method) {
+        if !state.settings.write() {
+            let mut res = error_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "The WebDAV share is read-only",
+            );
+            res.headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static(allow));
+            return res;
+        }
+        if !state.may_write(user.as_deref()) {
+            return error_response(StatusCode::FORBIDDEN, "Your account cannot change files");
+        }
+        let user = user.as_deref();
+        log::info!(
+            "[DAV] Client: {} | User: {} | {} {}",
+            addr,
+            user.map_or("-".to_string(), |user| user.to_string()),
+            method,
+            path
+        );
+        return match write(&state, addr, user, &method, &headers, &path, body).await {
+            Ok(res) => res,
+            Err((status, msg)) => error_response(status, &msg),
+        };
+    }
+
+    match method.as_str() {
+        "OPTIONS" => {
+            let mut res = StatusCode::OK.into_response();
+            let headers = res.headers_mut();
+            // class 2 only announces the no-op locks, macOS mounts the share read-only without
+            let class = if state.settings.write() { "1, 2" } else { "1" };
+            headers.insert("dav", HeaderValue::from_static(class));
+            headers.insert(header::ALLOW, HeaderValue::from_static(allow));
+            // makes Windows use WebDAV instead of FrontPage extensions
+            headers.insert("ms-author-via", HeaderValue::from_static("DAV"));
+            res
+        }
+        "PROPFIND" => {
+            let anonymous = anonymous.is_some();
+            propfind(&state, addr, user.as_deref(), anonymous, &headers, &path).await
+        }
+        "GET" | "HEAD" => {
+            let is_dir = match state.locate(&headers, user.as_deref(), &path) {
+                Ok(Located::Disk(root, below)) => root.join(below).is_dir(),
+                Ok(Located::Mounts(_)) => true,
+                Ok(Located::Packed(packed, below)) => packed.is_dir(below) == Some(true),
+                Err((status, msg)) => return error_response(status, &msg),
+            };
+            if is_dir {
+                let listing = if path.is_empty() {
+                    "/".to_string()
+                } else {
+                    format!("/browse/{}", utils::encode_path(&path))
+                };
+                return Redirect::to(&base_path::link(&listing)).into_response();
+            }
+            download_file(
+                State(state),
+                ConnectInfo(addr),
+                user,
+                method.clone(),
+                headers,
+                AxumPath(path),
+            )
+            .await
+        }
+        _ => unreachable!("write methods are handled above"),
+    }
+}
+
+async fn write(
+    state: &AppState,
+    addr: SocketAddr,
+    user: Option<&User>,
+    method: &Method,
+    headers: &HeaderMap,
+    path: &str,
+    body: Body,
+) -> Result<Response, (StatusCode, String)> {
+    if !state.within_depth(path, 1) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Path is deeper than the allowed browse depth".to_string(),
+        ));
+    }
+    let target = writable_target(state, user, headers, path)?;
+
+    let status = match method.as_str() {
+        "PUT" => put(&target, body).await?,
+        "MKCOL" => {
+            if fs::symlink_metadata(&target).await.is_ok() {
+                return Err((
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "The folder already exists".to_string(),
+                ));
+            }
+            fs::create_dir(&target).await.map_err(io_error)?;
+            StatusCode::CREATED
+        }
+        "DELETE" => {
+            let meta = fs::symlink_metadata(&target)
+                .await
+                .map_err(|_| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
+            if meta.is_dir() {
+                removable(state, user, headers, path, &target).await?;
+                fs::remove_dir_all(&target).await.map_err(io_error)?;
+            } else {
+                fs::remove_file(&target).await.map_err(io_error)?;
+            }
+            log_removed(addr, user, &target, "");
+            StatusCode::NO_CONTENT
+        }
+        "MOVE" | "COPY" => {
+            let destination_path = destination(headers)?;
+            if !state.within_depth(&destination_path, 1) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Destination is deeper than the allowed browse depth".to_string(),
+                ));
+            }
+            if state.exclude.matches(&destination_path) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Destination is excluded from the share".to_string(),
+                ));
+            }
+            let destination = writable_target(state, user, headers, &destination_path)?;
+            // a folder in the way is removed, only when the share shows all of it
+            if fs::symlink_metadata(&destination)
+                .await
+                .is_ok_and(|meta| meta.is_dir())
+            {
+                removable(state, user, headers, &destination_path, &destination).await?;
+            }
+            let status = transfer(&target, &destination, method == "MOVE", headers).await?;
+            if status == StatusCode::NO_CONTENT {
+                log_removed(
+                    addr,
+                    user,
+                    &destination,
+                    &format!(", replaced by {}", method),
+                );
+            }
+            status
+        }
+        "LOCK" => return Ok(lock(path)),
+        "UNLOCK" => StatusCode::NO_CONTENT,
+        _ => {
+            let mut res = error_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not supported by the WebDAV share",
+            );
+            res.headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static(ALLOW_WRITE));
+            return Ok(res);
+        }
+    };
+    Ok(status.into_response())
+}
+
+fn io_error(err: io::Error) -> (StatusCode, String) {
+    (io_error_status(&err), err.to_string())
+}
+
+// the same line as the trash button of the listing
+fn log_removed(addr: SocketAddr, user: Option<&User>, target: &Path, how: &str) {
+    log::info!(
+        "[DELETE] Client: {} | User: {} | {}{}",
+        addr,
+        user.map_or("-".to_string(), |user| user.to_string()),
+        target.display(),
+        how
+    );
+}
+
+// a folder goes with everything in it, also what the share leaves out: excluded, hidden and
+// filtered entries or folders below the browse depth. it is only removed when it holds none
+async fn removable(
+    state: &AppState,
+    user: Option<&User>,
+    headers: &HeaderMap,
+    path: &str,
+    dir: &Path,
+) -> Result<(), (StatusCode, String)> {
+    let root = match state.locate(headers, user, path)? {
+        Located::Disk(root, _) => root.to_path_buf(),
+        _ => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "The folder cannot be removed".to_string(),
+            ))
+        }
+    };
+    let (state, dir, path) = (state.clone(), dir.to_path_buf(), path.to_string());
+    let shared = tokio::task::spawn_blocking(move || all_shared(&state, &root, &dir, &path))
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)))
+        .map_err(io_error)?;
+    if !shared {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "The folder holds files that are not shared".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// whether the listings show every entry below the folder, `folder` is its path below the root
+fn all_shared(state: &AppState, root: &Path, dir: &Path, folder: &str) -> io::Result<bool> {
+    let entries = std::fs::read_dir(dir)?.count();
+    let mut rows = read_rows(root, dir)?;
+    state.shared_rows(&mut rows, folder, false);
+    if rows.len() != entries {
+        return Ok(false);
+    }
+    for row in rows.iter().filter(|row| row.is_dir) {
+        let sub = dir.join(&row.name);
+        // a link goes, not what it leads to
+        if std::fs::symlink_metadata(&sub)?.file_type().is_symlink() {
+            continue;
+        }
+        if !all_shared(state, root, &sub, &format!("{}/{}", folder, row.name))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// the file or folder a write may change, its parent has to be a folder inside the root and
+// links are never changed, writing through them could leave the share
+pub fn writable_target(
+    state: &AppState,
+    user: Option<&User>,
+    headers: &HeaderMap,
+    path: &str,
+) -> Result<PathBuf, (StatusCode, String)> {
+    let (root, path) = match state.locate(headers, user, path)? {
+        Located::Disk(root, below) => (root, below),
+        // the folders of --folder are no folder on disk
+        Located::Mounts(_) => ("".as_ref(), ""),
+        Located::Packed(..) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "The archive cannot be changed".to_string(),
+            ))
+        }
+    };
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() || name == "." || name == ".." {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "The shared folder itself cannot be changed".to_string(),
+        ));
+    }
+    let parent = safe_dir(root, &root.join(parent)).map_err(|(status, msg)| {
+        if status == StatusCode::NOT_FOUND {
+            (
+                StatusCode::CONFLICT,
+                "The parent folder does not exist".to_string(),
+            )
+        } else {
+            (status, msg)
+        }
+    })?;
+    let target = parent.join(name);
+    if std::fs::symlink_metadata(&target).is_ok_and(|meta| meta.file_type().is_symlink()) {
+        return Err((StatusCode::FORBIDDEN, "Links cannot be changed".to_string()));
+    }
+    Ok(target)
+}
+
+// writes the body next to the file first, the file is replaced only when the whole body arrived
+async fn put(target: &Path, body: Body) -> Result<StatusCode, (StatusCode, String)> {
+    let existed = match fs::metadata(target).await {
+        Ok(meta) if meta.is_dir() => {
+            return Err((
+                StatusCode::CONFLICT,
+                "A folder with this name exists".to_string(),
+            ));
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    };
+    let name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("upload");
+    let partial = target.with_file_name(format!(".{}.{}.part", name, &utils::random_token()[..8]));
+
+    let written = async {
+        let mut file = fs::File::create_new(&partial).await?;
+        let mut stream = body.into_data_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk.map_err(io::Error::other)?).await?;
+        }
+        file.flush().await?;
+        fs::rename(&partial, target).await
+    }
+    .await;
+
+    if let Err(err) = written {
+        let _ = fs::remove_file(&partial).await;
+        return Err(io_error(err));
+    }
+    Ok(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+// relative path of the Destination header of MOVE and COPY, a full url or an absolute path
+fn destination(headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+    let invalid = || {
+        (
+            StatusCode::BAD_REQUEST,
+            "Invalid Destination header".to_string(),
+        )
+    };
+    let value = headers
+        .get("destination")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(invalid)?;
+    let path = match value.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => value,
+    };
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let path = path
+        .strip_prefix(base_path::get())
+        .and_then(|path| path.strip_prefix("/dav/"))
+        .ok_or_else(invalid)?
+        .trim_end_matches('/');
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.contains('\\') {
+        return Err(invalid());
+    }
+    Ok(path.to_string())
+}
+
+async fn transfer(
+    source: &Path,
+    destination: &Path,
+    remove_source: bool,
+    headers: &HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let meta = fs::symlink_metadata(source)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
+    if source == destination {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Source and destination are the same".to_string(),
+        ));
+    }
+    if meta.is_dir() && destination.starts_with(source) {
+        return Err((
+            StatusCode::CONFLICT,
+            "A folder cannot be placed inside itself".to_string(),
+        ));
+    }
+
+    let existed = fs::symlink_metadata(destination).await.is_ok();
+    if existed {
+        let overwrite = headers.get("overwrite").is_none_or(|h| h != "F");
+        if !overwrite {
+            return Err((
+                StatusCode::PRECONDITION_FAILED,
+                "The destination exists".to_string(),
+            ));
+        }
+        if fs::metadata(destination).await.is_ok_and(|m| m.is_dir()) {
+            fs::remove_dir_all(destination).await.map_err(io_error)?;
+        } else {
+            fs::remove_file(destination).await.map_err(io_error)?;
+        }
+    }
+
+    if remove_source {
+        fs::rename(source, destination).await.map_err(io_error)?;
+    } else {
+        let (source, destination) = (source.to_path_buf(), destination.to_path_buf());
+        tokio::task::spawn_blocking(move || copy_tree(&source, &destination))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)))
+            .map_err(io_error)?;
+    }
+    Ok(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+// copies a file or a folder with its content, links inside are left out
+fn copy_tree(source: &Path, destination: &Path) -> io::Result<()> {
+    if !source.is_dir() {
+        return std::fs::copy(source, destination).map(|_| ());
+    }
+    std::fs::create_dir(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_type()?.is_symlink() {
+            continue;
+        }
+        copy_tree(&entry.path(), &destination.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+// locks are not enforced, the answer only satisfies clients that lock before writing
+fn lock(path: &str) -> Response {
+    let token = format!("opaquelocktoken:{}", utils::random_token());
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>0</D:depth><D:timeout>Second-3600</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>/dav/{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>\n",
+        token,
+        utils::html_escape(&utils::encode_path(path))
+    );
+    let mut res = (StatusCode::OK, body).into_response();
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("<{}>", token)) {
+        headers.insert("lock-token", value);
+    }
+    res
+}
+
+// properties of the resource and, unless Depth is 0, of the entries of a folder
+async fn propfind(
+    state: &AppState,
+    addr: SocketAddr,
+    user: Option<&User>,
+    anonymous: bool,
+    headers: &HeaderMap,
+    path: &str,
+) -> Response {
+    let (root, below) = match state.locate(headers, user, path) {
+        Ok(Located::Disk(root, below)) => (root.to_path_buf(), below),
+        Ok(Located::Mounts(mounts)) => {
+            return propfind_mounts(state, headers, mounts, anonymous);
+        }
+        Ok(Located::Packed(packed, below)) => {
+            return propfind_packed(state, headers, packed, below, anonymous);
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let target = root.join(below);
+    let Ok(meta) = tokio::fs::metadata(&target).await else {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    };
+
+    let href = if path.is_empty() {
+        base_path::link("/dav/")
+    } else {
+        base_path::link(&format!("/dav/{}", utils::encode_path(path)))
+    };
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let modified = meta.modified().ok();
+
+    let mut body = String::from(MULTISTATUS);
+    if !meta.is_dir() {
+        if let Err((status, msg)) = symlinks::resolve(&root, &target, "File") {
+            return error_response(status, &msg);
+        }
+        if !state.within_depth(path, 1)
+            || !state.filter.matches(meta.len(), modified)
+            || (!state.exclude.show_hidden && exclude::has_hidden_attribute(&meta))
+        {
+            return error_response(StatusCode::NOT_FOUND, "File not found");
+        }
+        body.push_str(&entry(&href, name, false, meta.len(), modified));
+        return multistatus(body);
+    }
+
+    if !state.within_depth(path, 0) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Folder is deeper than the allowed browse depth",
+        );
+    }
+    let dir = match safe_dir(&root, &target) {
+        Ok(dir) => dir,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let href = format!("{}/", href.trim_end_matches('/'));
+    body.push_str(&entry(&href, name, true, 0, modified));
+
+    // Depth: infinity is answered like 1, clients walk the tree folder by folder
+    if depth(headers) != Some("0") {
+        log::info!(
+            "[DAV] Client: {} | User: {} | {}",
+            addr,
+            user.map_or("-".to_string(), |user| user.to_string()),
+            dir.display()
+        );
+        let rows = tokio::task::spawn_blocking(move || read_rows(&root, &dir))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        let mut rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                let msg = format!("Failed to read directory: {}", e);
+                return error_response(crate::errors::io_error_status(&e), &msg);
+            }
+        };
+        state.shared_rows(&mut rows, path, anonymous);
+        push_rows(&mut body, &href, rows);
+    }
+    multistatus(body)
+}
+
+// the root of the --folder mounts, a folder with one folder per mount
+fn propfind_mounts(
+    state: &AppState,
+    headers: &HeaderMap,
+    mounts: &Mounts,
+    anonymous: bool,
+) -> Response {
+    let mut body = String::from(MULTISTATUS);
+    let href = base_path::link("/dav/");
+    body.push_str(&entry(&href, "", true, 0, None));
+    if depth(headers) != Some("0") {
+        let mut rows = mounts.rows();
+        state.shared_rows(&mut rows, "", anonymous);
+        push_rows(&mut body, &href, rows);
+    }
+    multistatus(body)
+}
+
+// an entry of the --archive file, with the entries of a folder below it
+fn propfind_packed(
+    state: &AppState,
+    headers: &HeaderMap,
+    packed: &Packed,
+    path: &str,
+    anonymous: bool,
+) -> Response {
+    let (is_dir, size, modified) = match packed.row(path) {
+        Some(row) => (row.is_dir, row.size, row.modified),
+        // the root of the archive has no entry of its own
+        None if packed.is_dir(path) == Some(true) => (true, 0, None),
+        None => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    if !is_dir && !state.filter.matches(size, modified) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let mut href = base_path::link(&format!("/dav/{}", utils::encode_path(path)));
+    if is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    let name = path.rsplit('/').next().unwrap_or_default();
+
+    let mut body = String::from(MULTISTATUS);
+    body.push_str(&entry(&href, name, is_dir, size, modified));
+    if is_dir && depth(headers) != Some("0") {
+        let mut rows = packed.rows(path).unwrap_or_default();
+        state.shared_rows(&mut rows, path, anonymous);
+        push_rows(&mut body, &href, rows);
+    }
+    multistatus(body)
+}
+
+fn depth(headers: &HeaderMap) -> Option<&str> {
+    headers.get("depth").and_then(|h| h.to_str().ok())
+}
+
+// the entries of a folder, below its href
+fn push_rows(body: &mut String, href: &str, rows: Vec<FileRow>) {
+    for row in rows {
+        let mut row_href = format!("{}{}", href, utils::encode_path(&row.name));
+        if row.is_dir {
+            row_href.push('/');
+        }
+        body.push_str(&entry(
+            &row_href,
+            &row.name,
+            row.is_dir,
+            row.size,
+            row.modified,
+        ));
+    }
+}
+
+fn entry(href: &str, name: &str, is_dir: bool, size: u64, modified: Option<SystemTime>) -> String {
+    let mut props = format!(
+        "<D:displayname>{}</D:displayname>",
+        utils::html_escape(name)
+    );
+    if let Some(modified) = modified {
+        props.push_str(&format!(
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            DateTime::<Utc>::from(modified).format("%a, %d %b %Y %H:%M:%S GMT")
+        ));
+    }
+    if is_dir {
+        props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        let mime = mime_guess::from_path(name).first_or_octet_stream();
+        let version = modified
+            .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        props.push_str(&format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getcontenttype>{}</D:getcontenttype><D:getetag>\"{:x}-{:x}\"</D:getetag>",
+            size, mime, size, version
+        ));
+    }
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        utils::html_escape(href),
+        props
+    )
+}
+
+const MULTISTATUS: &str =
+    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n";
+
+fn multistatus(mut body: String) -> Response {
+    body.push_str("</D:multistatus>\n");
+    let mut res = (StatusCode::MULTI_STATUS, body).into_response();
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    res
+}
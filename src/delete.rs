@@ -0,0 +1,72 @@
+use crate::{
+    auth::User,
+    dav,
+    errors::{error_response, io_error_status},
+    AppState,
+};
+use axum::{
+    extract::{ConnectInfo, Path as AxumPath, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use std::{io::ErrorKind, net::SocketAddr};
+use tokio::fs;
+
+// DELETE /delete/<path>, the trash button of a listing with --allow-delete. files and empty
+// folders go, a folder with something in it is left alone
+pub async fn delete_entry(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    // turned off on /admin, or read-only mode
+    if !state.settings.delete() {
+        return error_response(StatusCode::FORBIDDEN, "Deleting files is turned off");
+    }
+    if !state.may_write(user.as_deref()) {
+        return error_response(StatusCode::FORBIDDEN, "Your account cannot delete files");
+    }
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+    let path = path.trim_end_matches('/');
+    if !state.within_depth(path, 1) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Path is deeper than the allowed browse depth",
+        );
+    }
+    if state.exclude.matches(path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    // the same checks as a WebDAV write: inside the root, no link and never the root itself
+    let target = match dav::writable_target(&state, user.as_deref(), &headers, path) {
+        Ok(target) => target,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let removed = match fs::symlink_metadata(&target).await {
+        Ok(meta) if meta.is_dir() => fs::remove_dir(&target).await,
+        Ok(_) => fs::remove_file(&target).await,
+        Err(_) => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    if let Err(err) = removed {
+        if err.kind() == ErrorKind::DirectoryNotEmpty {
+            return error_response(StatusCode::CONFLICT, "The folder is not empty");
+        }
+        log::warn!("[DELETE] cannot delete {}: {}", target.display(), err);
+        let msg = format!("Failed to delete: {}", err);
+        return error_response(io_error_status(&err), &msg);
+    }
+    log::info!(
+        "[DELETE] Client: {} | User: {} | {}",
+        addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
+        target.display()
+    );
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,141 @@
+use crate::{
+    exclude::{self, ExcludeRules},
+    FileRow,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+// a size older than this is shown once more and computed again, changes deep below a folder
+// leave its modification time alone
+const REFRESH_AFTER: Duration = Duration::from_secs(300);
+
+// --dir-sizes, the size of every folder with everything below it. sizes are computed in the
+// background and kept by folder until its modification time changes, a listing shows the ones
+// known so far
+struct DirSizes {
+    known: Mutex<HashMap<PathBuf, Known>>,
+    // folders being computed
+    pending: Mutex<HashSet<PathBuf>>,
+}
+
+struct Known {
+    size: u64,
+    modified: Option<SystemTime>,
+    at: Instant,
+}
+
+static SIZES: OnceLock<DirSizes> = OnceLock::new();
+
+pub fn enable() {
+    let _ = SIZES.set(DirSizes {
+        known: Mutex::new(HashMap::new()),
+        pending: Mutex::new(HashSet::new()),
+    });
+}
+
+pub fn enabled() -> bool {
+    SIZES.get().is_some()
+}
+
+// the size of every folder among the rows of `dir`, none while it is computed. `path` is the
+// folder relative to the root, for the --exclude globs
+pub fn lookup(
+    dir: &Path,
+    path: &str,
+    rows: &[FileRow],
+    exclude: &Arc<ExcludeRules>,
+) -> HashMap<String, Option<u64>> {
+    let Some(sizes) = SIZES.get() else {
+        return HashMap::new();
+    };
+    let mut found = HashMap::new();
+    for row in rows.iter().filter(|row| row.is_dir) {
+        let folder = dir.join(&row.name);
+        let modified = fs::metadata(&folder).and_then(|meta| meta.modified()).ok();
+        let known = sizes
+            .known
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&folder)
+            .filter(|known| known.modified == modified)
+            .map(|known| (known.size, known.at.elapsed() < REFRESH_AFTER));
+        if known.is_none_or(|(_, fresh)| !fresh) {
+            let below = if path.is_empty() {
+                row.name.clone()
+            } else {
+                format!("{}/{}", path, row.name)
+            };
+            sizes.compute(folder, below, exclude.clone());
+        }
+        found.insert(row.name.clone(), known.map(|(size, _)| size));
+    }
+    found
+}
+
+impl DirSizes {
+    // walks the folder on the blocking pool unless that already happens
+    fn compute(&'static self, folder: PathBuf, path: String, exclude: Arc<ExcludeRules>) {
+        if !self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(folder.clone())
+        {
+            return;
+        }
+        tokio::task::spawn_blocking(move || {
+            let mut walked = Vec::new();
+            walk(&folder, &path, &exclude, &mut walked);
+            let at = Instant::now();
+            let mut known = self.known.lock().unwrap_or_else(|e| e.into_inner());
+            // the folders below are known as well once the walk went through them
+            for (dir, modified, size) in walked {
+                known.insert(dir, Known { size, modified, at });
+            }
+            drop(known);
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&folder);
+        });
+    }
+}
+
+// the size of the files below `dir`, the shared ones only. links are not followed, they would
+// count files twice or outside the root. every folder goes into `walked` after its content
+fn walk(
+    dir: &Path,
+    path: &str,
+    rules: &ExcludeRules,
+    walked: &mut Vec<(PathBuf, Option<SystemTime>, u64)>,
+) -> u64 {
+    let modified = fs::metadata(dir).and_then(|meta| meta.modified()).ok();
+    let mut size = 0;
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let below = format!("{}/{}", path, name);
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if rules.matches(&below) || (!rules.show_hidden && exclude::has_hidden_attribute(&meta))
+            {
+                continue;
+            }
+            if meta.is_dir() {
+                size += walk(&entry.path(), &below, rules, walked);
+            } else if meta.is_file() {
+                size += meta.len();
+            }
+        }
+    }
+    walked.push((dir.to_path_buf(), modified, size));
+    size
+}
@@ -0,0 +1,304 @@
+use crate::{mdns, templates, tls::TlsSource, AppState};
+use clap::ArgMatches;
+use std::{
+    fs,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+};
+
+// runs the startup checks, prints one line per check and returns whether all passed
+pub fn run(
+    state: &AppState,
+    addr: SocketAddr,
+    tls: Option<&TlsSource>,
+    log_path: Option<&Path>,
+) -> bool {
+    let mut checks: Vec<(String, Result<String, String>)> = Vec::new();
+
+    for (name, _) in templates::BUILT_IN {
+        let path = templates::dir().join(name);
+        let check = if path.exists() {
+            fs::read_to_string(&path)
+                .map(|t| format!("{}, {} bytes", path.display(), t.len()))
+                .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+        } else {
+            Ok("built in".to_string())
+        }
+        .and_then(|found| templates::check(name).map(|_| found));
+        checks.push((format!("template {}", name), check));
+    }
+
+    let roots = std::iter::once(state.root.as_path())
+        .chain(state.host_roots.values().map(|root| root.as_path()))
+        .chain(
+            state
+                .mounts
+                .iter()
+                .flat_map(|m| m.iter().map(|(_, path)| path)),
+        );
+    for root in roots {
+        checks.push((format!("folder {}", root.display()), check_folder(root)));
+    }
+
+    checks.push((
+        format!("port {}", addr.port()),
+        TcpListener::bind(addr)
+            .map(|_| format!("{} is free", addr))
+            .map_err(|e| format!("cannot listen on {}: {}", addr, e)),
+    ));
+
+    checks.push(("interface".to_string(), check_interfaces()));
+
+    checks.push((
+        "tls".to_string(),
+        match tls {
+            Some(source) => check_tls(source),
+            None => Ok("not used, the server only speaks plain HTTP".to_string()),
+        },
+    ));
+
+    // --log-file none writes nothing
+    if let Some(log_path) = log_path {
+        let log_dir = log_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        checks.push((
+            format!("log folder {}", log_dir.display()),
+            check_writable(log_dir),
+        ));
+    }
+
+    let mut passed = true;
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => println!("ok    {}: {}", name, detail),
+            Err(err) => {
+                passed = false;
+                println!("FAIL  {}: {}", name, err);
+            }
+        }
+    }
+    passed
+}
+
+fn check_folder(root: &Path) -> Result<String, String> {
+    let meta = fs::metadata(root).map_err(|e| e.to_string())?;
+    if !meta.is_dir() {
+        return Err("not a folder".to_string());
+    }
+    let entries = fs::read_dir(root).map_err(|e| e.to_string())?.count();
+    Ok(format!("readable, {} entries", entries))
+}
+
+fn check_interfaces() -> Result<String, String> {
+    let interfaces = get_if_addrs::get_if_addrs().map_err(|e| e.to_string())?;
+    let addresses: Vec<String> = interfaces
+        .iter()
+        .filter(|i| !i.is_loopback() && i.ip().is_ipv4())
+        .map(|i| format!("{} ({})", i.ip(), i.name))
+        .collect();
+    if addresses.is_empty() {
+        Err("no LAN address found, pass one with --interface".to_string())
+    } else {
+        Ok(addresses.join(", "))
+    }
+}
+
+fn check_tls(source: &TlsSource) -> Result<String, String> {
+    source.load().map_err(|e| e.to_string())?;
+    Ok(match source {
+        TlsSource::Pem(cert, _) => format!("certificate {} loaded", cert.display()),
+        TlsSource::SelfSigned(names) => {
+            format!("self-signed certificate for {}", names.join(", "))
+        }
+    })
+}
+
+fn check_writable(dir: &Path) -> Result<String, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let probe = dir.join(".file-serve-doctor");
+    fs::write(&probe, b"").map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(&probe);
+    Ok("writable".to_string())
+}
+
+// prints the configuration after defaults and argument parsing, for --check
+pub fn print_config(
+    matches: &ArgMatches,
+    state: &AppState,
+    scheme: &str,
+    address: &str,
+    port: u16,
+) {
+    let value = |id: &str| matches.get_one::<String>(id).cloned();
+    let or_default = |id: &str, default: &str| value(id).unwrap_or_else(|| default.to_string());
+
+    let workers = value("workers").unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or("-".to_string(), |n| n.to_string())
+    });
+
+    let mut logins: Vec<String> = Vec::new();
+    if let Some(issuer) = value("oidc-issuer") {
+        logins.push(format!("openid {}", issuer));
+    }
+    if let Some(url) = value("ldap-url") {
+        logins.push(format!("ldap {}", url));
+    }
+    if let Some(file) = value("htpasswd") {
+        logins.push(format!("htpasswd {}", file));
+    }
+    if let Some(users) = matches.get_many::<String>("auth") {
+        logins.push(format!("basic auth, {} users", users.count()));
+    }
+    #[cfg(all(unix, feature = "pam"))]
+    if let Some(service) = value("pam") {
+        logins.push(format!("pam {}", service));
+    }
+
+    let row = |label: &str, value: String| println!("{:<19} {}", format!("{}:", label), value);
+    row("folder", state.root.display().to_string());
+    for (name, root) in state.host_roots.iter() {
+        row(&format!("host {}", name), root.display().to_string());
+    }
+    for (name, path) in state.mounts.iter().flat_map(|m| m.iter()) {
+        row(&format!("folder {}", name), path.display().to_string());
+    }
+    if let Some(packed) = &state.packed {
+        row("archive", packed.file().display().to_string());
+    }
+    row("listen", format!("0.0.0.0:{}", port));
+    row("link", format!("{}://{}:{}", scheme, address, port));
+    row("bind", or_default("bind", "0.0.0.0"));
+    row("find port", matches.get_flag("find-port").to_string());
+    row("live", matches.get_flag("live").to_string());
+    row("stats", matches.get_flag("stats").to_string());
+    row("stats file", or_default("stats-file", "none"));
+    row(
+        "webhooks",
+        matches
+            .get_many::<String>("webhook")
+            .map_or("0".to_string(), |urls| urls.count().to_string()),
+    );
+    row("webhook events", or_default("webhook-events", "all"));
+    row("tui", matches.get_flag("tui").to_string());
+    row("print json", matches.get_flag("print-json").to_string());
+    row("log file", or_default("log-file", "logs/file_serve.log"));
+    row("log max size", or_default("log-max-size", "10M"));
+    row("log rotate", or_default("log-rotate", "off"));
+    row("log keep", or_default("log-keep", "5"));
+    row("log level", or_default("log-level", "info"));
+    row("quiet", matches.get_flag("quiet").to_string());
+    row("log format", or_default("log-format", "text"));
+    row("auth log", or_default("auth-log", "none"));
+    row("base path", or_default("base-path", "/"));
+    #[cfg(unix)]
+    row("unix socket", or_default("unix", "off"));
+    row(
+        "tls",
+        if matches.get_flag("tls-self-signed") {
+            "self-signed".to_string()
+        } else {
+            or_default("cert", "off")
+        },
+    );
+    row("redirect http", or_default("redirect-http", "off"));
+    row(
+        "hsts",
+        if matches.get_flag("hsts") {
+            format!("{} seconds", or_default("hsts-max-age", "86400"))
+        } else {
+            "off".to_string()
+        },
+    );
+    row("lan only", matches.get_flag("lan-only").to_string());
+    let mdns_name = value("mdns-name").unwrap_or_else(mdns::default_name);
+    row(
+        "mdns",
+        format!("{} as {}", mdns_name, mdns::host_name(&mdns_name)),
+    );
+    row(
+        "streams per ip",
+        or_default("max-streams-per-ip", "unlimited"),
+    );
+    row("rate limit", or_default("rate-limit", "unlimited"));
+    row(
+        "trusted proxies",
+        matches
+            .get_many::<String>("trusted-proxy")
+            .map_or("none".to_string(), |ranges| {
+                ranges.cloned().collect::<Vec<_>>().join(", ")
+            }),
+    );
+    row("limit rate", or_default("limit-rate", "unlimited"));
+    row(
+        "limit rate per connection",
+        or_default("limit-rate-per-connection", "unlimited"),
+    );
+    row(
+        "login",
+        if logins.is_empty() {
+            "none".to_string()
+        } else {
+            logins.join(", ")
+        },
+    );
+    row("write role", or_default("write-role", "none"));
+    row("signed links", value("url-secret").is_some().to_string());
+    row("admin panel", value("admin").is_some().to_string());
+    row(
+        "remote shutdown",
+        value("shutdown-token").is_some().to_string(),
+    );
+    row(
+        "visibility",
+        matches
+            .get_many::<String>("visibility")
+            .map_or("-".to_string(), |v| {
+                v.cloned().collect::<Vec<_>>().join(", ")
+            }),
+    );
+    row("min file size", or_default("min-file-size", "-"));
+    row("max file size", or_default("max-file-size", "-"));
+    row("newer than", or_default("newer-than", "-"));
+    row("max depth", or_default("max-depth", "unlimited"));
+    row(
+        "exclude",
+        matches
+            .get_many::<String>("exclude")
+            .map_or("-".to_string(), |v| {
+                v.cloned().collect::<Vec<_>>().join(", ")
+            }),
+    );
+    row(
+        "follow symlinks",
+        or_default("follow-symlinks", "inside-root"),
+    );
+    row("gitignore", matches.get_flag("gitignore").to_string());
+    row("show hidden", matches.get_flag("show-hidden").to_string());
+    row("single page app", matches.get_flag("spa").to_string());
+    row("website", matches.get_flag("site").to_string());
+    row("thumbnails", matches.get_flag("thumbnails").to_string());
+    row(
+        "cache control",
+        or_default("cache-control", "private, max-age=300"),
+    );
+    row(
+        "compression",
+        (!matches.get_flag("no-compression")).to_string(),
+    );
+    row("dir sizes", matches.get_flag("dir-sizes").to_string());
+    row("show gps", matches.get_flag("show-gps").to_string());
+    row("theme", or_default("theme", "auto"));
+    row("title", or_default("title", "LAN File Server"));
+    row("allow delete", matches.get_flag("allow-delete").to_string());
+    row("on upload", or_default("on-upload", "none"));
+    row("on upload jobs", or_default("on-upload-jobs", "2"));
+    row("sandbox", matches.get_flag("sandbox").to_string());
+    row("workers", workers);
+    row("blocking threads", or_default("blocking-threads", "512"));
+    #[cfg(feature = "transcode")]
+    row("transcode", matches.get_flag("transcode").to_string());
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    row("io_uring", matches.get_flag("io-uring").to_string());
+}
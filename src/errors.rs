@@ -0,0 +1,139 @@
+use crate::{
+    templates::{self, Theme},
+    utils,
+};
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// message of a failed handler, the error page itself is rendered by `error_pages`
+#[derive(Clone)]
+struct ErrorMessage(String);
+
+// fields of the error page
+#[derive(Serialize)]
+struct ErrorPage<'a> {
+    status: String,
+    error_message: &'a str,
+    path: &'a str,
+    request_id: &'a str,
+    retry_hint: &'static str,
+}
+
+// the page for a status, <code>.html when the template folder has one else error.html
+fn error_template(status: StatusCode) -> String {
+    let name = format!("{}.html", status.as_u16());
+    if templates::exists(&name) {
+        name
+    } else {
+        "error.html".to_string()
+    }
+}
+
+// maps a file system error to the status reported to the client
+pub fn io_error_status(err: &std::io::Error) -> StatusCode {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        std::io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub fn error_response(status: StatusCode, msg: &str) -> Response {
+    let mut res = status.into_response();
+    res.extensions_mut().insert(ErrorMessage(msg.to_string()));
+    res
+}
+
+// the fallback of the routes, unknown paths get the 404 page instead of an empty answer
+pub async fn not_found() -> Response {
+    error_response(StatusCode::NOT_FOUND, "Page not found")
+}
+
+// a known path asked with a method it does not answer, the Allow header of axum is kept
+pub async fn method_not_allowed() -> Response {
+    error_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed")
+}
+
+// tags every request with an id and renders the error page of failed handlers
+pub async fn error_pages(req: Request, next: Next) -> Response {
+    let request_id = format!("{:08x}", NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed));
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .to_string();
+    let theme = Theme::of(req.headers());
+
+    let mut res = next.run(req).await;
+
+    if let Some(ErrorMessage(msg)) = res.extensions_mut().remove::<ErrorMessage>() {
+        let status = res.status();
+        log::warn!("[{}] {} {}: {}", request_id, status.as_u16(), path, msg);
+        let mut page = (
+            status,
+            Html(error_page(status, &msg, &path, &request_id, theme)),
+        )
+            .into_response();
+        // keep the headers set by the handler, e.g. WWW-Authenticate, with every value of the
+        // ones that repeat like Set-Cookie
+        for name in res.headers().keys() {
+            if page.headers().contains_key(name) {
+                continue;
+            }
+            for value in res.headers().get_all(name) {
+                page.headers_mut().append(name.clone(), value.clone());
+            }
+        }
+        res = page;
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert("x-request-id", value);
+    }
+    res
+}
+
+fn retry_hint(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::NOT_FOUND => "Check the address or go back to the listing.",
+        StatusCode::FORBIDDEN => "You do not have access to this resource.",
+        StatusCode::BAD_REQUEST => "The request is malformed, check the address.",
+        StatusCode::GONE => "Ask the person who shared the link for a new one.",
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+            "The server is busy, try again in a moment."
+        }
+        s if s.is_server_error() => {
+            "Try again later, if the problem persists check the server log."
+        }
+        _ => "",
+    }
+}
+
+fn error_page(status: StatusCode, msg: &str, path: &str, request_id: &str, theme: Theme) -> String {
+    let page = ErrorPage {
+        status: status.to_string(),
+        error_message: msg,
+        path,
+        request_id,
+        retry_hint: retry_hint(status),
+    };
+    match templates::render(&error_template(status), theme, &page) {
+        Ok(html) => html,
+        Err(e) => {
+            log::error!("Error loading error template: {}", e);
+            // Fallback to simple error page
+            format!(
+                "<h1>Error</h1><p>Failed to load error template: {}</p><p>Error: {}</p>",
+                utils::html_escape(&e.to_string()),
+                utils::html_escape(msg)
+            )
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match, WalkBuilder,
+};
+use std::{
+    cmp::Reverse,
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
+
+// entries that are never shared, the --exclude globs, the dotfiles unless --show-hidden and
+// with --gitignore the ignored files. the listings drop them and every route that takes a
+// path answers them with not found
+pub struct ExcludeRules {
+    globs: GlobSet,
+    // the ignore files with the folder they are in below the root, deepest first
+    gitignores: Vec<(PathBuf, Gitignore)>,
+    root: PathBuf,
+    pub show_hidden: bool,
+}
+
+impl ExcludeRules {
+    // a glob without a slash matches the name at any depth, e.g. *.log, one with a slash the
+    // path below the root. node_modules/** excludes the folder itself like node_modules does
+    pub fn new<'a>(
+        patterns: impl IntoIterator<Item = &'a String>,
+        show_hidden: bool,
+    ) -> Result<Self, String> {
+        let mut globs = GlobSetBuilder::new();
+        for pattern in patterns {
+            let trimmed = pattern.trim_matches('/');
+            let trimmed = trimmed.strip_suffix("/**").unwrap_or(trimmed);
+            let glob = if trimmed.contains('/') {
+                trimmed.to_string()
+            } else {
+                format!("**/{}", trimmed)
+            };
+            let glob = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("invalid exclude {}: {}", pattern, e))?;
+            globs.add(glob);
+        }
+        let globs = globs.build().map_err(|e| e.to_string())?;
+        Ok(ExcludeRules {
+            globs,
+            gitignores: Vec::new(),
+            root: PathBuf::new(),
+            show_hidden,
+        })
+    }
+
+    // reads the .gitignore files of the served folder and .git/info/exclude, once at startup.
+    // ignored folders are not searched for more of them
+    pub fn with_gitignore(mut self, root: &Path) -> Self {
+        let mut files: Vec<PathBuf> = WalkBuilder::new(root)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_name() == ".gitignore")
+            .map(|entry| entry.into_path())
+            .collect();
+        files.push(root.join(".git").join("info").join("exclude"));
+
+        for file in files.into_iter().filter(|file| file.is_file()) {
+            let dir = file
+                .parent()
+                .and_then(|dir| dir.strip_prefix(root).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            // .git/info/exclude holds patterns for the whole repository
+            let dir = if dir.starts_with(".git") {
+                PathBuf::new()
+            } else {
+                dir
+            };
+            let mut builder = GitignoreBuilder::new(root.join(&dir));
+            if let Some(err) = builder.add(&file) {
+                log::warn!("[GITIGNORE] skipped lines of {}: {}", file.display(), err);
+            }
+            match builder.build() {
+                Ok(gitignore) => self.gitignores.push((dir, gitignore)),
+                Err(err) => log::warn!("[GITIGNORE] cannot read {}: {}", file.display(), err),
+            }
+        }
+        // the .gitignore files come first among the ones of a folder, so they win over
+        // .git/info/exclude
+        self.gitignores
+            .sort_by_key(|(dir, _)| Reverse(dir.components().count()));
+        log::info!(
+            "[GITIGNORE] {} ignore files in {}",
+            self.gitignores.len(),
+            root.display()
+        );
+        self.root = root.to_path_buf();
+        self
+    }
+
+    // whether the entry or one of the folders it is in is excluded, paths relative to the root
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return false;
+        }
+        let mut prefix = String::new();
+        for part in path.split(['/', '\\']) {
+            if !self.show_hidden && part.starts_with('.') {
+                return true;
+            }
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            if self.globs.is_match(Path::new(&prefix)) {
+                return true;
+            }
+            if !self.gitignores.is_empty() {
+                // only the entry itself can be a file
+                let is_dir = prefix.len() < path.len() || self.root.join(&prefix).is_dir();
+                if self.gitignored(&prefix, is_dir) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // the deepest ignore file with a rule for the path decides, like in git
+    fn gitignored(&self, path: &str, is_dir: bool) -> bool {
+        let path = Path::new(path);
+        for (dir, gitignore) in &self.gitignores {
+            let Ok(below) = path.strip_prefix(dir) else {
+                continue;
+            };
+            match gitignore.matched(below, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+// a file hidden by its attribute on Windows, also left out unless --show-hidden
+#[cfg(windows)]
+pub fn has_hidden_attribute(meta: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+pub fn has_hidden_attribute(_meta: &Metadata) -> bool {
+    false
+}
@@ -0,0 +1,148 @@
+use crate::{assets, read_rows, render_index, templates::Theme, AppState, LinkStyle};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// renders the listing of every folder to <out>/<folder>/index.html with relative links and
+// places the shared files next to them, returns the number of exported files
+pub fn export(state: &AppState, out_dir: &Path, link: bool) -> io::Result<usize> {
+    if state.packed.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the entries of --archive cannot be exported, extract it instead",
+        ));
+    }
+    fs::create_dir_all(out_dir)?;
+    let mut export = Export {
+        state,
+        root: state.root.canonicalize()?,
+        out_dir: out_dir.canonicalize()?,
+        link,
+    };
+    assets::export(out_dir)?;
+    let Some(mounts) = state.mounts.as_deref() else {
+        return export.folder(&export.root, "", out_dir, &mut HashSet::new());
+    };
+
+    // the --folder mounts are exported below a listing of them, each with its own root
+    let mut rows = mounts.rows();
+    state.shared_rows(&mut rows, "", false);
+    rows.retain(|row| row.name != assets::EXPORT_DIR);
+    let mut seen = HashSet::new();
+    let mut files = 0;
+    for row in &rows {
+        let Some(folder) = mounts.get(&row.name) else {
+            continue;
+        };
+        let root = folder.canonicalize()?;
+        export.root = root.clone();
+        files += export.folder(&root, &row.name, &out_dir.join(&row.name), &mut seen)?;
+    }
+    fs::write(
+        out_dir.join("index.html"),
+        render_index(
+            rows,
+            "",
+            LinkStyle::Static,
+            false,
+            false,
+            None,
+            Theme::default_theme(),
+        ),
+    )?;
+    Ok(files)
+}
+
+struct Export<'a> {
+    state: &'a AppState,
+    root: PathBuf,
+    out_dir: PathBuf,
+    // symlink the files instead of copying them
+    link: bool,
+}
+
+impl Export<'_> {
+    // `seen` holds the folders exported so far, one reached again through a link is skipped
+    fn folder(
+        &self,
+        dir: &Path,
+        current_path: &str,
+        out: &Path,
+        seen: &mut HashSet<PathBuf>,
+    ) -> io::Result<usize> {
+        if !seen.insert(dir.canonicalize()?) {
+            log::warn!("[EXPORT] skipping {}, already exported", dir.display());
+            return Ok(0);
+        }
+        fs::create_dir_all(out)?;
+        let mut rows = read_rows(&self.root, dir)?;
+        self.state.shared_rows(&mut rows, current_path, false);
+
+        let mut files = 0;
+        let mut exported = Vec::with_capacity(rows.len());
+        for row in rows {
+            let source = dir.join(&row.name);
+            let target = out.join(&row.name);
+            let row_path = if current_path.is_empty() {
+                row.name.clone()
+            } else {
+                format!("{}/{}", current_path, row.name)
+            };
+
+            if row.is_dir {
+                // an output folder inside the share
+                if source == self.out_dir {
+                    continue;
+                }
+                if current_path.is_empty() && row.name == assets::EXPORT_DIR {
+                    log::warn!(
+                        "[EXPORT] {} is replaced by the assets of the pages",
+                        source.display()
+                    );
+                    continue;
+                }
+                files += self.folder(&source, &row_path, &target, seen)?;
+            } else if !fs::metadata(&source).is_ok_and(|meta| meta.is_file()) {
+                // sockets and the like, or a file that went away
+                log::warn!("[EXPORT] skipping {}, not a regular file", source.display());
+                continue;
+            } else if row.name == "index.html" {
+                log::warn!(
+                    "[EXPORT] {} is replaced by the generated listing",
+                    source.display()
+                );
+                continue;
+            } else {
+                self.file(&source, &target)?;
+                files += 1;
+            }
+            exported.push(row);
+        }
+
+        fs::write(
+            out.join("index.html"),
+            render_index(
+                exported,
+                current_path,
+                LinkStyle::Static,
+                false,
+                false,
+                None,
+                Theme::default_theme(),
+            ),
+        )?;
+        Ok(files)
+    }
+
+    fn file(&self, source: &Path, target: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        if self.link {
+            // replace the link of a previous export
+            let _ = fs::remove_file(target);
+            return std::os::unix::fs::symlink(source.canonicalize()?, target);
+        }
+        fs::copy(source, target).map(|_| ())
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, SystemTime};
+
+// limits the shared files by size and age, --min-file-size, --max-file-size and --newer-than
+#[derive(Clone, Copy)]
+pub struct FileFilter {
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub newer_than: Option<Duration>,
+}
+
+impl FileFilter {
+    // folders are never filtered, only the files inside them
+    pub fn matches(&self, size: u64, modified: Option<SystemTime>) -> bool {
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        match self.newer_than {
+            Some(age) => modified
+                .and_then(|m| m.elapsed().ok())
+                .is_some_and(|elapsed| elapsed <= age),
+            None => true,
+        }
+    }
+}
+
+// parses sizes like 512, 20K, 5M or 2G (powers of 1024)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return Err(format!("unknown size unit in {}", s)),
+    };
+    number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid size {}", s))
+        .and_then(|n| {
+            n.checked_mul(multiplier)
+                .ok_or(format!("size {} too large", s))
+        })
+}
+
+// parses ages like 30m, 12h, 7d or 2w
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("age {} needs a unit, s, m, h, d or w", s)),
+    };
+    number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid age {}", s))
+        .map(|n| Duration::from_secs(n.saturating_mul(seconds)))
+}
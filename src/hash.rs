@@ -0,0 +1,183 @@
+use crate::{
+    auth::User,
+    errors::{error_response, io_error_status},
+    exclude,
+    listing::{self, Format},
+    symlinks, AppState, Located,
+};
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+#[derive(Deserialize)]
+pub struct HashParams {
+    #[serde(default)]
+    algo: Algo,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+enum Algo {
+    Md5,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl Algo {
+    fn name(self) -> &'static str {
+        match self {
+            Algo::Md5 => "md5",
+            Algo::Sha256 => "sha256",
+            Algo::Sha512 => "sha512",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Checksum<'a> {
+    name: &'a str,
+    algo: &'static str,
+    digest: &'a str,
+    size: u64,
+}
+
+// a digest with the size and modification time of the file it was made of, a changed file is
+// hashed again
+struct Cached {
+    size: u64,
+    modified: Option<SystemTime>,
+    digest: String,
+}
+
+// the digests made while the server runs, by file and algorithm
+lazy_static::lazy_static! {
+    static ref CACHE: Mutex<HashMap<(PathBuf, Algo), Cached>> = Mutex::new(HashMap::new());
+}
+
+// /api/hash/<path>?algo=sha256|sha512|md5, the digest of a file read on the server as JSON, or
+// a line of sha256sum for Accept: text/plain. the file goes through the checks of a download
+pub async fn checksum(
+    State(state): State<AppState>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    Query(params): Query<HashParams>,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    if !state.within_depth(&path, 1) || state.exclude.matches(&path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let file = match state.locate(&headers, user.as_deref(), &path) {
+        Ok(Located::Disk(root, below)) => {
+            match symlinks::resolve(root, &root.join(below), "File") {
+                Ok(file) => file,
+                Err((status, msg)) => return error_response(status, &msg),
+            }
+        }
+        // the entries of --archive are not hashed
+        Ok(Located::Mounts(_)) | Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::NOT_FOUND, "File not found")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    // files outside the size and age filters are not shared, nor hidden ones
+    let meta = match fs::metadata(&file) {
+        Ok(meta)
+            if meta.is_file()
+                && state.filter.matches(meta.len(), meta.modified().ok())
+                && (state.exclude.show_hidden || !exclude::has_hidden_attribute(&meta)) =>
+        {
+            meta
+        }
+        _ => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    let (size, modified) = (meta.len(), meta.modified().ok());
+
+    let algo = params.algo;
+    let key = (file.clone(), algo);
+    let cached = CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+        .filter(|cached| cached.size == size && cached.modified == modified)
+        .map(|cached| cached.digest.clone());
+    let digest = match cached {
+        Some(digest) => digest,
+        None => {
+            let made = tokio::task::spawn_blocking(move || match algo {
+                Algo::Md5 => hash::<md5::Md5>(&file),
+                Algo::Sha256 => hash::<Sha256>(&file),
+                Algo::Sha512 => hash::<Sha512>(&file),
+            })
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)));
+            match made {
+                Ok(digest) => {
+                    log::info!("[HASH] {} of {}: {}", algo.name(), path, digest);
+                    CACHE.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                        key,
+                        Cached {
+                            size,
+                            modified,
+                            digest: digest.clone(),
+                        },
+                    );
+                    digest
+                }
+                Err(err) => {
+                    let status = io_error_status(&err);
+                    return error_response(status, &format!("Cannot read the file: {}", err));
+                }
+            }
+        }
+    };
+
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    match listing::format(&headers) {
+        // the line of sha256sum and the like, so `sha256sum -c` checks the downloaded file
+        Format::Text => format!("{}  {}\n", digest, name).into_response(),
+        Format::Html | Format::Json => Json(Checksum {
+            name,
+            algo: algo.name(),
+            digest: &digest,
+            size,
+        })
+        .into_response(),
+    }
+}
+
+// reads the whole file, blocking so it must run on the blocking pool
+fn hash<D: Digest>(file: &Path) -> io::Result<String> {
+    let mut file = File::open(file)?;
+    let mut hasher = D::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
@@ -0,0 +1,227 @@
+use md5::{Digest, Md5};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+const APR1_MAGIC: &str = "$apr1$";
+const CRYPT_ALPHABET: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+struct Entries {
+    modified: Option<SystemTime>,
+    hashes: HashMap<String, String>,
+}
+
+// validates user names and passwords against an Apache htpasswd file (bcrypt or apr1 md5),
+// the file is read again whenever it changes so users can be managed while the server runs
+pub struct Htpasswd {
+    path: PathBuf,
+    entries: Mutex<Entries>,
+}
+
+impl Htpasswd {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let modified = std::fs::metadata(path)?.modified().ok();
+        let hashes = parse(&std::fs::read_to_string(path)?);
+        Ok(Htpasswd {
+            path: path.to_path_buf(),
+            entries: Mutex::new(Entries { modified, hashes }),
+        })
+    }
+
+    pub async fn authenticate(&self, user: &str, password: &str) -> Result<(), String> {
+        if user.is_empty() || password.is_empty() {
+            return Err("empty user name or password".to_string());
+        }
+
+        self.reload().await;
+        let hash = self
+            .entries
+            .lock()
+            .unwrap()
+            .hashes
+            .get(user)
+            .cloned()
+            .ok_or_else(|| format!("{} is not in {}", user, self.path.display()))?;
+
+        // bcrypt is slow by design, keep it off the runtime threads
+        let password = password.to_string();
+        let valid = tokio::task::spawn_blocking(move || verify(&password, &hash))
+            .await
+            .map_err(|e| e.to_string())??;
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("wrong password for {}", user))
+        }
+    }
+
+    // reads the file again when its modification time changed, keeps the old users on errors
+    async fn reload(&self) {
+        let modified = match tokio::fs::metadata(&self.path).await {
+            Ok(meta) => meta.modified().ok(),
+            Err(err) => {
+                log::error!("cannot read {}\n{}", self.path.display(), err);
+                return;
+            }
+        };
+        if modified == self.entries.lock().unwrap().modified {
+            return;
+        }
+
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => {
+                let hashes = parse(&content);
+                log::info!(
+                    "[AUTH] loaded {} users from {}",
+                    hashes.len(),
+                    self.path.display()
+                );
+                *self.entries.lock().unwrap() = Entries { modified, hashes };
+            }
+            Err(err) => log::error!("cannot read {}\n{}", self.path.display(), err),
+        }
+    }
+}
+
+fn parse(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(user, hash)| (user.to_string(), hash.to_string()))
+        .collect()
+}
+
+fn verify(password: &str, hash: &str) -> Result<bool, String> {
+    if let Some(rest) = hash.strip_prefix(APR1_MAGIC) {
+        let salt = rest.split('$').next().unwrap_or_default();
+        Ok(apr1(password.as_bytes(), salt.as_bytes()) == hash)
+    } else if hash.starts_with("$2") {
+        bcrypt::verify(password, hash).map_err(|e| e.to_string())
+    } else {
+        Err("unsupported password hash, only bcrypt and apr1 are accepted".to_string())
+    }
+}
+
+// Apache's variant of the md5 crypt scheme
+fn apr1(password: &[u8], salt: &[u8]) -> String {
+    let salt = &salt[..salt.len().min(8)];
+
+    let alternate = Md5::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+
+    let mut ctx = Md5::new()
+        .chain_update(password)
+        .chain_update(APR1_MAGIC)
+        .chain_update(salt);
+    for chunk in password.chunks(16) {
+        ctx.update(&alternate[..chunk.len()]);
+    }
+    let mut i = password.len();
+    while i > 0 {
+        if i & 1 == 1 {
+            ctx.update([0u8]);
+        } else {
+            ctx.update(&password[..1]);
+        }
+        i >>= 1;
+    }
+    let mut digest = ctx.finalize();
+
+    for round in 0..1000 {
+        let mut ctx = Md5::new();
+        if round & 1 == 1 {
+            ctx.update(password);
+        } else {
+            ctx.update(digest);
+        }
+        if round % 3 != 0 {
+            ctx.update(salt);
+        }
+        if round % 7 != 0 {
+            ctx.update(password);
+        }
+        if round & 1 == 1 {
+            ctx.update(digest);
+        } else {
+            ctx.update(password);
+        }
+        digest = ctx.finalize();
+    }
+
+    let mut encoded = String::with_capacity(22);
+    let mut push = |value: u32, chars: usize| {
+        for n in 0..chars {
+            encoded.push(CRYPT_ALPHABET[((value >> (6 * n)) & 0x3f) as usize] as char);
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        push(
+            (digest[a] as u32) << 16 | (digest[b] as u32) << 8 | digest[c] as u32,
+            4,
+        );
+    }
+    push(digest[11] as u32, 2);
+
+    format!(
+        "{}{}${}",
+        APR1_MAGIC,
+        String::from_utf8_lossy(salt),
+        encoded
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made with openssl passwd -apr1 -salt abcdefgh secret
+    const APR1_SECRET: &str = "$apr1$abcdefgh$h9FWgUz3n9YxylKLlR5SQ/";
+    // the U*U vector of crypt_blowfish
+    const BCRYPT_UU: &str = "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW";
+
+    #[test]
+    fn parses_entries_and_skips_comments() {
+        let users = parse(&format!(
+            "# staff\n\nalice:{}\n  bob:{}  \nbroken line\n",
+            APR1_SECRET, BCRYPT_UU
+        ));
+        assert_eq!(users.len(), 2);
+        assert_eq!(users["alice"], APR1_SECRET);
+        assert_eq!(users["bob"], BCRYPT_UU);
+    }
+
+    #[test]
+    fn checks_apr1() {
+        assert_eq!(verify("secret", APR1_SECRET), Ok(true));
+        assert_eq!(verify("Secret", APR1_SECRET), Ok(false));
+        // longer than one md5 block
+        assert_eq!(
+            verify(
+                "a password longer than sixteen bytes",
+                "$apr1$Kq3yPbHd$csPRsJbBgaCHd99piUNMV/"
+            ),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn checks_bcrypt() {
+        assert_eq!(verify("U*U", BCRYPT_UU), Ok(true));
+        assert_eq!(verify("U*V", BCRYPT_UU), Ok(false));
+    }
+
+    #[test]
+    fn refuses_other_hashes() {
+        assert!(verify("secret", "secret").is_err());
+        assert!(verify("secret", "{SHA}5en6G6MezRroT3XKqkdPOmY/BfQ=").is_err());
+    }
+}
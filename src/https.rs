@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, uri::Authority, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::net::TcpListener;
+
+// --hsts without --hsts-max-age, a day: on a LAN the certificate or the TLS setup changes now
+// and then, browsers should not refuse plain HTTP for long after that
+pub const HSTS_MAX_AGE: u64 = 24 * 60 * 60;
+
+// the link the redirects lead to
+#[derive(Clone)]
+struct Target {
+    // the host of the link printed at startup, for clients that send no Host
+    host: String,
+    // the port of the HTTPS listener
+    port: u16,
+}
+
+// --redirect-http PORT, a plain HTTP listener that sends every request to the same path on the
+// HTTPS one. it ends with the server
+pub async fn redirect(listener: TcpListener, host: String, port: u16) {
+    let app = Router::new()
+        .fallback(to_https)
+        .with_state(Target { host, port });
+    match tokio::net::TcpListener::from_std(listener) {
+        Ok(listener) => {
+            if let Err(err) = axum::serve(listener, app).await {
+                log::error!("[HTTPS] the redirect listener stopped: {}", err);
+            }
+        }
+        Err(err) => log::error!("[HTTPS] cannot run the redirect listener: {}", err),
+    }
+}
+
+// the host the client asked for, the port is the one of HTTPS and left out when it is 443
+async fn to_https(State(target): State<Target>, req: Request) -> Response {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<Authority>().ok())
+        .map_or(target.host.clone(), |authority| {
+            authority.host().to_string()
+        });
+    let port = if target.port == 443 {
+        String::new()
+    } else {
+        format!(":{}", target.port)
+    };
+    let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+    let location = format!("https://{}{}{}", host, port, path);
+    match HeaderValue::from_str(&location) {
+        Ok(location) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, location)],
+        )
+            .into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+// Strict-Transport-Security on every response of the HTTPS listener, browsers ignore it over
+// plain HTTP anyway
+pub async fn hsts(State(value): State<HeaderValue>, req: Request, next: Next) -> Response {
+    let mut res = next.run(req).await;
+    res.headers_mut()
+        .insert(header::STRICT_TRANSPORT_SECURITY, value);
+    res
+}
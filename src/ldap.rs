@@ -0,0 +1,72 @@
+use ldap3::{dn_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// validates user names and passwords by binding to an LDAP / Active Directory server
+pub struct LdapAuth {
+    url: String,
+    // bind dn with a {user} placeholder, e.g. uid={user},ou=people,dc=example,dc=org
+    user_dn: String,
+    // group dn and the role granted to its members
+    group_roles: Vec<(String, String)>,
+}
+
+impl LdapAuth {
+    pub fn new(url: &str, user_dn: &str, group_roles: Vec<(String, String)>) -> Self {
+        LdapAuth {
+            url: url.to_string(),
+            user_dn: user_dn.to_string(),
+            group_roles,
+        }
+    }
+
+    // binds as the user, returns the roles mapped from the user groups
+    pub async fn authenticate(&self, user: &str, password: &str) -> Result<Vec<String>, String> {
+        // an empty password would be an anonymous bind that always succeeds
+        if user.is_empty() || password.is_empty() {
+            return Err("empty user name or password".to_string());
+        }
+
+        let settings = LdapConnSettings::new().set_conn_timeout(CONNECT_TIMEOUT);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url)
+            .await
+            .map_err(|e| format!("cannot connect to {}: {}", self.url, e))?;
+        ldap3::drive!(conn);
+
+        let dn = self.user_dn.replace("{user}", &dn_escape(user));
+        ldap.simple_bind(&dn, password)
+            .await
+            .and_then(|res| res.success())
+            .map_err(|e| format!("bind as {} failed: {}", dn, e))?;
+
+        let roles = if self.group_roles.is_empty() {
+            Vec::new()
+        } else {
+            let (entries, _) = ldap
+                .search(&dn, Scope::Base, "(objectClass=*)", vec!["memberOf"])
+                .await
+                .and_then(|res| res.success())
+                .map_err(|e| format!("cannot read groups of {}: {}", dn, e))?;
+            let groups: Vec<String> = entries
+                .into_iter()
+                .map(SearchEntry::construct)
+                .flat_map(|entry| entry.attrs.get("memberOf").cloned().unwrap_or_default())
+                .collect();
+
+            let roles: Vec<String> = self
+                .group_roles
+                .iter()
+                .filter(|(group, _)| groups.iter().any(|g| g.eq_ignore_ascii_case(group)))
+                .map(|(_, role)| role.clone())
+                .collect();
+            if roles.is_empty() {
+                return Err(format!("{} is not member of a mapped group", dn));
+            }
+            roles
+        };
+
+        let _ = ldap.unbind().await;
+        Ok(roles)
+    }
+}
@@ -0,0 +1,320 @@
+use crate::{base_path, thumbnails, utils, FileRow};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+// how a folder listing is sent, picked from the Accept header of the request
+#[derive(PartialEq)]
+pub enum Format {
+    Html,
+    Json,
+    Text,
+}
+
+// ?sort=name|size|modified&order=asc|desc, folders always come first
+#[derive(Deserialize, Default)]
+pub struct SortParams {
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    order: Order,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+}
+
+impl Order {
+    pub fn name(self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+}
+
+impl SortParams {
+    pub fn key(&self) -> SortKey {
+        self.sort
+    }
+
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    pub fn sort(&self, rows: &mut [FileRow]) {
+        rows.sort_by(|a, b| {
+            let by_key = match self.sort {
+                SortKey::Name => Ordering::Equal,
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Modified => a.modified.cmp(&b.modified),
+            }
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            let by_key = match self.order {
+                Order::Asc => by_key,
+                Order::Desc => by_key.reverse(),
+            };
+            b.is_dir.cmp(&a.is_dir).then(by_key)
+        });
+    }
+
+    // the column header link, it flips the order of the current column, the largest and
+    // newest files come first on a new one
+    pub fn link(&self, key: SortKey) -> String {
+        let order = match (self.sort == key, key) {
+            (true, _) if self.order == Order::Asc => Order::Desc,
+            (true, _) => Order::Asc,
+            (false, SortKey::Name) => Order::Asc,
+            (false, _) => Order::Desc,
+        };
+        format!("?sort={}&order={}", key.name(), order.name())
+    }
+}
+
+// ?view=gallery, the images of a folder as a grid of tiles instead of the table
+#[derive(Deserialize, Default)]
+pub struct LayoutParams {
+    #[serde(default)]
+    view: Layout,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    List,
+    Gallery,
+}
+
+impl LayoutParams {
+    pub fn layout(&self) -> Layout {
+        self.view
+    }
+
+    // the listing in the same order with the other layout
+    pub fn toggle(&self, sort: &SortParams) -> String {
+        let link = format!("?sort={}&order={}", sort.sort.name(), sort.order.name());
+        match self.view {
+            Layout::List => format!("{}&view=gallery", link),
+            Layout::Gallery => link,
+        }
+    }
+}
+
+// the gallery is offered when at least half of the files are images
+pub fn mostly_images(rows: &[FileRow]) -> bool {
+    let files = rows.iter().filter(|row| !row.is_dir);
+    let (images, total) = files.fold((0, 0), |(images, total), row| {
+        (images + thumbnails::is_image(&row.name) as usize, total + 1)
+    });
+    images > 0 && images * 2 >= total
+}
+
+// ?page=2&per_page=500, big folders are sent a page at a time
+#[derive(Deserialize)]
+pub struct PageParams {
+    #[serde(default = "first_page")]
+    page: usize,
+    #[serde(default = "default_per_page")]
+    per_page: usize,
+}
+
+pub const PER_PAGE: usize = 500;
+const MAX_PER_PAGE: usize = 10_000;
+
+fn first_page() -> usize {
+    1
+}
+
+fn default_per_page() -> usize {
+    PER_PAGE
+}
+
+// the page sent, numbered from 1
+#[derive(Serialize, Clone, Copy)]
+pub struct Page {
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+    pub pages: usize,
+}
+
+impl Page {
+    // link to another page of the listing in the same order and layout
+    pub fn link(&self, sort: &SortParams, layout: &LayoutParams, page: usize) -> String {
+        let mut link = format!(
+            "?sort={}&order={}&page={}",
+            sort.sort.name(),
+            sort.order.name(),
+            page
+        );
+        if self.per_page != PER_PAGE {
+            link.push_str(&format!("&per_page={}", self.per_page));
+        }
+        if layout.view == Layout::Gallery {
+            link.push_str("&view=gallery");
+        }
+        link
+    }
+}
+
+impl PageParams {
+    // keeps the rows of the requested page, a page past the end shows the last one
+    pub fn cut(&self, rows: &mut Vec<FileRow>) -> Page {
+        let per_page = self.per_page.clamp(1, MAX_PER_PAGE);
+        let total = rows.len();
+        let pages = total.div_ceil(per_page).max(1);
+        let page = self.page.clamp(1, pages);
+        rows.truncate(page * per_page);
+        rows.drain(..(page - 1) * per_page);
+        Page {
+            page,
+            per_page,
+            total,
+            pages,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Listing<'a> {
+    path: &'a str,
+    #[serde(flatten)]
+    page: Option<Page>,
+    entries: Vec<Entry<'a>>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    size: Option<u64>,
+    modified: Option<String>,
+    href: String,
+}
+
+// the format with the highest quality in Accept, browsers list text/html first so they
+// keep the page, a missing header or */* alone also means HTML
+pub fn format(headers: &HeaderMap) -> Format {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|h| h.to_str().ok()) else {
+        return Format::Html;
+    };
+    let mut best = (Format::Html, 0.0);
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let format = match media.as_str() {
+            "text/html" | "application/xhtml+xml" | "*/*" => Format::Html,
+            "application/json" => Format::Json,
+            "text/plain" => Format::Text,
+            _ => continue,
+        };
+        // on equal quality the earlier media range wins
+        if quality > best.1 {
+            best = (format, quality);
+        }
+    }
+    best.0
+}
+
+// the listing of a folder, `html` renders the page for browsers. `page` tells JSON clients
+// which part of a paginated folder they got
+pub fn respond(
+    format: Format,
+    rows: Vec<FileRow>,
+    current_path: &str,
+    page: Option<Page>,
+    html: impl FnOnce(Vec<FileRow>) -> Body,
+) -> Response {
+    let mut res = match format {
+        Format::Html => Html(html(rows)).into_response(),
+        Format::Json => Json(Listing {
+            path: current_path,
+            page,
+            entries: rows.iter().map(|row| entry(row, current_path)).collect(),
+        })
+        .into_response(),
+        Format::Text => text(&rows).into_response(),
+    };
+    // caches must not hand the page to curl or the JSON to a browser
+    res.headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept"));
+    res
+}
+
+fn entry<'a>(row: &'a FileRow, current_path: &str) -> Entry<'a> {
+    let path = if current_path.is_empty() {
+        row.name.clone()
+    } else {
+        format!("{}/{}", current_path, row.name)
+    };
+    let route = if row.is_dir { "browse" } else { "download" };
+    Entry {
+        name: &row.name,
+        kind: if row.is_dir { "dir" } else { "file" },
+        size: (!row.is_dir).then_some(row.size),
+        modified: row.modified.map(|modified| {
+            DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, true)
+        }),
+        href: base_path::link(&format!("/{}/{}", route, utils::encode_path(&path))),
+    }
+}
+
+// one entry per line like ls -l, size in bytes, folders end with a slash
+fn text(rows: &[FileRow]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let modified = row
+            .modified
+            .map(|modified| {
+                DateTime::<Utc>::from(modified)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let (size, slash) = if row.is_dir {
+            ("-".to_string(), "/")
+        } else {
+            (row.size.to_string(), "")
+        };
+        out.push_str(&format!(
+            "{:>12}  {}  {}{}\n",
+            size, modified, row.name, slash
+        ));
+    }
+    out
+}
@@ -0,0 +1,159 @@
+use crate::{errors::error_response, AppState};
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex, OnceLock},
+    time::Duration,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// changes coming in this close together are sent as one, copying a file changes it many times
+const SETTLE: Duration = Duration::from_millis(300);
+
+// --live, the served folders are watched and the open listings are told when an entry of their
+// folder comes, goes or changes
+struct Live {
+    // the folder of every change, relative to the root. gone once the server stops, which ends
+    // the streams of the pages
+    changes: Mutex<Option<broadcast::Sender<String>>>,
+    // watching stops when it is dropped
+    _watcher: Mutex<RecommendedWatcher>,
+}
+
+static LIVE: OnceLock<Live> = OnceLock::new();
+
+// watches every folder below the roots, mounted ones are listed below their name
+pub fn enable(roots: Vec<(String, PathBuf)>) -> notify::Result<()> {
+    // some systems report the changes with the resolved path
+    let roots: Vec<(String, PathBuf)> = roots
+        .into_iter()
+        .map(|(name, root)| (name, root.canonicalize().unwrap_or(root)))
+        .collect();
+    let (changed, settle) = mpsc::channel::<String>();
+    let watched = roots.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for path in &event.paths {
+            if let Some(folder) = folder_of(&watched, path) {
+                let _ = changed.send(folder);
+            }
+        }
+    })?;
+    for (_, root) in &roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    let _ = LIVE.set(Live {
+        changes: Mutex::new(Some(broadcast::channel(64).0)),
+        _watcher: Mutex::new(watcher),
+    });
+    std::thread::spawn(move || {
+        while let Ok(first) = settle.recv() {
+            let mut folders = HashSet::from([first]);
+            while let Ok(folder) = settle.recv_timeout(SETTLE) {
+                folders.insert(folder);
+            }
+            let Some(live) = LIVE.get() else {
+                return;
+            };
+            let changes = live.changes.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(sender) = changes.as_ref() else {
+                return;
+            };
+            for folder in folders {
+                let _ = sender.send(folder);
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    LIVE.get().is_some()
+}
+
+// ends the streams of the open pages, the server would wait for them to stop
+pub fn close() {
+    if let Some(live) = LIVE.get() {
+        live.changes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+}
+
+// the folder listing a changed entry, as the path of its listing
+fn folder_of(roots: &[(String, PathBuf)], path: &Path) -> Option<String> {
+    roots.iter().find_map(|(name, root)| {
+        let below = path.strip_prefix(root).ok()?;
+        let mut folder: Vec<String> = below
+            .parent()?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        if !name.is_empty() {
+            folder.insert(0, name.clone());
+        }
+        Some(folder.join("/"))
+    })
+}
+
+// /events/<folder>, a stream of Server-Sent Events with a `change` event whenever the listing
+// of the folder changed
+pub async fn events(State(state): State<AppState>, path: Option<AxumPath<String>>) -> Response {
+    let Some(live) = LIVE.get() else {
+        return error_response(StatusCode::NOT_FOUND, "Live updates are off");
+    };
+    let folder = path.map_or(String::new(), |AxumPath(path)| {
+        path.trim_matches('/').to_string()
+    });
+    // Security check: prevent directory traversal attacks
+    if folder.contains("..") || folder.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid folder path");
+    }
+    if !state.within_depth(&folder, 0) || state.exclude.matches(&folder) {
+        return error_response(StatusCode::NOT_FOUND, "Folder not found");
+    }
+    let Some(changes) = live
+        .changes
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(broadcast::Sender::subscribe)
+    else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "The server is stopping");
+    };
+
+    let stream =
+        futures_util::stream::unfold((changes, folder), |(mut changes, folder)| async move {
+            loop {
+                match changes.recv().await {
+                    Ok(changed) if changed != folder => continue,
+                    // some changes were missed, this folder may be among them
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        let event = Event::default().event("change").data(folder.clone());
+                        return Some((Ok::<_, Infallible>(event), (changes, folder)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
@@ -0,0 +1,321 @@
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use chrono::Local;
+use log::{
+    kv::{self, VisitSource},
+    LevelFilter, Record,
+};
+use log4rs::{
+    append::{
+        console::{ConsoleAppender, Target},
+        file::FileAppender,
+        rolling_file::{
+            policy::compound::{
+                roll::{delete::DeleteRoller, fixed_window::FixedWindowRoller, Roll},
+                trigger::{
+                    size::SizeTrigger,
+                    time::{TimeTrigger, TimeTriggerConfig, TimeTriggerInterval},
+                    Trigger,
+                },
+                CompoundPolicy,
+            },
+            LogFile, RollingFileAppender,
+        },
+        Append,
+    },
+    config::{Appender, Config, Root},
+    encode::{pattern::PatternEncoder, Encode, Write},
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::VecDeque, error::Error, net::SocketAddr, path::Path, str::FromStr, sync::Mutex,
+    time::Instant,
+};
+
+// --log-format, lines for people or one JSON object per event for Loki, ELK and the like
+#[derive(Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {}", s)),
+        }
+    }
+}
+
+// --log-max-size, --log-rotate and --log-keep: the log file is set aside as file.1, file.2 and
+// so on once it is big or old enough, the oldest beyond the kept ones are removed
+pub struct Rotation {
+    pub max_size: Option<u64>,
+    pub every: Option<Period>,
+    pub keep: u32,
+}
+
+#[derive(Clone, Copy)]
+pub enum Period {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hourly" => Ok(Period::Hourly),
+            "daily" => Ok(Period::Daily),
+            "weekly" => Ok(Period::Weekly),
+            _ => Err(format!("unknown rotation period {}", s)),
+        }
+    }
+}
+
+// where the log shows in the terminal
+#[derive(Clone, Copy, PartialEq)]
+pub enum Console {
+    Stderr,
+    // the last lines are kept for the dashboard of --tui, which owns the terminal
+    Dashboard,
+    // --quiet
+    Off,
+}
+
+// the last lines of the log, the dashboard shows the ones that fit
+const RECENT: usize = 200;
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// the log file and the terminal, either can be turned off. the terminal gets the log on stderr
+// so it does not mix with the banner and --print-json on stdout. the log file is only created
+// once the log is started, failing to open it leaves the terminal logging
+pub fn start(
+    file: Option<&Path>,
+    rotation: &Rotation,
+    console: Console,
+    level: LevelFilter,
+    format: LogFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::builder();
+    let mut root = Root::builder();
+    let mut failed = None;
+    if let Some(path) = file {
+        match log_file(path, rotation, format) {
+            Ok(logfile) => {
+                config = config.appender(Appender::builder().build("logfile", logfile));
+                root = root.appender("logfile");
+            }
+            Err(err) => failed = Some(err),
+        }
+    }
+    match console {
+        Console::Stderr => {
+            let terminal = ConsoleAppender::builder()
+                .target(Target::Stderr)
+                .encoder(encoder(format))
+                .build();
+            config = config.appender(Appender::builder().build("console", Box::new(terminal)));
+            root = root.appender("console");
+        }
+        Console::Dashboard => {
+            config = config.appender(Appender::builder().build("recent", Box::new(Recent)));
+            root = root.appender("recent");
+        }
+        Console::Off => {}
+    }
+
+    log4rs::init_config(config.build(root.build(level))?)?;
+    match failed {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+// without a size nor a period the file grows for as long as the server runs
+fn log_file(
+    path: &Path,
+    rotation: &Rotation,
+    format: LogFormat,
+) -> Result<Box<dyn Append>, Box<dyn Error>> {
+    let mut triggers: Vec<Box<dyn Trigger>> = Vec::new();
+    if let Some(max_size) = rotation.max_size {
+        triggers.push(Box::new(SizeTrigger::new(max_size)));
+    }
+    if let Some(every) = rotation.every {
+        let interval = match every {
+            Period::Hourly => TimeTriggerInterval::Hour(1),
+            Period::Daily => TimeTriggerInterval::Day(1),
+            Period::Weekly => TimeTriggerInterval::Week(1),
+        };
+        // on the hour, at midnight or on monday rather than counted from the start
+        triggers.push(Box::new(TimeTrigger::new(TimeTriggerConfig {
+            interval,
+            modulate: true,
+            max_random_delay: 0,
+        })));
+    }
+    if triggers.is_empty() {
+        let file = FileAppender::builder()
+            .encoder(encoder(format))
+            .build(path)?;
+        return Ok(Box::new(file));
+    }
+    let roller: Box<dyn Roll> = if rotation.keep == 0 {
+        Box::new(DeleteRoller::new())
+    } else {
+        let pattern = format!("{}.{{}}", path.display());
+        Box::new(FixedWindowRoller::builder().build(&pattern, rotation.keep)?)
+    };
+    let policy = CompoundPolicy::new(Box::new(AnyOf(triggers)), roller);
+    let file = RollingFileAppender::builder()
+        .encoder(encoder(format))
+        .build(path, Box::new(policy))?;
+    Ok(Box::new(file))
+}
+
+// rotates once the first of the size and the period is reached
+#[derive(Debug)]
+struct AnyOf(Vec<Box<dyn Trigger>>);
+
+impl Trigger for AnyOf {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        for trigger in &self.0 {
+            if trigger.trigger(file)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn is_pre_process(&self) -> bool {
+        self.0.iter().any(|trigger| trigger.is_pre_process())
+    }
+}
+
+// the newest last
+pub fn recent(count: usize) -> Vec<String> {
+    let lines = RECENT_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(count))
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug)]
+struct Recent;
+
+impl Append for Recent {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let line = format!(
+            "{} {} - {}",
+            Local::now().format("%H:%M:%S"),
+            record.level(),
+            record.args()
+        );
+        let mut lines = RECENT_LINES.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == RECENT {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+fn encoder(format: LogFormat) -> Box<dyn Encode> {
+    match format {
+        LogFormat::Text => Box::new(PatternEncoder::new("[{d(%d-%m-%y %H:%M:%S)}] {l} - {m}{n}")),
+        LogFormat::Json => Box::new(JsonEncoder),
+    }
+}
+
+// a line of JSON per event: ts, level and message, and the fields the event carries like the
+// client, path and status of a request
+#[derive(Debug)]
+struct JsonEncoder;
+
+impl Encode for JsonEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record) -> anyhow::Result<()> {
+        let mut event = Map::new();
+        event.insert(
+            "ts".to_string(),
+            Value::from(Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        event.insert(
+            "level".to_string(),
+            Value::from(record.level().as_str().to_lowercase()),
+        );
+        event.insert("target".to_string(), Value::from(record.target()));
+        event.insert(
+            "message".to_string(),
+            Value::from(record.args().to_string()),
+        );
+        let _ = record.key_values().visit(&mut Fields(&mut event));
+        serde_json::to_writer(&mut *w, &event)?;
+        w.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(number) = value.to_u64() {
+            Value::from(number)
+        } else if let Some(flag) = value.to_bool() {
+            Value::from(flag)
+        } else {
+            Value::from(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+// with --log-format json every request is an event of its own, with the client after
+// --trusted-proxy, the status the client got and how long the handler took
+pub async fn access(req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or("-".to_string(), |ConnectInfo(addr)| addr.ip().to_string());
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+
+    let res = next.run(req).await;
+
+    let bytes = res
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<u64>().ok())
+        .unwrap_or(0);
+    let duration_ms = started.elapsed().as_millis() as u64;
+    log::info!(
+        target: "access",
+        client = client.as_str(),
+        method = method.as_str(),
+        path = path.as_str(),
+        status = res.status().as_u16(),
+        bytes = bytes,
+        duration_ms = duration_ms;
+        "[ACCESS] {} {} {} {}", client, method, path, res.status().as_u16()
+    );
+    res
+}
@@ -1,32 +1,278 @@
+mod access;
+mod admin;
+mod archive;
+mod assets;
+mod audio;
+mod auth;
+mod auth_log;
+mod base_path;
+mod cache_control;
+mod compression;
+mod conditional;
+mod config;
+mod dashboard;
+mod dav;
+mod delete;
+mod dir_sizes;
+mod doctor;
+mod errors;
+mod exclude;
+mod export;
+mod filter;
+mod hash;
+mod htpasswd;
+mod https;
+mod ldap;
+mod listing;
+mod live;
+mod logging;
+mod markdown;
+mod mdns;
+mod meta;
+mod mounts;
+mod oidc;
+mod packed;
+mod qr;
+mod range;
+mod receive;
+mod sandbox;
+mod search;
+mod settings;
+mod share;
+mod shutdown;
+mod signed_url;
+mod single;
+mod site;
+mod spa;
+mod stats;
+mod symlinks;
+#[cfg(all(unix, feature = "pam"))]
+mod system_auth;
+#[cfg(unix)]
+mod systemd;
+mod templates;
+mod throttle;
+mod thumbnails;
+mod tls;
+#[cfg(feature = "transcode")]
+mod transcode;
+mod transfers;
+mod upload;
+mod upload_hook;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+mod users;
 mod utils;
+mod video;
+mod visibility;
+mod webhook;
 
 use axum::{
     body::Body,
-    extract::{ConnectInfo, Path as AxumPath, State},
-    http::{header, HeaderMap, HeaderValue, StatusCode},
-    response::{Html, IntoResponse, Response},
-    routing::get,
-    Router,
+    extract::DefaultBodyLimit,
+    extract::{ConnectInfo, Path as AxumPath, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware,
+    response::Response,
+    routing::{any, delete, get, post},
+    Extension, Router,
 };
 
+use access::{RateLimiter, StreamGuard, StreamLimiter, TrustedProxies};
+use auth::{Auth, User};
+use bytes::Bytes;
+use cache_control::{CachePolicy, FileContent};
 use chrono::{DateTime, Local};
-use clap::{Arg, Command};
-use log;
+use clap::{Arg, ArgAction, Command};
+use conditional::Validators;
+use errors::{error_response, io_error_status};
+use exclude::ExcludeRules;
+use filter::FileFilter;
+use futures_util::{Stream, StreamExt};
+use htpasswd::Htpasswd;
+use ldap::LdapAuth;
+use listing::{Layout, LayoutParams, PageParams, SortKey, SortParams};
+use log::LevelFilter;
+use logging::{Console, LogFormat, Rotation};
+use mounts::Mounts;
+use packed::Packed;
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use range::Requested;
+use receive::DropBox;
+use serde::Serialize;
+use settings::Settings;
+use share::ShareLinks;
+use shutdown::Shutdown;
+use signed_url::UrlSigner;
+use socket2::{Domain, Protocol, Socket, Type};
+use templates::{Branding, Theme};
+use tls::TlsSource;
+use users::Users;
+use visibility::{Anonymous, Visibility, VisibilityRules};
 
 use std::{
+    collections::HashMap,
+    convert::Infallible,
     env,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
-    sync::Mutex,
+    sync::Arc,
     time::SystemTime,
 };
-use tokio::{fs, io::AsyncReadExt};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
 use tokio_util::io::ReaderStream;
 
 #[derive(Clone)]
 struct AppState {
     root: PathBuf,
+    // roots selected by the Host header, --host name=path
+    host_roots: Arc<HashMap<String, PathBuf>>,
+    stream_limiter: Option<Arc<StreamLimiter>>,
+    visibility: Arc<VisibilityRules>,
+    filter: FileFilter,
+    // folder levels below the root that can be browsed, --max-depth
+    max_depth: Option<usize>,
+    // --exclude globs and the dotfiles, --show-hidden shares the latter
+    exclude: Arc<ExcludeRules>,
+    // uploads, WebDAV writes and read-only mode, changed at runtime by /admin
+    settings: Arc<Settings>,
+    // --write-role, the role a user needs for the changes the settings allow
+    write_role: Option<Arc<str>>,
+    // /s/<token> links, --url-secret
+    shares: Option<Arc<ShareLinks>>,
+    // file-serve receive, renames clashing uploads and keeps them within --quota
+    drop_box: Option<Arc<DropBox>>,
+    // --max-downloads and --timeout
+    shutdown: Arc<Shutdown>,
+    // --folder NAME=PATH, the default root is then made of these folders
+    mounts: Option<Arc<Mounts>>,
+    // --archive FILE, its entries are then the default root
+    packed: Option<Arc<Packed>>,
+}
+
+// a path below the root, see AppState::locate
+enum Located<'a> {
+    // the folder it is below on disk and the path below that folder
+    Disk(&'a Path, &'a str),
+    // the root listing of the --folder mounts, one entry per mount
+    Mounts(&'a Mounts),
+    // the --archive file and the path of the entry inside it
+    Packed(&'a Packed, &'a str),
+}
+
+impl AppState {
+    // where a path below the root is: below the root of the logged in user, else the one
+    // mapped to the request Host header, else inside the --archive file, the --folder mount it
+    // starts with or the default root
+    fn locate<'a>(
+        &'a self,
+        headers: &HeaderMap,
+        user: Option<&'a User>,
+        path: &'a str,
+    ) -> Result<Located<'a>, (StatusCode, String)> {
+        let own_root = user.and_then(|user| user.root.as_deref()).or_else(|| {
+            self.host_name(headers)
+                .and_then(|name| self.host_roots.get(name))
+                .map(PathBuf::as_path)
+        });
+        if let (None, Some(packed)) = (own_root, self.packed.as_deref()) {
+            return Ok(Located::Packed(packed, path));
+        }
+        match (own_root, self.mounts.as_deref()) {
+            (Some(root), _) => Ok(Located::Disk(root, path)),
+            (None, Some(mounts)) if path.trim_matches('/').is_empty() => {
+                Ok(Located::Mounts(mounts))
+            }
+            (None, Some(mounts)) => mounts
+                .split(path)
+                .map(|(folder, below)| Located::Disk(folder, below))
+                .ok_or_else(|| (StatusCode::NOT_FOUND, "Folder not found".to_string())),
+            (None, None) => Ok(Located::Disk(&self.root, path)),
+        }
+    }
+
+    // the --host name the request Host header maps to a root of
+    fn host_name(&self, headers: &HeaderMap) -> Option<&str> {
+        headers
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .map(|host| host.rsplit_once(':').map_or(host, |(name, _)| name))
+            .and_then(|name| self.host_roots.get_key_value(&name.to_lowercase()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    // whether the user may upload, delete and write through WebDAV, anyone may without
+    // --write-role
+    fn may_write(&self, user: Option<&User>) -> bool {
+        self.write_role
+            .as_deref()
+            .is_none_or(|role| user.is_some_and(|user| user.roles.iter().any(|r| r == role)))
+    }
+
+    // download slot of the client when the streams per address are limited
+    fn stream_guard(&self, addr: SocketAddr) -> Result<Option<StreamGuard>, (StatusCode, String)> {
+        let Some(limiter) = &self.stream_limiter else {
+            return Ok(None);
+        };
+        match limiter.acquire(addr.ip()) {
+            Some(guard) => Ok(Some(guard)),
+            None => {
+                log::warn!("[LIMIT] too many parallel downloads from {}", addr.ip());
+                Err((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Too many parallel downloads from your address.".to_string(),
+                ))
+            }
+        }
+    }
+
+    // drops the entries that are not shared, sorts by name ascending, directories first.
+    // visitors of a public folder without a login only keep its public entries, links left out
+    fn shared_rows(&self, rows: &mut Vec<FileRow>, current_path: &str, anonymous: bool) {
+        rows.retain(|row| {
+            let row_path = format!("{}/{}", current_path, row.name);
+            let shared = if row.is_dir {
+                self.within_depth(&row_path, 0)
+            } else {
+                self.filter.matches(row.size, row.modified)
+            };
+            shared
+                && (self.exclude.show_hidden || !row.hidden)
+                && !self.exclude.matches(&row_path)
+                && !self.visibility.is_hidden(&row_path)
+                && (!anonymous || (self.visibility.is_public(&row_path) && !row.link))
+        });
+
+        // Sort by name ascending, directories first
+        rows.sort_by(|a, b| {
+            if a.is_dir != b.is_dir {
+                return b.is_dir.cmp(&a.is_dir); // directories first
+            }
+            a.name.to_lowercase().cmp(&b.name.to_lowercase())
+        });
+    }
+
+    // whether a path relative to the root is at most `extra` levels below the deepest folder
+    // that may be browsed, files count as one level below their folder
+    fn within_depth(&self, path: &str, extra: usize) -> bool {
+        let depth = Path::new(path.trim_matches('/')).components().count();
+        self.max_depth.is_none_or(|max| depth <= max + extra)
+    }
+}
+
+// how the listing links to folders and files
+#[derive(Clone, Copy, PartialEq)]
+enum LinkStyle<'a> {
+    // /browse and /download routes of the running server
+    Server,
+    // relative links between exported index.html pages
+    Static,
+    // links below a share link, /s/<token>
+    Share(&'a str),
 }
 
 struct FileRow {
@@ -34,291 +280,2487 @@ struct FileRow {
     size: u64,
     modified: Option<SystemTime>,
     is_dir: bool,
+    // hidden by its attribute on Windows
+    hidden: bool,
+    // a symbolic link, followed as --follow-symlinks says
+    link: bool,
+}
+
+const LOG_PATH: &str = "logs/file_serve.log";
+// the log file is rotated at this size unless --log-max-size says otherwise, and this many old
+// ones are kept
+const LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const LOG_KEEP: u32 = 5;
+// ports tried above one in use with --find-port
+const PORT_SCAN: u16 = 100;
+
+fn main() {
+    let cli = Command::new("file-serve")
+        .version("0.6")
+        .about("Serve files through your LAN")
+        .subcommand(
+            Command::new("serve")
+                .about("Serve the folder, the default when no command is given."),
+        )
+        .subcommand(
+            Command::new("share")
+                .about("Serve a single file and stop after its first complete download.")
+                .arg(Arg::new("file").value_name("FILE").required(true)),
+        )
+        .subcommand(
+            Command::new("receive")
+                .about("Let visitors upload files into DIR without seeing its content.")
+                .arg(Arg::new("dir").value_name("DIR").required(true))
+                .arg(
+                    Arg::new("quota")
+                        .long("quota")
+                        .value_name("SIZE")
+                        .help("Refuse uploads once the files in DIR take up SIZE, e.g. 10G."),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check templates, folders, port, interfaces and the log folder, then exit."),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the listings of the folder as static HTML pages next to the files.")
+                .arg(Arg::new("out-dir").value_name("OUT_DIR").required(true))
+                .arg(
+                    Arg::new("link")
+                        .long("link")
+                        .action(ArgAction::SetTrue)
+                        .help("Symlink the files instead of copying them."),
+                ),
+        )
+        .subcommand(
+            Command::new("link")
+                .about("Print a share link to a file or folder, needs --url-secret.")
+                .arg(Arg::new("path").value_name("PATH").required(true))
+                .arg(
+                    Arg::new("ttl")
+                        .long("ttl")
+                        .value_name("AGE")
+                        .help("Time the link stays valid, e.g. 30m, 12h or 7d, defaults to 1d."),
+                )
+                .arg(
+                    Arg::new("once")
+                        .long("once")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("max-downloads")
+                        .help("Stop the link after the first completed download."),
+                ),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("TOML file with defaults for the options, the command line overrides it."),
+        )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .action(ArgAction::SetTrue)
+                .help("Print the effective configuration and exit."),
+        )
+        .arg(
+            Arg::new("port")
+                .short('p')
+                .long("port")
+                .value_name("P")
+                .help("Server port, defaults to 8080. 0 takes any free port."),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Log file, defaults to logs/file_serve.log. none writes no log file."),
+        )
+        .arg(
+            Arg::new("log-max-size")
+                .long("log-max-size")
+                .value_name("SIZE")
+                .help("Rotate the log file once it reaches SIZE, defaults to 10M. 0 turns it off."),
+        )
+        .arg(
+            Arg::new("log-rotate")
+                .long("log-rotate")
+                .value_name("PERIOD")
+                .value_parser(["hourly", "daily", "weekly"])
+                .help("Rotate the log file every hour, day or week as well."),
+        )
+        .arg(
+            Arg::new("log-keep")
+                .long("log-keep")
+                .value_name("N")
+                .help("Rotated log files kept, defaults to 5."),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .value_parser(["off", "error", "warn", "info", "debug", "trace"])
+                .help("Least important events logged, defaults to info."),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .help("Log to the log file only, not to the terminal."),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .help("Format of the log file: text, or json for one object per event with every request logged."),
+        )
+        .arg(
+            Arg::new("auth-log")
+                .long("auth-log")
+                .value_name("PATH")
+                .help("Write a line per failed login to this file, for fail2ban or crowdsec."),
+        )
+        .arg(
+            Arg::new("live")
+                .long("live")
+                .action(ArgAction::SetTrue)
+                .help("Watch the folder and update the open listings when files are added or removed."),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .action(ArgAction::SetTrue)
+                .help("Count the downloads of every file, shown at /stats and /api/stats."),
+        )
+        .arg(
+            Arg::new("stats-file")
+                .long("stats-file")
+                .value_name("PATH")
+                .requires("stats")
+                .help("Keep the download statistics in this JSON file across restarts."),
+        )
+        .arg(
+            Arg::new("webhook")
+                .long("webhook")
+                .value_name("URL")
+                .action(ArgAction::Append)
+                .help("POST a JSON event to this URL on downloads, uploads and failed logins, can be repeated."),
+        )
+        .arg(
+            Arg::new("webhook-events")
+                .long("webhook-events")
+                .value_name("EVENTS")
+                .requires("webhook")
+                .help("Comma separated events sent to the webhooks: download-started, download-completed, upload-received, auth-failed. All by default."),
+        )
+        .arg(
+            Arg::new("tui")
+                .long("tui")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("print-json")
+                .help("Show a live dashboard of the downloads and the log in the terminal instead of the banner."),
+        )
+        .arg(
+            Arg::new("print-json")
+                .long("print-json")
+                .action(ArgAction::SetTrue)
+                .help("Print the address, port, folder and link as one line of JSON at startup instead of the banner."),
+        )
+        .arg(
+            Arg::new("find-port")
+                .long("find-port")
+                .action(ArgAction::SetTrue)
+                .help("When the port is in use, take the next free one above it."),
+        )
+        .arg(
+            Arg::new("folder")
+                .short('f')
+                .long("folder")
+                .value_name("f")
+                .action(ArgAction::Append)
+                .help("Folder to be served, default is current folder. Repeat as NAME=PATH to serve several folders side by side."),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_name("FILE")
+                .conflicts_with_all(["folder", "allow-upload", "allow-write"])
+                .help("Serve the entries of a .zip or .tar file as the folder, without extracting it."),
+        )
+        .arg(
+            Arg::new("spa")
+                .long("spa")
+                .action(ArgAction::SetTrue)
+                .help("Serve a built single page app: paths that are no file get the index.html of the folder, files are shown instead of downloaded."),
+        )
+        .arg(
+            Arg::new("site")
+                .long("site")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("spa")
+                .help("Serve the folder as a static website: folders show their index.html, files are shown instead of downloaded, listings only where there is no index.html."),
+        )
+        .arg(
+            Arg::new("host")
+                .long("host")
+                .value_name("NAME=PATH")
+                .action(ArgAction::Append)
+                .help("Serve PATH to requests for host NAME, can be repeated."),
+        )
+        .arg(
+            Arg::new("interface")
+                .short('i')
+                .long("interface")
+                .value_name("i")
+                .help("Interface to bind, default is first occurring interface."),
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("ADDRESS")
+                .help("Address to listen on, e.g. 127.0.0.1 behind an SSH tunnel or a reverse proxy, :: for IPv6 and IPv4. Defaults to all IPv4 interfaces."),
+        )
+        .arg(
+            Arg::new("base-path")
+                .long("base-path")
+                .value_name("PATH")
+                .help("Serve the app below PATH, e.g. /files behind a reverse proxy forwarding location /files/."),
+        )
+        .arg(
+            Arg::new("lan-only")
+                .long("lan-only")
+                .action(ArgAction::SetTrue)
+                .help("Reject clients outside private, link-local and loopback ranges."),
+        )
+        .arg(
+            Arg::new("max-downloads")
+                .long("max-downloads")
+                .value_name("N")
+                .help("Stop the server after N complete downloads, file-serve share defaults to 1. With link the link stops working instead."),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("AGE")
+                .help("Stop the server after AGE, e.g. 10m or 2h."),
+        )
+        .arg(
+            Arg::new("mdns-name")
+                .long("mdns-name")
+                .value_name("NAME")
+                .help("Name announced over mDNS/Bonjour, defaults to the host name."),
+        )
+        .arg(
+            Arg::new("cert")
+                .long("cert")
+                .value_name("FILE")
+                .requires("key")
+                .help("PEM certificate chain, serves HTTPS together with --key."),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .value_name("FILE")
+                .requires("cert")
+                .help("PEM private key of the --cert certificate."),
+        )
+        .arg(
+            Arg::new("tls-self-signed")
+                .long("tls-self-signed")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("cert")
+                .help("Serve HTTPS with a self-signed certificate generated at startup."),
+        )
+        .arg(
+            Arg::new("redirect-http")
+                .long("redirect-http")
+                .value_name("PORT")
+                .help("With HTTPS, answer plain HTTP on PORT with a redirect to the HTTPS link."),
+        )
+        .arg(
+            Arg::new("hsts")
+                .long("hsts")
+                .action(ArgAction::SetTrue)
+                .help("With HTTPS, tell browsers to only use HTTPS for this server (Strict-Transport-Security)."),
+        )
+        .arg(
+            Arg::new("hsts-max-age")
+                .long("hsts-max-age")
+                .value_name("SECONDS")
+                .requires("hsts")
+                .help("How long browsers remember --hsts, defaults to 86400, a day."),
+        )
+        .arg(
+            Arg::new("max-streams-per-ip")
+                .long("max-streams-per-ip")
+                .value_name("N")
+                .help("Maximum simultaneous downloads for a single client address."),
+        )
+        .arg(
+            Arg::new("rate-limit")
+                .long("rate-limit")
+                .value_name("N")
+                .help("Maximum requests per second for a single client address, more get 429 Too Many Requests."),
+        )
+        .arg(
+            Arg::new("limit-rate")
+                .long("limit-rate")
+                .value_name("RATE")
+                .help("Maximum speed of all downloads together, e.g. 5MB/s."),
+        )
+        .arg(
+            Arg::new("limit-rate-per-connection")
+                .long("limit-rate-per-connection")
+                .value_name("RATE")
+                .help("Maximum speed of every single download, e.g. 1MB/s."),
+        )
+        .arg(
+            Arg::new("trusted-proxy")
+                .long("trusted-proxy")
+                .value_name("RANGE")
+                .action(ArgAction::Append)
+                .help("Take the client address from Forwarded or X-Forwarded-For for connections from this proxy address or range, e.g. 10.0.0.0/8, can be repeated."),
+        )
+        .arg(
+            Arg::new("oidc-issuer")
+                .long("oidc-issuer")
+                .value_name("URL")
+                .requires_all(["oidc-client-id", "oidc-client-secret"])
+                .help("Require an OpenID Connect login from this issuer."),
+        )
+        .arg(
+            Arg::new("oidc-client-id")
+                .long("oidc-client-id")
+                .value_name("ID")
+                .requires("oidc-issuer")
+                .help("Client id registered at the OpenID provider."),
+        )
+        .arg(
+            Arg::new("oidc-client-secret")
+                .long("oidc-client-secret")
+                .value_name("SECRET")
+                .requires("oidc-issuer")
+                .help("Client secret registered at the OpenID provider."),
+        )
+        .arg(
+            Arg::new("oidc-redirect-url")
+                .long("oidc-redirect-url")
+                .value_name("URL")
+                .requires("oidc-issuer")
+                .help("Callback url registered at the provider, defaults to http(s)://<address>:<port>/auth/callback."),
+        )
+        .arg(
+            Arg::new("ldap-url")
+                .long("ldap-url")
+                .value_name("URL")
+                .requires("ldap-user-dn")
+                .help("Require a login checked against this LDAP server, e.g. ldap://dc.example.org."),
+        )
+        .arg(
+            Arg::new("ldap-user-dn")
+                .long("ldap-user-dn")
+                .value_name("DN")
+                .requires("ldap-url")
+                .help("Bind dn of the users, {user} is replaced by the login name."),
+        )
+        .arg(
+            Arg::new("ldap-group-role")
+                .long("ldap-group-role")
+                .value_name("GROUP_DN=ROLE")
+                .action(ArgAction::Append)
+                .requires("ldap-url")
+                .help("Grant ROLE to members of the group, only mapped users may log in. Can be repeated."),
+        )
+        .arg(
+            Arg::new("write-role")
+                .long("write-role")
+                .value_name("ROLE")
+                .requires("ldap-group-role")
+                .help("Only users with this role may upload, delete and write through WebDAV, the others can only read."),
+        )
+        .arg(
+            Arg::new("htpasswd")
+                .long("htpasswd")
+                .visible_alias("auth-file")
+                .value_name("FILE")
+                .conflicts_with("ldap-url")
+                .help("Require a login checked against an Apache htpasswd file (bcrypt or apr1), reloaded on change."),
+        )
+        .arg(
+            Arg::new("auth")
+                .long("auth")
+                .value_name("USER:PASS")
+                .action(ArgAction::Append)
+                .conflicts_with_all(["ldap-url", "htpasswd"])
+                .help("Require HTTP Basic authentication with this account, can be repeated."),
+        )
+        .arg(
+            Arg::new("shutdown-token")
+                .long("shutdown-token")
+                .value_name("TOKEN")
+                .help("Stop the server on POST /api/shutdown with the header Authorization: Bearer TOKEN."),
+        )
+        .arg(
+            Arg::new("admin")
+                .long("admin")
+                .value_name("USER:PASS")
+                .help("Serve an admin panel at /admin behind this account, apart from the logins of the listing."),
+        )
+        .arg(
+            Arg::new("min-file-size")
+                .long("min-file-size")
+                .value_name("SIZE")
+                .help("Only share files of at least SIZE, e.g. 100K."),
+        )
+        .arg(
+            Arg::new("max-file-size")
+                .long("max-file-size")
+                .value_name("SIZE")
+                .help("Only share files up to SIZE, e.g. 20M."),
+        )
+        .arg(
+            Arg::new("newer-than")
+                .long("newer-than")
+                .value_name("AGE")
+                .help("Only share files modified within AGE, e.g. 12h, 7d or 2w."),
+        )
+        .arg(
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Only browse N folder levels below the served folder, 0 shares the top folder only."),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("Do not share the entries matching GLOB, e.g. \"*.log\" or \"node_modules/**\". Can be repeated."),
+        )
+        .arg(
+            Arg::new("follow-symlinks")
+                .long("follow-symlinks")
+                .value_name("POLICY")
+                .value_parser(["never", "inside-root", "always"])
+                .default_value("inside-root")
+                .help("Which symbolic links to follow: none, those leading to a place inside the served folder, or all."),
+        )
+        .arg(
+            Arg::new("gitignore")
+                .long("gitignore")
+                .action(ArgAction::SetTrue)
+                .help("Do not share the files ignored by the .gitignore files of the served folder."),
+        )
+        .arg(
+            Arg::new("show-hidden")
+                .long("show-hidden")
+                .action(ArgAction::SetTrue)
+                .help("Share dotfiles like .env and .git and files hidden on Windows, left out by default."),
+        )
+        .arg(
+            Arg::new("visibility")
+                .long("visibility")
+                .value_name("PATH=public|login|hidden")
+                .action(ArgAction::Append)
+                .help("Make a folder public or login only, or hide it from its parent listing. Can be repeated."),
+        )
+        .arg(
+            Arg::new("url-secret")
+                .long("url-secret")
+                .value_name("SECRET")
+                .help("Secret used to sign download links, signed links skip the login until they expire."),
+        )
+        .arg(
+            Arg::new("sign")
+                .long("sign")
+                .value_name("FILE")
+                .requires("url-secret")
+                .help("Print a signed download link for FILE, relative to the folder, and exit."),
+        )
+        .arg(
+            Arg::new("sign-ttl")
+                .long("sign-ttl")
+                .value_name("SECONDS")
+                .requires("sign")
+                .help("Validity of the signed link, defaults to 3600 seconds."),
+        )
+        .arg(
+            Arg::new("allow-upload")
+                .long("allow-upload")
+                .action(ArgAction::SetTrue)
+                .help("Let clients upload files into the served folders."),
+        )
+        .arg(
+            Arg::new("on-upload")
+                .long("on-upload")
+                .value_name("COMMAND")
+                .conflicts_with("sandbox")
+                .help("Run this command for every uploaded file, {path} is replaced by the file and {name} by its name."),
+        )
+        .arg(
+            Arg::new("on-upload-jobs")
+                .long("on-upload-jobs")
+                .value_name("N")
+                .requires("on-upload")
+                .help("Upload commands running at once, defaults to 2."),
+        )
+        .arg(
+            Arg::new("allow-write")
+                .long("allow-write")
+                .action(ArgAction::SetTrue)
+                .help("Let WebDAV clients create, change, move and delete files and folders."),
+        )
+        .arg(
+            Arg::new("allow-delete")
+                .long("allow-delete")
+                .action(ArgAction::SetTrue)
+                .help("Show a button to delete files and empty folders in the listings."),
+        )
+        .arg(
+            Arg::new("templates")
+                .long("templates")
+                .visible_alias("template-dir")
+                .value_name("DIR")
+                .help("Folder with pages that replace the built-in ones, defaults to ./templates. Edits are picked up without a restart."),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("THEME")
+                .value_parser(["light", "dark", "auto"])
+                .default_value("auto")
+                .help("Colors of the pages, auto follows the system. Visitors can switch with the toggle on the listing."),
+        )
+        .arg(
+            Arg::new("title")
+                .long("title")
+                .value_name("TEXT")
+                .help("Name shown in the page titles instead of LAN File Server."),
+        )
+        .arg(
+            Arg::new("logo")
+                .long("logo")
+                .value_name("FILE")
+                .help("Image shown at the top of every page."),
+        )
+        .arg(
+            Arg::new("footer")
+                .long("footer")
+                .value_name("HTML")
+                .help("Footer of every page, inserted as HTML."),
+        )
+        .arg(
+            Arg::new("thumbnails")
+                .long("thumbnails")
+                .action(ArgAction::SetTrue)
+                .help("Show previews of the images in the listings, kept in the cache folder once made."),
+        )
+        .arg(
+            Arg::new("cache-control")
+                .long("cache-control")
+                .value_name("VALUE")
+                .help("Cache-Control header of downloaded files, e.g. \"max-age=3600\". Defaults to \"private, max-age=300\", listings are always revalidated."),
+        )
+        .arg(
+            Arg::new("no-compression")
+                .long("no-compression")
+                .action(ArgAction::SetTrue)
+                .help("Send pages, JSON and text files uncompressed instead of with gzip or brotli."),
+        )
+        .arg(
+            Arg::new("dir-sizes")
+                .long("dir-sizes")
+                .action(ArgAction::SetTrue)
+                .help("Show the size of the folders in the listings, computed in the background."),
+        )
+        .arg(
+            Arg::new("show-gps")
+                .long("show-gps")
+                .action(ArgAction::SetTrue)
+                .help("Include the position photos were taken at in their details, it is left out otherwise."),
+        )
+        .arg(
+            Arg::new("sandbox")
+                .long("sandbox")
+                .action(ArgAction::SetTrue)
+                .help("Restrict filesystem access to the served folder (Landlock/unveil)."),
+        )
+        .arg(
+            Arg::new("workers")
+                .long("workers")
+                .value_name("N")
+                .help("Number of runtime worker threads, defaults to the number of cores."),
+        )
+        .arg(
+            Arg::new("blocking-threads")
+                .long("blocking-threads")
+                .value_name("N")
+                .help("Maximum threads for blocking file system work, defaults to 512."),
+        );
+    #[cfg(all(unix, feature = "pam"))]
+    let cli = cli
+        .arg(
+            Arg::new("pam")
+                .long("pam")
+                .value_name("SERVICE")
+                .conflicts_with_all(["ldap-url", "htpasswd", "auth", "sandbox"])
+                .help("Require a login with a local system account, checked by the PAM service, e.g. login."),
+        )
+        .arg(
+            Arg::new("pam-home")
+                .long("pam-home")
+                .action(ArgAction::SetTrue)
+                .requires("pam")
+                .conflicts_with("sandbox")
+                .help("Serve the home directory of the logged in user instead of the folder."),
+        );
+    #[cfg(unix)]
+    let cli = cli.arg(
+        Arg::new("unix")
+            .long("unix")
+            .value_name("PATH")
+            .conflicts_with_all(["bind", "cert", "tls-self-signed", "sandbox"])
+            .help("Listen on a Unix domain socket at PATH instead of a TCP port, e.g. behind nginx or Caddy."),
+    );
+    #[cfg(feature = "transcode")]
+    let cli = cli.arg(
+        Arg::new("transcode")
+            .long("transcode")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("sandbox")
+            .help("Convert videos browsers cannot play to HLS with ffmpeg while they are watched."),
+    );
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    let cli = cli.arg(
+        Arg::new("io-uring")
+            .long("io-uring")
+            .action(ArgAction::SetTrue)
+            .help("Read files through io_uring instead of the blocking thread pool."),
+    );
+    // the options work before and after the command, file-serve -p 80 share notes.txt as well
+    // as file-serve share notes.txt -p 80
+    let cli = cli.mut_args(|arg| arg.global(true));
+    let (args, config_file) = match config::args(&cli, env::args_os().collect()) {
+        Ok(read) => read,
+        Err(err) => {
+            log::error!("Failed to read config: {}", err);
+            eprintln!("Failed to read config: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let matches = cli.get_matches_from(args);
+
+    let log_format = matches
+        .get_one::<String>("log-format")
+        .map(|f| {
+            f.parse::<LogFormat>()
+                .expect("log-format must be text or json")
+        })
+        .unwrap_or(LogFormat::Text);
+    // --log-file none writes no log file, and creates no logs folder either
+    let log_file = match matches.get_one::<String>("log-file").map(String::as_str) {
+        Some("none") => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from(LOG_PATH)),
+    };
+    let log_level = matches
+        .get_one::<String>("log-level")
+        .map_or(LevelFilter::Info, |l| {
+            l.parse()
+                .expect("log-level must be off, error, warn, info, debug or trace")
+        });
+    let rotation = Rotation {
+        max_size: match matches.get_one::<String>("log-max-size") {
+            Some(size) => {
+                Some(filter::parse_size(size).expect("log-max-size must be a size like 10M or 0"))
+                    .filter(|max| *max > 0)
+            }
+            None => Some(LOG_MAX_SIZE),
+        },
+        every: matches.get_one::<String>("log-rotate").map(|p| {
+            p.parse()
+                .expect("log-rotate must be hourly, daily or weekly")
+        }),
+        keep: matches
+            .get_one::<String>("log-keep")
+            .map_or(LOG_KEEP, |k| k.parse().expect("log-keep must be a number")),
+    };
+    if let Err(err) = logging::start(
+        log_file.as_deref(),
+        &rotation,
+        if matches.get_flag("tui") {
+            Console::Dashboard
+        } else if matches.get_flag("quiet") {
+            Console::Off
+        } else {
+            Console::Stderr
+        },
+        log_level,
+        log_format,
+    ) {
+        eprintln!(
+            "Failed to open log file {}: {}",
+            log_file.as_deref().unwrap_or(Path::new(LOG_PATH)).display(),
+            err
+        );
+    }
+    if let Some(path) = &config_file {
+        log::info!("[CONFIG] read {}", path.display());
+    }
+
+    if let Some(dir) = matches.get_one::<String>("templates") {
+        templates::set_dir(PathBuf::from(dir));
+    }
+    if let Some(theme) = matches
+        .get_one::<String>("theme")
+        .and_then(|t| Theme::parse(t))
+    {
+        templates::set_theme(theme);
+    }
+    if let Some(policy) = matches
+        .get_one::<String>("follow-symlinks")
+        .and_then(|p| p.parse().ok())
+    {
+        symlinks::set_policy(policy);
+    }
+    meta::set_show_gps(matches.get_flag("show-gps"));
+    if let Some(base) = matches.get_one::<String>("base-path")
+        && let Err(err) = base_path::set(base)
+    {
+        log::error!("{}", err);
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    let rate = |id: &str| {
+        matches.get_one::<String>(id).map(|r| {
+            throttle::parse_rate(r)
+                .unwrap_or_else(|err| panic!("{} must be a rate like 5MB/s: {}", id, err))
+        })
+    };
+    throttle::set_limits(rate("limit-rate"), rate("limit-rate-per-connection"));
+    let cache_policy = match CachePolicy::new(
+        matches
+            .get_one::<String>("cache-control")
+            .map(String::as_str),
+    ) {
+        Ok(policy) => Arc::new(policy),
+        Err(err) => {
+            log::error!("{}", err);
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if matches.get_flag("dir-sizes") {
+        dir_sizes::enable();
+    }
+    let logo = match matches.get_one::<String>("logo") {
+        Some(file) => match templates::logo_url(Path::new(file)) {
+            Ok(url) => Some(url),
+            Err(err) => {
+                log::error!("Invalid logo: {}", err);
+                eprintln!("Invalid logo: {}", err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    templates::set_branding(Branding {
+        site_title: matches.get_one::<String>("title").cloned(),
+        logo,
+        footer: matches.get_one::<String>("footer").cloned(),
+    });
+
+    let mut port = 8080; // default port
+    if let Some(p) = matches.get_one::<String>("port") {
+        port = p.parse::<u16>().expect("port must be a number");
+    }
+
+    let mounts = Mounts::parse(matches.get_many::<String>("folder").into_iter().flatten())
+        .unwrap_or_else(|err| {
+            log::error!("Invalid --folder: {}", err);
+            eprintln!("Invalid --folder: {}", err);
+            std::process::exit(1);
+        });
+    let mut root = env::current_dir().expect("Failed to get current dir");
+    if mounts.is_none()
+        && let Some(f) = matches.get_one::<String>("folder")
+    {
+        root.push(f.as_str());
+    }
+    // share and receive name their file or folder instead of --folder
+    let mut single_file = None;
+    match matches.subcommand() {
+        Some(("share", share)) => {
+            let mut file = env::current_dir().expect("Failed to get current dir");
+            file.push(share.get_one::<String>("file").unwrap());
+            let name = file.file_name().and_then(|n| n.to_str());
+            let (Some(name), true) = (name, file.is_file()) else {
+                eprintln!("Cannot share {}: not a file", file.display());
+                std::process::exit(1);
+            };
+            single_file = Some(name.to_string());
+            root = file.parent().unwrap().to_path_buf();
+        }
+        Some(("receive", receive)) => {
+            root = env::current_dir().expect("Failed to get current dir");
+            root.push(receive.get_one::<String>("dir").unwrap());
+        }
+        _ => {}
+    }
+    let receiving = matches.subcommand_matches("receive").is_some();
+    // 0 keeps serving, file-serve share stops after the first download unless told otherwise
+    let max_downloads = match matches.get_one::<String>("max-downloads") {
+        Some(max) => {
+            Some(max.parse::<u32>().expect("max-downloads must be a number")).filter(|max| *max > 0)
+        }
+        None if single_file.is_some() => Some(1),
+        None => None,
+    };
+    let timeout = matches
+        .get_one::<String>("timeout")
+        .map(|t| filter::parse_age(t).expect("timeout must be an age like 10m"));
+    let drop_box = matches.subcommand_matches("receive").map(|receive| {
+        let quota = receive
+            .get_one::<String>("quota")
+            .map(|q| filter::parse_size(q).expect("quota must be a size like 10G"));
+        match DropBox::new(&root, quota) {
+            Ok(drop_box) => Arc::new(drop_box),
+            Err(err) => {
+                eprintln!("Cannot receive into {}: {}", root.display(), err);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    // share and receive serve a folder on disk
+    let packed = matches
+        .get_one::<String>("archive")
+        .filter(|_| !matches!(matches.subcommand_name(), Some("share" | "receive")))
+        .map(|file| match Packed::open(Path::new(file)) {
+            Ok(packed) => Arc::new(packed),
+            Err(err) => {
+                log::error!("Failed to read archive {}: {}", file, err);
+                eprintln!("Cannot serve {}: {}", file, err);
+                std::process::exit(1);
+            }
+        });
+
+    let mut host_roots = HashMap::new();
+    for mapping in matches.get_many::<String>("host").unwrap_or_default() {
+        let (name, folder) = mapping
+            .split_once('=')
+            .expect("host must be in the form name=path");
+        let mut host_root = env::current_dir().expect("Failed to get current dir");
+        host_root.push(folder);
+        host_roots.insert(name.to_lowercase(), host_root);
+    }
+
+    let stream_limiter = matches.get_one::<String>("max-streams-per-ip").map(|m| {
+        let max = m
+            .parse::<usize>()
+            .expect("max-streams-per-ip must be a number");
+        assert!(max > 0, "max-streams-per-ip must be greater than 0");
+        Arc::new(StreamLimiter::new(max))
+    });
+
+    let rate_limiter = matches.get_one::<String>("rate-limit").map(|m| {
+        let rate = m.parse::<f64>().expect("rate-limit must be a number");
+        assert!(
+            rate.is_finite() && rate > 0.0,
+            "rate-limit must be greater than 0"
+        );
+        Arc::new(RateLimiter::new(rate))
+    });
+
+    let trusted_proxies = match matches.get_many::<String>("trusted-proxy") {
+        Some(ranges) => match TrustedProxies::parse(ranges) {
+            Ok(proxies) => Some(Arc::new(proxies)),
+            Err(err) => {
+                log::error!("{}", err);
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let htpasswd_path = matches.get_one::<String>("htpasswd").map(PathBuf::from);
+    let htpasswd = htpasswd_path
+        .as_ref()
+        .map(|path| match Htpasswd::open(path) {
+            Ok(htpasswd) => htpasswd,
+            Err(err) => {
+                log::error!("Failed to read {}: {}", path.display(), err);
+                eprintln!("Failed to read {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        });
+
+    let users = matches.get_many::<String>("auth").map(|specs| {
+        Users::parse(specs).unwrap_or_else(|err| {
+            log::error!("Invalid --auth: {}", err);
+            eprintln!("Invalid --auth: {}", err);
+            std::process::exit(1);
+        })
+    });
+
+    if let Some(token) = matches.get_one::<String>("shutdown-token") {
+        shutdown::set_token(token);
+    }
+    if let Some(spec) = matches.get_one::<String>("admin") {
+        match Users::parse(std::iter::once(spec)) {
+            Ok(users) => admin::enable(users),
+            Err(err) => {
+                log::error!("Invalid --admin: {}", err);
+                eprintln!("Invalid --admin: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let visibility = matches
+        .get_many::<String>("visibility")
+        .unwrap_or_default()
+        .map(|rule| {
+            let (path, visibility) = rule
+                .rsplit_once('=')
+                .expect("visibility must be in the form PATH=public|login|hidden");
+            let visibility = visibility
+                .parse::<Visibility>()
+                .unwrap_or_else(|e| panic!("{}, visibility must be public, login or hidden", e));
+            (path.to_string(), visibility)
+        })
+        .collect();
+
+    let filter = FileFilter {
+        min_size: matches
+            .get_one::<String>("min-file-size")
+            .map(|s| filter::parse_size(s).expect("min-file-size must be a size like 100K")),
+        max_size: matches
+            .get_one::<String>("max-file-size")
+            .map(|s| filter::parse_size(s).expect("max-file-size must be a size like 20M")),
+        newer_than: matches
+            .get_one::<String>("newer-than")
+            .map(|s| filter::parse_age(s).expect("newer-than must be an age like 7d")),
+    };
+
+    let max_depth = matches
+        .get_one::<String>("max-depth")
+        .map(|d| d.parse::<usize>().expect("max-depth must be a number"));
+
+    let exclude = ExcludeRules::new(
+        matches.get_many::<String>("exclude").into_iter().flatten(),
+        matches.get_flag("show-hidden"),
+    )
+    .unwrap_or_else(|err| {
+        log::error!("Invalid --exclude: {}", err);
+        eprintln!("Invalid --exclude: {}", err);
+        std::process::exit(1);
+    });
+    let exclude = if matches.get_flag("gitignore") {
+        exclude.with_gitignore(&root)
+    } else {
+        exclude
+    };
+
+    let state = AppState {
+        root,
+        host_roots: Arc::new(host_roots),
+        stream_limiter,
+        visibility: Arc::new(VisibilityRules::new(visibility)),
+        filter,
+        max_depth,
+        exclude: Arc::new(exclude),
+        settings: Arc::new(Settings::new(
+            matches.get_flag("allow-upload") || receiving,
+            matches.get_flag("allow-write"),
+            matches.get_flag("allow-delete"),
+        )),
+        write_role: matches
+            .get_one::<String>("write-role")
+            .map(|role| role.as_str().into()),
+        shares: matches
+            .get_one::<String>("url-secret")
+            .map(|secret| Arc::new(ShareLinks::new(secret))),
+        drop_box,
+        shutdown: Arc::new(Shutdown::new(max_downloads)),
+        // share and receive serve a single folder
+        mounts: mounts
+            .filter(|_| !matches!(matches.subcommand_name(), Some("share" | "receive")))
+            .map(Arc::new),
+        packed,
+    };
+
+    // socket activation, systemd listens for the server and passes the socket on
+    #[cfg(unix)]
+    let inherited = match systemd::listener() {
+        Some(Ok(inherited)) => Some(inherited),
+        Some(Err(err)) => {
+            log::error!("Failed to take the socket of systemd: {}", err);
+            eprintln!("Failed to take the socket of systemd: {}", err);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    // the link shows the port systemd listens on
+    #[cfg(unix)]
+    if let Some(inherited_port) = inherited.as_ref().and_then(systemd::Inherited::port) {
+        port = inherited_port;
+    }
+
+    let bind = matches.get_one::<String>("bind").map(|b| {
+        let b = b.trim_start_matches('[').trim_end_matches(']');
+        let addr = bind_address(b, port)
+            .expect("bind must be an IP address like 127.0.0.1 or ::1, or fe80::1%eth0");
+        (addr, b.to_string())
+    });
+    let addr = bind
+        .as_ref()
+        .map_or(SocketAddr::from(([0, 0, 0, 0], port)), |(addr, _)| *addr);
+
+    let add: String;
+    if let Some(f) = matches.get_one::<String>("interface") {
+        add = f.clone();
+    } else if let Some((_, b)) = bind.filter(|(addr, _)| !addr.ip().is_unspecified()) {
+        // the only address the server can be reached at
+        add = b;
+    } else {
+        add = get_address(addr.is_ipv6())
+    }
+    // the address in links and QR codes
+    let host = utils::url_host(&add);
+
+    let tls_source = if matches.get_flag("tls-self-signed") {
+        // certificates name addresses without the zone
+        Some(TlsSource::SelfSigned(vec![
+            add.split('%').next().unwrap_or(&add).to_string(),
+            "localhost".to_string(),
+        ]))
+    } else {
+        matches
+            .get_one::<String>("cert")
+            .zip(matches.get_one::<String>("key"))
+            .map(|(cert, key)| TlsSource::Pem(PathBuf::from(cert), PathBuf::from(key)))
+    };
+    let scheme = if tls_source.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let redirect_http = matches.get_one::<String>("redirect-http").map(|port| {
+        port.parse::<u16>()
+            .expect("redirect-http must be a port number")
+    });
+    let hsts = matches.get_flag("hsts").then(|| {
+        let max_age =
+            matches
+                .get_one::<String>("hsts-max-age")
+                .map_or(https::HSTS_MAX_AGE, |age| {
+                    age.parse::<u64>()
+                        .expect("hsts-max-age must be a number of seconds")
+                });
+        HeaderValue::from_str(&format!("max-age={}", max_age))
+            .expect("hsts-max-age must be a number of seconds")
+    });
+    if (redirect_http.is_some() || hsts.is_some()) && tls_source.is_none() {
+        log::error!("--redirect-http and --hsts need HTTPS, --cert and --key or --tls-self-signed");
+        eprintln!("--redirect-http and --hsts need HTTPS, --cert and --key or --tls-self-signed");
+        std::process::exit(1);
+    }
+
+    if matches.subcommand_matches("doctor").is_some() {
+        let passed = doctor::run(&state, addr, tls_source.as_ref(), log_file.as_deref());
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+    if matches.get_flag("check") {
+        doctor::print_config(&matches, &state, scheme, &host, port);
+        return;
+    }
+
+    if let Some(export) = matches.subcommand_matches("export") {
+        let out_dir = PathBuf::from(export.get_one::<String>("out-dir").unwrap());
+        match export::export(&state, &out_dir, export.get_flag("link")) {
+            Ok(files) => println!(
+                "Exported '{}' to '{}', {} files",
+                state.root.display(),
+                out_dir.display(),
+                files
+            ),
+            Err(err) => {
+                log::error!("Failed to export to {}: {}", out_dir.display(), err);
+                eprintln!("Failed to export to {}: {}", out_dir.display(), err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if matches.get_flag("live") {
+        let roots = match &state.mounts {
+            Some(mounts) => mounts
+                .iter()
+                .map(|(name, path)| (name.to_string(), path.to_path_buf()))
+                .collect(),
+            None => vec![(String::new(), state.root.clone())],
+        };
+        if let Err(err) = live::enable(roots) {
+            log::error!("Failed to watch the folder: {}", err);
+            eprintln!("Failed to watch the folder: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    let stats_file = matches.get_one::<String>("stats-file").map(PathBuf::from);
+    if matches.get_flag("stats")
+        && let Err(err) = stats::enable(stats_file.clone())
+    {
+        log::error!("Failed to load the download statistics: {}", err);
+        eprintln!("Failed to load the download statistics: {}", err);
+        std::process::exit(1);
+    }
+
+    if let Some(path) = matches.get_one::<String>("auth-log")
+        && let Err(err) = auth_log::enable(Path::new(path))
+    {
+        log::error!("Failed to open the auth log {}: {}", path, err);
+        eprintln!("Failed to open the auth log {}: {}", path, err);
+        std::process::exit(1);
+    }
+    let webhooks = matches.get_many::<String>("webhook").map(|urls| {
+        let urls = urls
+            .map(|url| {
+                reqwest::Url::parse(url).unwrap_or_else(|err| {
+                    log::error!("Invalid --webhook {}: {}", url, err);
+                    eprintln!("Invalid --webhook {}: {}", url, err);
+                    std::process::exit(1);
+                })
+            })
+            .collect();
+        let kinds = match matches.get_one::<String>("webhook-events") {
+            Some(list) => list
+                .split(',')
+                .map(|name| {
+                    webhook::Kind::parse(name.trim()).unwrap_or_else(|| {
+                        log::error!("Invalid --webhook-events: unknown event {}", name);
+                        eprintln!("Invalid --webhook-events: unknown event {}", name);
+                        std::process::exit(1);
+                    })
+                })
+                .collect(),
+            None => webhook::Kind::ALL.to_vec(),
+        };
+        webhook::enable(urls, kinds)
+    });
+
+    if let Some(command) = matches.get_one::<String>("on-upload") {
+        let jobs = matches
+            .get_one::<String>("on-upload-jobs")
+            .map_or(upload_hook::JOBS, |jobs| {
+                jobs.parse::<usize>()
+                    .expect("on-upload-jobs must be a number")
+            });
+        if let Err(err) = upload_hook::enable(command, jobs) {
+            log::error!("Invalid --on-upload: {}", err);
+            eprintln!("Invalid --on-upload: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // the cache folder has to exist before the sandbox grants it
+    let thumbnail_dir = if matches.get_flag("thumbnails") {
+        match thumbnails::enable() {
+            Ok(dir) => Some(dir),
+            Err(err) => {
+                log::error!("Failed to create the thumbnail folder: {}", err);
+                eprintln!("Failed to create the thumbnail folder: {}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    #[cfg(feature = "transcode")]
+    if matches.get_flag("transcode") {
+        if let Err(err) = transcode::enable() {
+            log::error!("Failed to create the folder of the video segments: {}", err);
+            eprintln!("Failed to create the folder of the video segments: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // the certificate is read before the sandbox, which does not grant its folder
+    let tls = tls_source.map(|source| match source.load() {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            log::error!("Failed to load TLS certificate: {}", err);
+            eprintln!("Failed to load TLS certificate: {}", err);
+            std::process::exit(1);
+        }
+    });
+
+    if matches.get_flag("sandbox") {
+        let mut read_paths: Vec<&Path> = vec![&state.root, templates::dir()];
+        read_paths.extend(state.host_roots.values().map(PathBuf::as_path));
+        read_paths.extend(
+            state
+                .mounts
+                .iter()
+                .flat_map(|m| m.iter().map(|(_, path)| path)),
+        );
+        read_paths.extend(state.packed.iter().map(|packed| packed.file()));
+        // the folder rather than the file, editors and htpasswd replace it with a new one
+        if let Some(folder) = htpasswd_path.as_deref().and_then(Path::parent) {
+            read_paths.push(if folder.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                folder
+            });
+        }
+        // uploads and WebDAV write into the served folders, /admin can turn uploads on later
+        let mut write_paths: Vec<&Path> = Vec::new();
+        write_paths.extend(thumbnail_dir);
+        // the statistics are replaced through a temporary file next to them
+        if let Some(folder) = stats_file.as_deref().and_then(Path::parent) {
+            write_paths.push(if folder.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                folder
+            });
+        }
+        // rotating renames the log file and creates a new one next to it
+        if (rotation.max_size.is_some() || rotation.every.is_some())
+            && let Some(folder) = log_file.as_deref().and_then(Path::parent)
+        {
+            write_paths.push(if folder.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                folder
+            });
+        }
+        if state.settings.upload_allowed()
+            || state.settings.write_allowed()
+            || state.settings.delete_allowed()
+            || admin::enabled()
+        {
+            write_paths.push(&state.root);
+            write_paths.extend(state.host_roots.values().map(PathBuf::as_path));
+            write_paths.extend(
+                state
+                    .mounts
+                    .iter()
+                    .flat_map(|m| m.iter().map(|(_, path)| path)),
+            );
+        }
+        if let Err(err) = sandbox::restrict(&read_paths, &write_paths) {
+            log::error!("Failed to enable filesystem sandbox: {}", err);
+            eprintln!("Failed to enable filesystem sandbox: {}", err);
+            std::process::exit(1);
+        }
+        log::info!("filesystem sandbox enabled for {}", state.root.display());
+    }
+
+    // the port is taken before anything is printed, so the link has the port the server got
+    #[cfg(unix)]
+    let listen = match (inherited, matches.get_one::<String>("unix")) {
+        (Some(inherited), _) => Listen::Systemd(inherited),
+        (None, Some(path)) => Listen::Unix(PathBuf::from(path)),
+        (None, None) => Listen::Tcp(bind_port(addr, matches.get_flag("find-port"))),
+    };
+    #[cfg(not(unix))]
+    let listen = Listen::Tcp(bind_port(addr, matches.get_flag("find-port")));
+    if let Listen::Tcp(listener) = &listen
+        && let Ok(local) = listener.local_addr()
+    {
+        port = local.port();
+    }
+    // next to the HTTPS listener, on the same address
+    let redirect_listener = redirect_http.map(|redirect| {
+        let mut redirect_addr = addr;
+        redirect_addr.set_port(redirect);
+        bind_port(redirect_addr, false)
+    });
+
+    if listen.on_network() {
+        qr::set_origin(format!("{}://{}:{}", scheme, host, port));
+    }
+
+    let full_link: String = match &single_file {
+        Some(name) => format!(
+            "{}://{}:{}{}\n",
+            scheme,
+            host,
+            port,
+            base_path::link(&format!("/{}", utils::encode_path(name)))
+        ),
+        None => format!("{}://{}:{}{}\n", scheme, host, port, base_path::get()),
+    };
+
+    let url_signer = matches
+        .get_one::<String>("url-secret")
+        .map(|secret| UrlSigner::new(secret));
+    if let (Some(signer), Some(file)) = (&url_signer, matches.get_one::<String>("sign")) {
+        let mut ttl = 3600;
+        if let Some(t) = matches.get_one::<String>("sign-ttl") {
+            ttl = t.parse::<u64>().expect("sign-ttl must be a number");
+        }
+        println!(
+            "{}://{}:{}{}",
+            scheme,
+            host,
+            port,
+            base_path::link(&signer.sign(file, ttl))
+        );
+        return;
+    }
+    if let Some(share) = matches.subcommand_matches("link") {
+        let Some(shares) = &state.shares else {
+            eprintln!("Share links are signed with the server secret, pass --url-secret");
+            std::process::exit(1);
+        };
+        let ttl = share
+            .get_one::<String>("ttl")
+            .map_or(share::DEFAULT_TTL, |t| t.as_str());
+        let ttl = filter::parse_age(ttl).expect("ttl must be an age like 7d");
+        let max_downloads = if share.get_flag("once") {
+            Some(1)
+        } else {
+            share
+                .get_one::<String>("max-downloads")
+                .map(|m| m.parse::<u32>().expect("max-downloads must be a number"))
+                .filter(|max| *max > 0)
+        };
+        let path = share.get_one::<String>("path").unwrap();
+        println!(
+            "{}://{}:{}{}",
+            scheme,
+            host,
+            port,
+            // below the default root, the link is not tied to a --host name
+            shares.mint(share::Kind::Share, None, path, ttl, max_downloads)
+        );
+        return;
+    }
+
+    // kept until the server stops, dropping the daemon ends the announcement
+    let mdns_name = matches
+        .get_one::<String>("mdns-name")
+        .cloned()
+        .unwrap_or_else(mdns::default_name);
+    // a socket is not reachable over the network
+    let advertised = listen
+        .on_network()
+        .then(|| mdns::advertise(&mdns_name, scheme, port));
+    let announcement = match advertised {
+        None => None,
+        Some(Ok(daemon)) => {
+            log::info!(
+                "[MDNS] announced as {} ({})",
+                mdns_name,
+                mdns::host_name(&mdns_name)
+            );
+            Some(daemon)
+        }
+        Some(Err(err)) => {
+            log::warn!("[MDNS] Failed to announce the server: {}", err);
+            None
+        }
+    };
+
+    let mut serving = match &single_file {
+        Some(name) => format!("Sharing '{}'", state.root.join(name).display()),
+        None if receiving => format!("Receiving files into '{}'", state.root.display()),
+        None => match (&state.mounts, &state.packed) {
+            (_, Some(packed)) => format!("Serving the content of '{}'", packed.file().display()),
+            (Some(mounts), None) => format!(
+                "Serving {}",
+                mounts
+                    .iter()
+                    .map(|(name, path)| format!("'{}' as {}", path.display(), name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            (None, None) => format!("Serving '{}'", state.root.display()),
+        },
+    };
+    match max_downloads {
+        Some(1) => serving.push_str(" until it is downloaded once"),
+        Some(max) => serving.push_str(&format!(" for {} downloads", max)),
+        None => {}
+    }
+    if let Some(t) = matches.get_one::<String>("timeout") {
+        serving.push_str(&format!(" for at most {}", t));
+    }
+    // wrapper scripts read the line instead of the banner, the dashboard shows it itself
+    let tui = matches.get_flag("tui");
+    if matches.get_flag("print-json") {
+        let started = Started {
+            address: listen.on_network().then_some(add.as_str()),
+            port: listen.on_network().then_some(port),
+            socket: (!listen.on_network()).then(|| listen.describe()),
+            root: state.root.display().to_string(),
+            url: listen.on_network().then(|| full_link.trim_end()),
+            mdns: announcement.is_some().then(|| mdns::host_name(&mdns_name)),
+            fingerprint: tls
+                .as_ref()
+                .and_then(|(_, fingerprint)| fingerprint.as_deref()),
+        };
+        println!("{}", serde_json::to_string(&started).unwrap_or_default());
+    } else if !tui {
+        if listen.on_network() {
+            println!(
+                "{} on:\n    {}\nPress Ctrl+C to stop.\n{}",
+                serving,
+                full_link,
+                utils::get_qr_code(&full_link)
+            );
+        } else {
+            println!(
+                "{} on {}\nPress Ctrl+C to stop.",
+                serving,
+                listen.describe()
+            );
+        }
+        if announcement.is_some() {
+            println!(
+                "Also found on the local network as {}://{}:{}",
+                scheme,
+                mdns::host_name(&mdns_name),
+                port
+            );
+        }
+        for (name, host_root) in state.host_roots.iter() {
+            println!("Serving '{}' for host {}", host_root.display(), name);
+        }
+        if let Some((_, Some(fingerprint))) = &tls {
+            println!(
+                "Self-signed certificate, SHA-256 fingerprint:\n    {}",
+                fingerprint
+            );
+        }
+    }
+    if let Some((_, Some(fingerprint))) = &tls {
+        log::info!("self-signed certificate {}", fingerprint);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if matches.get_flag("io-uring") {
+        uring::start().expect("Failed to start io_uring backend");
+        log::info!("io_uring file backend enabled");
+    }
+
+    // the runtime is built only after the sandbox so its worker threads inherit it
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(w) = matches.get_one::<String>("workers") {
+        let workers = w.parse::<usize>().expect("workers must be a number");
+        assert!(workers > 0, "workers must be greater than 0");
+        builder.worker_threads(workers);
+    }
+    if let Some(b) = matches.get_one::<String>("blocking-threads") {
+        let blocking = b
+            .parse::<usize>()
+            .expect("blocking-threads must be a number");
+        assert!(blocking > 0, "blocking-threads must be greater than 0");
+        builder.max_blocking_threads(blocking);
+    }
+    let runtime = builder.build().expect("Failed to build tokio runtime");
+
+    let oidc = matches.get_one::<String>("oidc-issuer").map(|issuer| {
+        let redirect_url = match matches.get_one::<String>("oidc-redirect-url") {
+            Some(url) => url.clone(),
+            None => format!(
+                "{}://{}:{}{}",
+                scheme,
+                host,
+                port,
+                base_path::link("/auth/callback")
+            ),
+        };
+        let oidc = runtime.block_on(oidc::Oidc::discover(
+            issuer,
+            matches.get_one::<String>("oidc-client-id").unwrap(),
+            matches.get_one::<String>("oidc-client-secret").unwrap(),
+            &redirect_url,
+        ));
+        match oidc {
+            Ok(oidc) => oidc,
+            Err(err) => {
+                log::error!("Failed to discover OpenID provider {}: {}", issuer, err);
+                eprintln!("Failed to discover OpenID provider {}: {}", issuer, err);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    let ldap = matches.get_one::<String>("ldap-url").map(|url| {
+        let group_roles = matches
+            .get_many::<String>("ldap-group-role")
+            .unwrap_or_default()
+            .map(|mapping| {
+                // the group dn contains '=' itself, the role follows the last one
+                let (group, role) = mapping
+                    .rsplit_once('=')
+                    .expect("ldap-group-role must be in the form GROUP_DN=ROLE");
+                (group.to_string(), role.to_string())
+            })
+            .collect();
+        LdapAuth::new(
+            url,
+            matches.get_one::<String>("ldap-user-dn").unwrap(),
+            group_roles,
+        )
+    });
+
+    let auth = (oidc.is_some() || ldap.is_some() || htpasswd.is_some())
+        .then(|| Auth::new(oidc, ldap, htpasswd));
+    let auth = match users {
+        Some(users) => Some(
+            auth.unwrap_or_else(|| Auth::new(None, None, None))
+                .with_users(users),
+        ),
+        None => auth,
+    };
+    #[cfg(all(unix, feature = "pam"))]
+    let auth = match matches.get_one::<String>("pam") {
+        Some(service) => {
+            let system = system_auth::SystemAuth::new(service, matches.get_flag("pam-home"));
+            Some(
+                auth.unwrap_or_else(|| Auth::new(None, None, None))
+                    .with_system_auth(system),
+            )
+        }
+        None => auth,
+    };
+    let auth = match url_signer {
+        Some(signer) => auth.map(|auth| auth.with_url_signer(signer)),
+        None => auth,
+    };
+    let auth = auth.map(Arc::new);
+
+    // the app owns every path with --spa, its own /assets folder wins over the one of the pages
+    let single_page_app = single_file.is_none() && !receiving && matches.get_flag("spa");
+    let routes_served = single_file.is_none() && !receiving && !single_page_app;
+    let mut app = match &single_file {
+        Some(name) => single::routes(name),
+        None if receiving => receive::routes(),
+        None if single_page_app => spa::routes(),
+        None => routes(&state, matches.get_flag("site")),
+    }
+    .method_not_allowed_fallback(errors::method_not_allowed);
+    if let Some(auth) = auth {
+        app = app
+            .layer(middleware::from_fn_with_state(
+                auth.clone(),
+                auth::require_login,
+            ))
+            .merge(auth::routes(auth));
+    }
+    // share and upload links are for people without an account
+    if routes_served && state.shares.is_some() {
+        let upload = state.settings.upload_allowed() || admin::enabled();
+        app = app.merge(share::routes(upload));
+    }
+    // not behind the login of the listing, the panel has accounts of its own
+    if admin::enabled() {
+        app = app.merge(admin::routes());
+    }
+    // scripts send the token instead of logging in
+    if shutdown::remote_enabled() {
+        app = app.route("/api/shutdown", post(shutdown::remote));
+    }
+    if !single_page_app {
+        app = app.merge(assets::routes());
+    }
+    // runs before the login check, which lets marked requests through
+    if !state.visibility.is_empty() {
+        app = app.layer(middleware::from_fn_with_state(
+            state.clone(),
+            visibility::mark_public,
+        ));
+    }
+    if matches.get_flag("lan-only") {
+        app = app.layer(middleware::from_fn(access::lan_only));
+    }
+    if let Some(limiter) = rate_limiter {
+        app = app.layer(middleware::from_fn_with_state(limiter, access::rate_limit));
+    }
+    if log_format == LogFormat::Json {
+        app = app.layer(middleware::from_fn(logging::access));
+    }
+    // the dashboard and /admin show the downloads running, the statistics and webhooks count
+    // them
+    if tui || stats::enabled() || admin::enabled() || webhook::enabled() {
+        transfers::enable();
+    }
+    if transfers::enabled() {
+        app = app.layer(middleware::from_fn(transfers::track));
+    }
+    // before everything that looks at the address of the client
+    if let Some(proxies) = trusted_proxies {
+        app = app.layer(middleware::from_fn_with_state(proxies, access::forwarded));
+    }
+    app = app.layer(middleware::from_fn_with_state(
+        cache_policy,
+        cache_control::apply,
+    ));
+    app = app.layer(middleware::from_fn(errors::error_pages));
+    // outermost, so the error pages are compressed as well
+    if !matches.get_flag("no-compression") {
+        app = app.layer(compression::layer());
+    }
+    let app = app.with_state(state.clone()); // clone to not consume
+
+    // --base-path, the proxy passes the prefix on and the routes are found below it
+    let app = match base_path::get() {
+        "" => app,
+        base => Router::new().nest(base, app),
+    };
+
+    if let Some(timeout) = timeout {
+        let shutdown = state.shutdown.clone();
+        runtime.spawn(async move { shutdown.stop_after(timeout).await });
+    }
+    let shutdown = state.shutdown.clone();
+    runtime.spawn(async move { shutdown.stop_on_signal().await });
+    if stats::enabled() {
+        runtime.spawn(stats::save_every(stats::SAVE_EVERY));
+    }
+    if let Some(events) = webhooks {
+        runtime.spawn(webhook::deliver(events));
+    }
+    if let Some(listener) = redirect_listener {
+        runtime.spawn(https::redirect(listener, host.clone(), port));
+    }
+    let dashboard = tui.then(|| {
+        let link = if listen.on_network() {
+            full_link.trim_end().to_string()
+        } else {
+            listen.describe()
+        };
+        dashboard::start(serving, link, state.shutdown.clone())
+    });
+    let shutdown = state.shutdown.clone();
+    runtime.block_on(serve(
+        app,
+        listen,
+        tls.map(|(config, _)| config),
+        hsts,
+        async move {
+            shutdown.wait().await;
+            live::close();
+        },
+    ));
+    #[cfg(feature = "transcode")]
+    transcode::clean_up();
+    if let Some(dashboard) = dashboard {
+        dashboard.finish();
+    }
+    stats::save();
+    log::info!("[SHUTDOWN] server stopped");
+    log::logger().flush();
+}
+
+// the routes of file-serve serve, the folder with its listings, downloads and extras. with
+// --site the paths of the website are all the ones left over
+fn routes(state: &AppState, site: bool) -> Router<AppState> {
+    let mut app = if site {
+        Router::new()
+            .route("/", get(site::send))
+            .fallback(site::send)
+    } else {
+        Router::new()
+            .route("/", get(list_files))
+            .fallback(errors::not_found)
+    };
+    app = app
+        .route("/browse/{*path}", get(list_files))
+        .route("/search", get(search::search))
+        .route("/qr.svg", get(qr::folder))
+        .route("/events", get(live::events))
+        .route("/events/{*path}", get(live::events))
+        .route("/qr/file/{*path}", get(qr::file))
+        .route("/stats", get(stats::page))
+        .route("/api/stats", get(stats::api))
+        .route("/download/{*path}", get(download_file))
+        .route("/delete/{*path}", delete(delete::delete_entry))
+        .route("/view/{*path}", get(view_file))
+        .route("/thumb/{*path}", get(thumbnails::thumbnail))
+        .route("/api/meta/{*path}", get(meta::metadata))
+        .route("/api/hash/{*path}", get(hash::checksum))
+        .route("/archive", post(archive::download_selection))
+        .route(
+            "/archive/{*path}",
+            get(archive::download_archive).post(archive::download_selection),
+        );
+    #[cfg(feature = "transcode")]
+    {
+        app = app.route("/hls/{*path}", get(transcode::stream));
+    }
+    // PROPFIND and the other WebDAV methods are not ones axum routes by name
+    app = app
+        .route("/dav", any(dav::handle))
+        .route("/dav/", any(dav::handle))
+        .route("/dav/{*path}", any(dav::handle));
+    if state.shares.is_some() {
+        app = app.route("/share/{*path}", get(share::share_page));
+    }
+    // upload links are handed out while uploads are on, /admin can turn them on later
+    if state.shares.is_some() && (state.settings.upload_allowed() || admin::enabled()) {
+        app = app
+            .route("/upload-link", get(share::upload_link_page))
+            .route("/upload-link/{*path}", get(share::upload_link_page));
+    }
+    // /admin can turn uploads on and off while the server runs
+    if state.settings.upload_allowed() || admin::enabled() {
+        // uploads are streamed to disk, no need for the default body limit
+        app = app
+            .route(
+                "/upload",
+                post(upload::upload_files).layer(DefaultBodyLimit::disable()),
+            )
+            .route(
+                "/upload/{*path}",
+                post(upload::upload_files).layer(DefaultBodyLimit::disable()),
+            );
+    }
+    app
 }
 
-// Global template cache
-lazy_static::lazy_static! {
-    static ref MAIN_TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
-    static ref ERROR_TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
+// the line of --print-json, what the server listens on once it started
+#[derive(Serialize)]
+struct Started<'a> {
+    address: Option<&'a str>,
+    port: Option<u16>,
+    socket: Option<String>,
+    root: String,
+    url: Option<&'a str>,
+    mdns: Option<String>,
+    fingerprint: Option<&'a str>,
 }
 
-// loads templates from cache, if template is none read from template file
-fn load_template() -> Result<String, Box<dyn std::error::Error>> {
-    let mut template = MAIN_TEMPLATE.lock().unwrap();
-
-    // if not cached load it
-    if template.is_none() {
-        let template_path = "templates/index.html";
-        let content = std::fs::read_to_string(template_path)
-            .map_err(|e| format!("Failed to read template file: {}", e))?;
-        *template = Some(content);
-    }
-
-    template
-        .as_ref()
-        .cloned()
-        .ok_or_else(|| "Template not loaded".into())
+// where the server takes its connections from
+enum Listen {
+    Tcp(std::net::TcpListener),
+    // --unix, a socket file for a reverse proxy on the same machine
+    #[cfg(unix)]
+    Unix(PathBuf),
+    // the socket systemd listens on, socket activation
+    #[cfg(unix)]
+    Systemd(systemd::Inherited),
 }
 
-// load the error page to be displayed
-fn load_error_template() -> Result<String, Box<dyn std::error::Error>> {
-    let mut template = ERROR_TEMPLATE.lock().unwrap();
-
-    if template.is_none() {
-        let template_path = "templates/error.html";
-        let content = std::fs::read_to_string(template_path)
-            .map_err(|e| format!("Failed to read error template file: {}", e))?;
-        *template = Some(content);
+impl Listen {
+    // whether clients of the network connect, the link, its QR code and mDNS are of no use
+    // for a socket file
+    fn on_network(&self) -> bool {
+        match self {
+            Listen::Tcp(_) => true,
+            #[cfg(unix)]
+            Listen::Systemd(systemd::Inherited::Tcp(_)) => true,
+            #[cfg(unix)]
+            Listen::Unix(_) | Listen::Systemd(systemd::Inherited::Unix(_)) => false,
+        }
     }
 
-    template
-        .as_ref()
-        .cloned()
-        .ok_or_else(|| "Error template not loaded".into())
+    fn describe(&self) -> String {
+        match self {
+            Listen::Tcp(listener) => listener
+                .local_addr()
+                .map_or("the TCP port".to_string(), |local| local.to_string()),
+            #[cfg(unix)]
+            Listen::Unix(path) => format!("the socket {}", path.display()),
+            #[cfg(unix)]
+            Listen::Systemd(systemd::Inherited::Tcp(listener)) => listener
+                .local_addr()
+                .map_or("the socket of systemd".to_string(), |local| {
+                    format!("{} of systemd", local)
+                }),
+            #[cfg(unix)]
+            Listen::Systemd(systemd::Inherited::Unix(_)) => "the socket of systemd".to_string(),
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() {
-    utils::start_logging("logs/file_serve.log");
-
-    let matches = Command::new("file-serve")
-        .version("0.6")
-        .about("Serve files through your LAN")
-        .arg(
-            Arg::new("port")
-                .short('p')
-                .long("port")
-                .value_name("P")
-                .help("Server port, defaults to 8080."),
-        )
-        .arg(
-            Arg::new("folder")
-                .short('f')
-                .long("folder")
-                .value_name("f")
-                .help("Folder to be served, default is current folder."),
-        )
-        .arg(
-            Arg::new("interface")
-                .short('i')
-                .long("interface")
-                .value_name("i")
-                .help("Interface to bind, default is first occurring interface."),
-        )
-        .get_matches();
-
-    let mut port = 8080; // default port
-    if let Some(p) = matches.get_one::<String>("port") {
-        port = p.parse::<u16>().expect("port must be a number");
+async fn serve(
+    app: Router,
+    listen: Listen,
+    tls: Option<Arc<rustls::ServerConfig>>,
+    hsts: Option<HeaderValue>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let name = listen.describe();
+    let listener = match listen {
+        Listen::Tcp(listener) => Ok(listener),
+        #[cfg(unix)]
+        Listen::Unix(path) => return serve_unix(app, &path, shutdown).await,
+        #[cfg(unix)]
+        Listen::Systemd(systemd::Inherited::Tcp(listener)) => Ok(listener),
+        #[cfg(unix)]
+        Listen::Systemd(systemd::Inherited::Unix(listener)) => {
+            if tls.is_some() {
+                log::warn!("[SYSTEMD] the Unix socket is served without TLS");
+            }
+            let listener = tokio::net::UnixListener::from_std(listener);
+            return serve_socket(app, listener, &name, shutdown).await;
+        }
+    };
+    if let Some(config) = tls {
+        // only the responses sent over TLS say to stay on it
+        let app = match hsts {
+            Some(value) => app.layer(middleware::from_fn_with_state(value, https::hsts)),
+            None => app,
+        };
+        let config = axum_server::tls_rustls::RustlsConfig::from_config(config);
+        let handle = axum_server::Handle::new();
+        let stopping = handle.clone();
+        tokio::spawn(async move {
+            shutdown.await;
+            stopping.graceful_shutdown(Some(shutdown::DRAIN));
+        });
+        let served =
+            match listener.and_then(|listener| axum_server::from_tcp_rustls(listener, config)) {
+                Ok(server) => {
+                    server
+                        .handle(handle)
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+        if let Err(err) = served {
+            log::error!("Failed to run TLS listener {}\n{}.", name, err);
+            eprintln!("Failed to listen on {}: {}", name, err);
+            std::process::exit(1);
+        }
+        return;
     }
 
-    let mut root = env::current_dir().expect("Failed to get current dir");
-    if let Some(f) = matches.get_one::<String>("folder") {
-        root.push(f.as_str());
+    match listener.and_then(tokio::net::TcpListener::from_std) {
+        Ok(listener) => {
+            let (shutdown, drained) = shutdown::with_drain(shutdown);
+            let server = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown);
+            tokio::select! {
+                served = server => served.unwrap(),
+                _ = drained => {}
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to run TCP listener {}\n{}.", name, err);
+            eprintln!("Failed to listen on {}: {}", name, err);
+            std::process::exit(1);
+        }
     }
+}
 
-    let state = AppState { root };
-
-    // Build router
-    let app = Router::new()
-        .route("/", get(list_files))
-        .route("/browse/{*path}", get(list_files))
-        .route("/download/{*path}", get(download_file))
-        .with_state(state.clone()); // clone to not consume
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+#[cfg(unix)]
+async fn serve_unix(app: Router, path: &Path, shutdown: impl Future<Output = ()> + Send + 'static) {
+    use std::os::unix::fs::FileTypeExt;
 
-    let add: String;
-    if let Some(f) = matches.get_one::<String>("interface") {
-        add = format!("{}", f);
-    } else {
-        add = get_address()
+    // the socket of a previous run is in the way, other files are not touched
+    if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
     }
+    let name = format!("the socket {}", path.display());
+    serve_socket(app, tokio::net::UnixListener::bind(path), &name, shutdown).await;
+    let _ = std::fs::remove_file(path);
+}
 
-    let full_link: String = format!("http://{}:{}\n", add, port);
-
-    println!(
-        "Serving '{}' on:\n    {}\nPress Ctrl+C to stop.\n{}",
-        state.root.display(),
-        full_link,
-        utils::get_qr_code(&full_link)
-    );
-
-    match tokio::net::TcpListener::bind(addr).await {
+#[cfg(unix)]
+async fn serve_socket(
+    app: Router,
+    listener: std::io::Result<tokio::net::UnixListener>,
+    name: &str,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    match listener {
         Ok(listener) => {
-            axum::serve(
-                listener,
-                app.into_make_service_with_connect_info::<SocketAddr>(),
-            )
-            .await
-            .unwrap();
+            // the handlers take the address of the client, every peer of the socket is on this
+            // machine
+            let app = app.layer(Extension(ConnectInfo(SocketAddr::from((
+                [127, 0, 0, 1],
+                0,
+            )))));
+            let (shutdown, drained) = shutdown::with_drain(shutdown);
+            let server = axum::serve(listener, app).with_graceful_shutdown(shutdown);
+            tokio::select! {
+                served = server => served.unwrap(),
+                _ = drained => {}
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to run Unix listener {}\n{}.", name, err);
+            eprintln!("Failed to listen on {}: {}", name, err);
+            std::process::exit(1);
         }
-        Err(err) => log::error!("Failed to run TCP listener {}\n{}.", addr, err),
     }
 }
 
-fn get_address() -> String {
+// the address of the machine on the network, IPv4 first. listening on IPv6 an IPv6 address
+// is taken when there is no other, a link-local one with the interface as zone
+fn get_address(ipv6: bool) -> String {
     if let Ok(interfaces) = get_if_addrs::get_if_addrs() {
+        let mut v6_address = None;
         for interface in interfaces {
             if interface.is_loopback() {
                 continue;
             }
-            if let std::net::IpAddr::V4(v4) = interface.ip() {
-                return format!("{}", v4); // use the first found
+            match interface.ip() {
+                IpAddr::V4(v4) => return format!("{}", v4), // use the first found
+                IpAddr::V6(v6) if ipv6 && v6.is_unicast_link_local() => {
+                    v6_address.get_or_insert(format!("{}%{}", v6, interface.name));
+                }
+                // global addresses win over link-local ones
+                IpAddr::V6(v6) if ipv6 => {
+                    if v6_address.as_ref().is_none_or(|a| a.contains('%')) {
+                        v6_address = Some(format!("{}", v6));
+                    }
+                }
+                IpAddr::V6(_) => {}
             }
         }
+        if let Some(address) = v6_address {
+            return address;
+        }
     }
     // loopback for testing purpose
-    "127.0.01".to_string()
+    "127.0.0.1".to_string()
+}
+
+// the address of --bind, a link-local IPv6 address takes its interface after a %, by name or
+// by index
+fn bind_address(address: &str, port: u16) -> Option<SocketAddr> {
+    let Some((ip, zone)) = address.split_once('%') else {
+        return address
+            .parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, port));
+    };
+    let ip = ip.parse::<std::net::Ipv6Addr>().ok()?;
+    let scope = match zone.parse::<u32>() {
+        Ok(index) => index,
+        Err(_) => interface_index(zone)?,
+    };
+    Some(SocketAddr::V6(std::net::SocketAddrV6::new(
+        ip, port, 0, scope,
+    )))
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: the name is a valid C string for the duration of the call
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+// other systems take the zone as a number only
+#[cfg(not(unix))]
+fn interface_index(_name: &str) -> Option<u32> {
+    None
+}
+
+// the port of the address, with --find-port the next free one above a port in use. the server
+// cannot start without it
+fn bind_port(addr: SocketAddr, find: bool) -> std::net::TcpListener {
+    let mut tried = addr;
+    loop {
+        match tcp_listener(tried) {
+            Ok(listener) => {
+                if tried != addr {
+                    log::info!("port {} is in use, listening on {}", addr.port(), tried);
+                }
+                return listener;
+            }
+            Err(err)
+                if find
+                    && err.kind() == std::io::ErrorKind::AddrInUse
+                    && tried.port() != 0
+                    && tried.port() - addr.port() < PORT_SCAN
+                    && tried.port() < u16::MAX =>
+            {
+                tried.set_port(tried.port() + 1);
+            }
+            Err(err) => {
+                log::error!("Failed to listen on {}\n{}.", tried, err);
+                eprintln!("Failed to listen on {}: {}", tried, err);
+                if err.kind() == std::io::ErrorKind::AddrInUse {
+                    eprintln!("Pick another port with --port, or take the next free one with --find-port.");
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// the socket of the server, listening on :: it takes IPv4 clients as well wherever the system
+// allows both on one socket
+fn tcp_listener(addr: SocketAddr) -> std::io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket.set_only_v6(false)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn list_files(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
     headers: HeaderMap,
+    Query(sort): Query<SortParams>,
+    Query(paging): Query<PageParams>,
+    Query(layout): Query<LayoutParams>,
     path: Option<AxumPath<String>>,
-) -> impl IntoResponse {
+) -> Response {
     log::info!(
-        "[LIST] Client: {} | UA: {} | Via {}",
+        "[LIST] Client: {} | User: {} | UA: {} | Via {}",
         addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
         headers
             .get(header::USER_AGENT)
             .and_then(|h| h.to_str().ok())
             .unwrap_or("-"),
-        headers.get("via").and_then(|h| h.to_str().ok()).unwrap_or("-")
+        headers
+            .get("via")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("-")
     );
 
+    if !state.within_depth(path.as_deref().map_or("", |v| v), 0) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Folder is deeper than the allowed browse depth",
+        );
+    }
+    if state.exclude.matches(path.as_deref().map_or("", |v| v)) {
+        return error_response(StatusCode::NOT_FOUND, "Folder not found");
+    }
+
     // Determine the directory to list
-    let current_path = if let Some(ref path) = path {
-        state.root.join(path.as_str())
-    } else {
-        state.root.clone()
+    let current_path_str = path.as_deref().map_or("", |v| v);
+    let located = state.locate(&headers, user.as_deref(), current_path_str);
+    // nothing can be uploaded next to the mounts
+    let writable = state.may_write(user.as_deref()) && matches!(located, Ok(Located::Disk(..)));
+    let upload = state.settings.upload() && writable;
+    let delete = state.settings.delete() && writable;
+    // the folder on disk, where the sizes of its subfolders are looked up
+    let mut disk_dir = None;
+    let rows = match located {
+        Ok(Located::Disk(root, below)) => {
+            let current_path = match safe_dir(root, &root.join(below)) {
+                Ok(dir) => dir,
+                Err((status, msg)) => return error_response(status, &msg),
+            };
+            disk_dir = Some(current_path.clone());
+            // the whole scan runs as a single blocking task instead of one per entry
+            let root = root.to_path_buf();
+            tokio::task::spawn_blocking(move || read_rows(&root, &current_path))
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+        }
+        // the mounts are no folder on disk
+        Ok(Located::Mounts(mounts)) => Ok(mounts.rows()),
+        Ok(Located::Packed(packed, below)) => match packed.rows(below) {
+            Some(rows) => Ok(rows),
+            None => return error_response(StatusCode::NOT_FOUND, "Folder not found"),
+        },
+        Err((status, msg)) => return error_response(status, &msg),
     };
-
-    let mut entries = match fs::read_dir(&current_path).await {
-        Ok(rd) => rd,
+    let mut rows = match rows {
+        Ok(rows) => rows,
         Err(e) => {
+            let status = io_error_status(&e);
             let msg = format!("Failed to read directory: {}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page(&msg)));
+            return error_response(status, &msg);
+        }
+    };
+
+    state.shared_rows(&mut rows, current_path_str, anonymous.is_some());
+    sort.sort(&mut rows);
+    let gallery = layout.layout() == Layout::Gallery || listing::mostly_images(&rows);
+    let page = paging.cut(&mut rows);
+    let dir_sizes = match &disk_dir {
+        Some(dir) if dir_sizes::enabled() => {
+            dir_sizes::lookup(dir, current_path_str, &rows, &state.exclude)
         }
+        _ => HashMap::new(),
     };
 
+    // curl and scripts can ask for JSON or plain text instead of the page
+    let format = listing::format(&headers);
+    listing::respond(format, rows, current_path_str, Some(page), |rows| {
+        let mut index = index_page(
+            rows,
+            current_path_str,
+            LinkStyle::Server,
+            upload,
+            state.shares.is_some(),
+            Some(&sort),
+        );
+        // a logged in user with a home folder cannot hand out links, like shares
+        if upload
+            && state.shares.is_some()
+            && user
+                .as_ref()
+                .is_none_or(|Extension(user)| user.root.is_none())
+        {
+            index.upload_link = Some(if current_path_str.is_empty() {
+                base_path::link("/upload-link")
+            } else {
+                base_path::link(&format!(
+                    "/upload-link/{}",
+                    utils::encode_path(current_path_str)
+                ))
+            });
+        }
+        // --allow-delete, every row gets a trash button
+        if delete {
+            for row in index.rows.iter_mut() {
+                let path = if current_path_str.is_empty() {
+                    row.name.clone()
+                } else {
+                    format!("{}/{}", current_path_str, row.name)
+                };
+                row.delete = Some(base_path::link(&format!(
+                    "/delete/{}",
+                    utils::encode_path(&path)
+                )));
+            }
+            index.delete = true;
+        }
+        // --dir-sizes, folders still being computed show an ellipsis
+        for row in index.rows.iter_mut().filter(|row| row.dir) {
+            if let Some(size) = dir_sizes.get(&row.name) {
+                row.size = size.map_or_else(|| "…".to_string(), utils::bytes_to_human_size);
+            }
+        }
+        index.pager = (page.pages > 1).then(|| Pager {
+            page: page.page,
+            pages: page.pages,
+            total: page.total,
+            prev: (page.page > 1).then(|| page.link(&sort, &layout, page.page - 1)),
+            next: (page.page < page.pages).then(|| page.link(&sort, &layout, page.page + 1)),
+        });
+        index.gallery = gallery.then(|| Gallery {
+            shown: layout.layout() == Layout::Gallery,
+            toggle: layout.toggle(&sort),
+        });
+        stream_page(index, Theme::of(&headers))
+    })
+}
+
+// the entries of a folder below `root`, links are followed as --follow-symlinks says
+fn read_rows(root: &Path, dir: &Path) -> std::io::Result<Vec<FileRow>> {
     let mut rows: Vec<FileRow> = Vec::new();
+    let canonical_root = root.canonicalize()?;
 
-    while let Ok(Some(entry)) = entries.next_entry().await {
+    for entry in std::fs::read_dir(dir)? {
+        let Ok(entry) = entry else { continue };
         let file_name = match entry.file_name().into_string() {
             Ok(s) => s,
             Err(_) => continue, // skip non-utf8 names
         };
-        let meta = match entry.metadata().await {
-            Ok(m) => m,
-            Err(_) => continue,
+        let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+        let meta = if is_link {
+            match symlinks::follow(&entry.path(), &canonical_root) {
+                Some(m) => m,
+                None => continue,
+            }
+        } else {
+            match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            }
         };
         let is_dir = meta.is_dir();
         let size = if is_dir { 0 } else { meta.len() };
         let modified: Option<SystemTime> = meta.modified().ok();
+        let hidden = exclude::has_hidden_attribute(&meta);
         rows.push(FileRow {
             name: file_name,
             size,
             modified,
             is_dir,
+            hidden,
+            link: is_link,
         });
     }
 
-    // Sort by name ascending, directories first
-    rows.sort_by(|a, b| {
-        if a.is_dir != b.is_dir {
-            return b.is_dir.cmp(&a.is_dir); // directories first
-        }
-        a.name.to_lowercase().cmp(&b.name.to_lowercase())
-    });
-
-    let current_path_str = path.as_deref().map_or("", |v| v);
-    (StatusCode::OK, Html(render_index(rows, current_path_str)))
+    Ok(rows)
 }
 
-fn render_index(rows: Vec<FileRow>, current_path: &str) -> String {
-    let mut file_rows = String::new();
-    for row in rows {
-        let encoded = utf8_percent_encode(&row.name, NON_ALPHANUMERIC).to_string();
+// fields of the listing page
+#[derive(Serialize)]
+struct IndexPage {
+    // the folder shown, home for the root
+    title: String,
+    // exported pages load them from the export
+    assets: String,
+    breadcrumb: Vec<Crumb>,
+    back: Option<String>,
+    upload_action: Option<String>,
+    // hands out a link for others to send files into the folder, --url-secret
+    upload_link: Option<String>,
+    // exported pages have no server to build the archive
+    selection_action: Option<String>,
+    rows: Vec<IndexRow>,
+    // column header links, only where the listing can be sorted
+    sort: Option<SortLinks>,
+    // the search below the folder, not in exported pages and shares
+    search: Option<SearchForm>,
+    // links to the other pages of a big folder
+    pager: Option<Pager>,
+    // offered for folders of images, only by the server
+    gallery: Option<Gallery>,
+    // the folder has music, the player goes through it as a playlist
+    player: bool,
+    // the QR code of the link of the folder, only by the server
+    qr: Option<String>,
+    // the events telling the page its folder changed, --live
+    live: Option<String>,
+    // rows have a trash button, --allow-delete
+    delete: bool,
+}
 
-        let name_display = if row.is_dir {
-            format!("📁 {}", utils::html_escape(&row.name))
-        } else {
-            format!("📄 {}", utils::html_escape(&row.name))
-        };
+#[derive(Serialize)]
+struct Gallery {
+    // the rows are shown as tiles
+    shown: bool,
+    // switches between the table and the tiles
+    toggle: String,
+}
 
-        let size_str = if row.is_dir {
-            "-".to_string()
-        } else {
-            utils::bytes_to_human_size(row.size)
-        };
+#[derive(Serialize)]
+struct Pager {
+    page: usize,
+    pages: usize,
+    total: usize,
+    prev: Option<String>,
+    next: Option<String>,
+}
 
-        let modified_str = row
-            .modified
-            .and_then(|st| {
-                Some(
-                    DateTime::<Local>::from(st)
-                        .format("%Y-%m-%d %H:%M:%S")
-                        .to_string(),
-                )
-            })
-            .unwrap_or_else(|| "-".to_string());
+#[derive(Serialize)]
+struct SearchForm {
+    path: String,
+    query: String,
+    // the page lists the matches of `query`
+    results: bool,
+    // the search stopped before it went through every folder
+    truncated: bool,
+    // number of matches
+    count: usize,
+}
 
-        let element_path = if current_path.is_empty() {
-            encoded.clone()
-        } else {
-            format!("{}/{}", current_path, encoded)
-        };
+#[derive(Serialize)]
+struct SortLinks {
+    key: &'static str,
+    order: &'static str,
+    name: String,
+    size: String,
+    modified: String,
+}
 
-        let action = if row.is_dir {
-            format!("browse/{}\">Open", element_path)
-        } else {
-            format!("download/{}\">Download", element_path)
-        };
+#[derive(Serialize)]
+struct Crumb {
+    name: String,
+    // none for the folder shown
+    href: Option<String>,
+}
 
-        file_rows.push_str(&format!(
-            "<tr>\n  <td class=\"truncate\">{}</td>\n  <td>{}</td>\n  <td>{}</td>\n  <td><a class=\"btn\"href=\"/{}<a></td>\n</tr>",
-            name_display, size_str, modified_str, action
-        ));
-    }
+#[derive(Serialize)]
+struct IndexRow {
+    name: String,
+    dir: bool,
+    size: String,
+    modified: String,
+    // opens the folder or downloads the file
+    href: String,
+    // shows the file in the browser
+    view: Option<String>,
+    // preview of an image, --thumbnails
+    thumb: Option<String>,
+    // shown in the lightbox of the gallery
+    image: bool,
+    // a track of the player, played from this link
+    audio: Option<String>,
+    archive: Option<String>,
+    share: Option<String>,
+    // the QR code of the download link of a file
+    qr: Option<String>,
+    // DELETE removes the file or empty folder, --allow-delete
+    delete: Option<String>,
+}
 
-    // Generate breadcrumb navigation
-    let breadcrumb = generate_breadcrumb(current_path);
+fn render_index(
+    rows: Vec<FileRow>,
+    current_path: &str,
+    links: LinkStyle,
+    upload: bool,
+    share: bool,
+    sort: Option<&SortParams>,
+    theme: Theme,
+) -> String {
+    render_page(
+        &index_page(rows, current_path, links, upload, share, sort),
+        theme,
+    )
+}
 
-    // Load and render template
-    match load_template() {
-        Ok(template) => {
-            let title_suffix = if current_path.is_empty() {
-                " - home".to_string()
+// the listing of a folder, row names can hold a path below it like the search results do
+fn index_page(
+    rows: Vec<FileRow>,
+    current_path: &str,
+    links: LinkStyle,
+    upload: bool,
+    share: bool,
+    sort: Option<&SortParams>,
+) -> IndexPage {
+    let rows: Vec<IndexRow> = rows
+        .into_iter()
+        .map(|row| {
+            let encoded = utils::encode_path(&row.name);
+            let element_path = if current_path.is_empty() {
+                encoded.clone()
             } else {
-                format!(" - {}", current_path)
+                utils::encode_path(&format!("{}/{}", current_path, row.name))
             };
-            // Compute back button (only if inside a subfolder)
-            let back_button = if current_path.is_empty() {
-                String::new()
-            } else {
-                let mut parts: Vec<&str> =
-                    current_path.split('/').filter(|s| !s.is_empty()).collect();
-                let _ = parts.pop();
-                let href = if parts.is_empty() {
-                    "/".to_string()
-                } else {
-                    format!("/browse/{}", parts.join("/"))
-                };
-                format!(
-                    "<p><a class=\"btn btn-secondary\" href=\"{}\">← Back</a></p>",
-                    href
-                )
+            let href = match (links, row.is_dir) {
+                (LinkStyle::Server, true) => base_path::link(&format!("/browse/{}", element_path)),
+                (LinkStyle::Server, false) => {
+                    base_path::link(&format!("/download/{}", element_path))
+                }
+                (LinkStyle::Share(prefix), _) => format!("{}/{}", prefix, element_path),
+                (LinkStyle::Static, true) => format!("{}/index.html", encoded),
+                (LinkStyle::Static, false) => encoded,
             };
+            IndexRow {
+                size: if row.is_dir {
+                    "-".to_string()
+                } else {
+                    utils::bytes_to_human_size(row.size)
+                },
+                modified: row
+                    .modified
+                    .map(|st| {
+                        DateTime::<Local>::from(st)
+                            .format("%Y-%m-%d %H:%M:%S")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| "-".to_string()),
+                href,
+                view: (matches!(links, LinkStyle::Server) && !row.is_dir)
+                    .then(|| base_path::link(&format!("/view/{}", element_path))),
+                thumb: (matches!(links, LinkStyle::Server)
+                    && !row.is_dir
+                    && thumbnails::enabled()
+                    && thumbnails::is_image(&row.name))
+                .then(|| base_path::link(&format!("/thumb/{}", element_path))),
+                image: !row.is_dir && thumbnails::is_image(&row.name),
+                audio: (matches!(links, LinkStyle::Server)
+                    && !row.is_dir
+                    && audio::is_audio(&row.name))
+                .then(|| base_path::link(&format!("/view/{}", element_path))),
+                archive: (matches!(links, LinkStyle::Server) && row.is_dir)
+                    .then(|| base_path::link(&format!("/archive/{}", element_path))),
+                share: share.then(|| base_path::link(&format!("/share/{}", element_path))),
+                qr: (matches!(links, LinkStyle::Server) && !row.is_dir)
+                    .then(|| base_path::link(&format!("/qr/file/{}", element_path))),
+                delete: None,
+                name: row.name,
+                dir: row.is_dir,
+            }
+        })
+        .collect();
+
+    let parts: Vec<&str> = current_path.split('/').filter(|s| !s.is_empty()).collect();
+    // only inside a subfolder
+    let back = parts.split_last().map(|(_, parents)| match links {
+        LinkStyle::Static => "../index.html".to_string(),
+        LinkStyle::Server if parents.is_empty() => base_path::link("/"),
+        LinkStyle::Server => base_path::link(&format!("/browse/{}", parents.join("/"))),
+        LinkStyle::Share(prefix) if parents.is_empty() => prefix.to_string(),
+        LinkStyle::Share(prefix) => format!("{}/{}", prefix, parents.join("/")),
+    });
 
-            // loading data into template
-            template
-                .replace("{title_suffix}", &title_suffix)
-                .replace("{breadcrumb}", &breadcrumb)
-                .replace("{back_button}", &back_button)
-                .replace("{file_rows}", &file_rows)
+    let encoded: Vec<String> = parts
+        .iter()
+        .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+        .collect();
+    let encoded = encoded.join("/");
+    let action = |base: &str| {
+        if encoded.is_empty() {
+            base.to_string()
+        } else {
+            format!("{}/{}", base, encoded)
         }
+    };
+
+    let player = rows.iter().any(|row| row.audio.is_some());
+    IndexPage {
+        title: if current_path.is_empty() {
+            "home".to_string()
+        } else {
+            current_path.to_string()
+        },
+        assets: match links {
+            LinkStyle::Static => format!("{}{}", "../".repeat(parts.len()), assets::EXPORT_DIR),
+            LinkStyle::Server | LinkStyle::Share(_) => base_path::link("/assets"),
+        },
+        breadcrumb: breadcrumb(&parts, links),
+        back,
+        upload_action: upload.then(|| action(&base_path::link("/upload"))),
+        upload_link: None,
+        selection_action: matches!(links, LinkStyle::Server)
+            .then(|| action(&base_path::link("/archive"))),
+        live: (matches!(links, LinkStyle::Server) && live::enabled())
+            .then(|| action(&base_path::link("/events"))),
+        delete: false,
+        qr: matches!(links, LinkStyle::Server).then(|| {
+            if encoded.is_empty() {
+                base_path::link("/qr.svg")
+            } else {
+                format!("{}?path={}", base_path::link("/qr.svg"), encoded)
+            }
+        }),
+        rows,
+        sort: sort.map(|sort| SortLinks {
+            key: sort.key().name(),
+            order: sort.order().name(),
+            name: sort.link(SortKey::Name),
+            size: sort.link(SortKey::Size),
+            modified: sort.link(SortKey::Modified),
+        }),
+        search: matches!(links, LinkStyle::Server).then(|| SearchForm {
+            path: current_path.to_string(),
+            query: String::new(),
+            results: false,
+            truncated: false,
+            count: 0,
+        }),
+        player,
+        pager: None,
+        gallery: None,
+    }
+}
+
+fn render_page(page: &IndexPage, theme: Theme) -> String {
+    match templates::render("index.html", theme, page) {
+        Ok(html) => html,
         Err(e) => {
             log::error!("Error loading template: {}", e);
             // Fallback to simple error page
@@ -330,94 +2772,413 @@ fn render_index(rows: Vec<FileRow>, current_path: &str) -> String {
     }
 }
 
-fn generate_breadcrumb(current_path: &str) -> String {
-    let mut breadcrumb = String::from("<a href=\"/\">Home</a>");
+// rows rendered at a time when a listing is streamed
+const STREAM_CHUNK: usize = 200;
 
-    if current_path.is_empty() {
-        return breadcrumb;
-    }
+// sends the listing a few rows at a time, so big folders start showing at once and the whole
+// page is never held in memory. the page is rendered again for every chunk and only the rows
+// between the <!-- rows --> and <!-- /rows --> marks are sent, a template without the marks
+// goes out in one piece
+fn stream_page(mut page: IndexPage, theme: Theme) -> Body {
+    let (tx, rx) = mpsc::channel::<String>(2);
+    tokio::task::spawn_blocking(move || {
+        let mut rows = std::mem::take(&mut page.rows).into_iter();
+        page.rows = rows.by_ref().take(STREAM_CHUNK).collect();
+        let first = render_page(&page, theme);
+        let Some((head, tail)) = split_rows(&first) else {
+            page.rows.extend(rows);
+            let _ = tx.blocking_send(render_page(&page, theme));
+            return;
+        };
+        if tx.blocking_send(head.to_string()).is_err() {
+            return;
+        }
+        loop {
+            page.rows = rows.by_ref().take(STREAM_CHUNK).collect();
+            if page.rows.is_empty() {
+                break;
+            }
+            let html = render_page(&page, theme);
+            let chunk = split_rows(&html).map_or("", |(head, _)| {
+                head.rsplit_once(ROWS_START).map_or("", |(_, rows)| rows)
+            });
+            // the client went away
+            if tx.blocking_send(chunk.to_string()).is_err() {
+                return;
+            }
+        }
+        let _ = tx.blocking_send(tail.to_string());
+    });
 
-    let path_parts: Vec<&str> = current_path.split('/').filter(|s| !s.is_empty()).collect();
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|chunk| (Ok::<_, Infallible>(chunk), rx))
+    });
+    Body::from_stream(stream)
+}
+
+const ROWS_START: &str = "<!-- rows -->";
+const ROWS_END: &str = "<!-- /rows -->";
 
-    let mut current_breadcrumb_path = String::new();
-    for (i, part) in path_parts.iter().enumerate() {
-        current_breadcrumb_path.push_str("/");
-        current_breadcrumb_path.push_str(part);
+// the page up to the end of its rows and the rest
+fn split_rows(html: &str) -> Option<(&str, &str)> {
+    let start = html.find(ROWS_START)?;
+    let end = start + html[start..].find(ROWS_END)?;
+    Some(html.split_at(end))
+}
 
-        let _encoded_part = utf8_percent_encode(part, NON_ALPHANUMERIC).to_string();
-        let encoded_path =
-            utf8_percent_encode(&current_breadcrumb_path, NON_ALPHANUMERIC).to_string();
+// Home, then every folder down to the one shown
+fn breadcrumb(parts: &[&str], links: LinkStyle) -> Vec<Crumb> {
+    // exported pages reach their parents through ../
+    let static_href = |levels_up: usize| format!("{}index.html", "../".repeat(levels_up));
 
-        breadcrumb.push_str(" / ");
-        if i == path_parts.len() - 1 {
-            // Last part is not clickable
-            breadcrumb.push_str(&utils::html_escape(part));
-        } else {
-            breadcrumb.push_str(&format!(
-                "<a href=\"/browse{}\">{}</a>",
-                encoded_path,
-                utils::html_escape(part)
-            ));
-        }
-    }
+    let home = match links {
+        LinkStyle::Server => base_path::link("/"),
+        LinkStyle::Static => static_href(parts.len()),
+        LinkStyle::Share(prefix) => prefix.to_string(),
+    };
+    let mut breadcrumb = vec![Crumb {
+        name: "Home".to_string(),
+        href: Some(home),
+    }];
 
+    for (i, part) in parts.iter().enumerate() {
+        let encoded: Vec<String> = parts[..=i]
+            .iter()
+            .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+            .collect();
+        // Last part is not clickable
+        let href = (i < parts.len() - 1).then(|| match links {
+            LinkStyle::Server => base_path::link(&format!("/browse/{}", encoded.join("/"))),
+            LinkStyle::Static => static_href(parts.len() - 1 - i),
+            LinkStyle::Share(prefix) => format!("{}/{}", prefix, encoded.join("/")),
+        });
+        breadcrumb.push(Crumb {
+            name: part.to_string(),
+            href,
+        });
+    }
     breadcrumb
 }
 
+// calls `done` once the whole body was sent, an aborted or failed transfer drops it instead
+fn when_sent(res: Response, done: impl FnOnce() + Send + 'static) -> Response {
+    let (parts, body) = res.into_parts();
+    let stream = futures_util::stream::unfold(
+        (body.into_data_stream(), Some(done)),
+        |(mut body, mut done)| async move {
+            match body.next().await {
+                Some(Ok(chunk)) => Some((Ok(chunk), (body, done))),
+                Some(Err(err)) => {
+                    done.take();
+                    Some((Err(std::io::Error::other(err)), (body, None)))
+                }
+                None => {
+                    if let Some(done) = done.take() {
+                        done();
+                    }
+                    None
+                }
+            }
+        },
+    );
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+// how a file is handed to the browser
+#[derive(Clone, Copy, PartialEq)]
+enum Disposition {
+    // saved under its file name
+    Attachment,
+    // shown by the browser, as far as it can
+    Inline,
+}
+
 async fn download_file(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    method: Method,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    send_file(
+        state,
+        addr,
+        user,
+        &method,
+        headers,
+        path,
+        Disposition::Attachment,
+    )
+    .await
+}
+
+// /view, the file shown by the browser instead of saved, Markdown rendered to a page and
+// videos in a player
+async fn view_file(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    method: Method,
+    headers: HeaderMap,
+    Query(params): Query<markdown::ViewParams>,
     AxumPath(path): AxumPath<String>,
 ) -> Response {
+    if markdown::is_markdown(&path) {
+        return markdown::view(state, addr, user, headers, path, params).await;
+    }
+    // the player page, it loads the file itself with ?raw=1
+    if video::is_video(&path) && !params.raw() {
+        return video::view(&state, user.as_deref(), &headers, &path);
+    }
+    send_file(
+        state,
+        addr,
+        user,
+        &method,
+        headers,
+        path,
+        Disposition::Inline,
+    )
+    .await
+}
+
+// the file at `path` below the root, with the checks of the listings. HEAD gets the headers of
+// the download alone, it takes no download slot and counts as no download
+async fn send_file(
+    state: AppState,
+    addr: SocketAddr,
+    user: Option<Extension<User>>,
+    method: &Method,
+    headers: HeaderMap,
+    path: String,
+    disposition: Disposition,
+) -> Response {
+    // requests can still arrive while the server stops after its last download
+    if state.shutdown.exhausted() {
+        return error_response(StatusCode::GONE, "The download limit was reached");
+    }
     // Security check: prevent directory traversal attacks
     if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
-        return (StatusCode::BAD_REQUEST, "Invalid file path").into_response();
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    if !state.within_depth(&path, 1) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "File is deeper than the allowed browse depth",
+        );
+    }
+    if state.exclude.matches(&path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
     }
 
-    let file_path: PathBuf = state.root.join(&path);
+    let head = method == Method::HEAD;
+    let guard = if head {
+        None
+    } else {
+        match state.stream_guard(addr) {
+            Ok(guard) => guard,
+            Err((status, msg)) => return error_response(status, &msg),
+        }
+    };
 
-    match safe_open(&state.root, &file_path).await {
-        Ok((file, mime)) => {
-            let stream = ReaderStream::new(file);
-            let body = Body::from_stream(stream);
-            let mut res = Response::new(body);
-            let headers = res.headers_mut();
-            headers.insert(
-                header::CONTENT_TYPE,
-                HeaderValue::from_str(&mime)
-                    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
-            );
+    let (root, below) = match state.locate(&headers, user.as_deref(), &path) {
+        Ok(Located::Disk(root, below)) => (root, below),
+        Ok(Located::Mounts(_)) => return error_response(StatusCode::NOT_FOUND, "File not found"),
+        Ok(Located::Packed(packed, below)) => {
+            // files outside the size and age filters are not shared
+            if !packed
+                .row(below)
+                .is_some_and(|row| state.filter.matches(row.size, row.modified))
+            {
+                return error_response(StatusCode::NOT_FOUND, "File not found");
+            }
+            let size = packed.row(below).map(|row| row.size);
+            let mime = content_type(Path::new(below));
+            if head {
+                let mut res = file_response(Body::empty(), mime, below, disposition);
+                if let Some(size) = size {
+                    content_length(&mut res, size);
+                }
+                return res;
+            }
+            return match packed.body(below, guard).await {
+                Ok(body) => {
+                    log::info!(
+                        "downloading file: {} from {} | User: {}",
+                        below,
+                        packed.file().display(),
+                        user.as_ref()
+                            .map_or("-".to_string(), |Extension(user)| user.to_string())
+                    );
+                    let mut res = file_response(body, mime, below, disposition);
+                    if let Some(size) = size {
+                        content_length(&mut res, size);
+                    }
+                    state.shutdown.counted(res)
+                }
+                Err((status, msg)) => error_response(status, &msg),
+            };
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let file_path: PathBuf = root.join(below);
 
-            // Extract just the filename for the download
-            let filename = file_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("download");
-            let disposition = format!("attachment; filename=\"{}\"", filename);
-            headers.insert(
-                header::CONTENT_DISPOSITION,
-                HeaderValue::from_str(&disposition).unwrap(),
-            );
+    match safe_open(root, &file_path).await {
+        Ok((file, mime, canonical_path)) => {
+            // files outside the size and age filters are not shared, nor hidden ones
+            let meta = match file.metadata().await {
+                Ok(meta)
+                    if state.filter.matches(meta.len(), meta.modified().ok())
+                        && (state.exclude.show_hidden || !exclude::has_hidden_attribute(&meta)) =>
+                {
+                    meta
+                }
+                _ => return error_response(StatusCode::NOT_FOUND, "File not found"),
+            };
+            // a .br or .gz copy next to the file is sent in its place, for whole files only
+            let copies = if headers.contains_key(header::RANGE) {
+                Vec::new()
+            } else {
+                compression::copies(root, &canonical_path, &meta)
+            };
+            let vary = !copies.is_empty();
+            let copy = copies
+                .into_iter()
+                .find(|copy| compression::accepts(&headers, copy.encoding));
+            let (mut file, meta, encoding) = match copy {
+                Some(copy) => match fs::File::open(&copy.path).await {
+                    Ok(opened) => (opened, copy.meta, Some(copy.encoding)),
+                    Err(_) => (file, meta, None),
+                },
+                None => (file, meta, None),
+            };
+            let len = meta.len();
+            // the browser still has this version of the file
+            let validators = Validators::of(&meta);
+            if validators.fresh(&headers) {
+                let mut res = validators.not_modified();
+                compression::mark(&mut res, None, vary);
+                return res;
+            }
 
-            log::info!("downloading file: {}", &file_path.display());
-            res
+            if !head {
+                log::info!(
+                    "downloading file: {} | User: {}",
+                    &file_path.display(),
+                    user.as_ref()
+                        .map_or("-".to_string(), |Extension(user)| user.to_string())
+                );
+            }
+            let requested = if validators.range_applies(&headers) {
+                range::requested(&headers, len)
+            } else {
+                Requested::Whole
+            };
+            match requested {
+                Requested::Whole => {
+                    let body = if head {
+                        Body::empty()
+                    } else {
+                        file_body(file, guard)
+                    };
+                    let mut res = file_response(body, &mime, below, disposition);
+                    range::accept(&mut res);
+                    validators.apply(&mut res);
+                    compression::mark(&mut res, encoding, vary);
+                    content_length(&mut res, len);
+                    if head {
+                        return res;
+                    }
+                    state.shutdown.counted(res)
+                }
+                // parts of a file, a player seeking in a video, are no complete download
+                Requested::Part(start, end) if head => {
+                    let mut res = file_response(Body::empty(), &mime, below, disposition);
+                    range::accept(&mut res);
+                    range::partial(&mut res, start, end, len);
+                    validators.apply(&mut res);
+                    res
+                }
+                Requested::Part(start, end) => {
+                    if let Err(err) = file.seek(std::io::SeekFrom::Start(start)).await {
+                        log::error!("cannot seek in file {}\n{}", file_path.display(), err);
+                        return error_response(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Cannot open desired file.",
+                        );
+                    }
+                    let body = guarded_body(ReaderStream::new(file.take(end - start + 1)), guard);
+                    let mut res = file_response(body, &mime, below, disposition);
+                    range::accept(&mut res);
+                    range::partial(&mut res, start, end, len);
+                    validators.apply(&mut res);
+                    res
+                }
+                Requested::Unsatisfiable => range::unsatisfiable(len),
+            }
         }
-        Err((status, msg)) => (status, msg).into_response(),
+        Err((status, msg)) => error_response(status, &msg),
     }
 }
 
-async fn safe_open(root: &Path, target: &Path) -> Result<(fs::File, String), (StatusCode, String)> {
-    let canonical_root = root.canonicalize().map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to access root".to_string(),
-        )
-    })?;
-    let canonical_target = target
-        .canonicalize()
-        .map_err(|_| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
-    if !canonical_target.starts_with(&canonical_root) {
-        return Err((StatusCode::FORBIDDEN, "Access denied".to_string()));
+// the type sent for a file, players need the right one for videos and music
+fn content_type(path: &Path) -> &'static str {
+    video::mime(path)
+        .or_else(|| audio::mime(path))
+        .or_else(|| mime_guess::from_path(path).first_raw())
+        .unwrap_or("application/octet-stream")
+}
+
+// the size of a file sent whole, so browsers can show the progress of the download. the body
+// is streamed and would be sent chunked without it
+fn content_length(res: &mut Response, len: u64) {
+    res.headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+}
+
+// the response sending the file at `path`
+fn file_response(body: Body, mime: &str, path: &str, disposition: Disposition) -> Response {
+    let mut res = Response::new(body);
+    res.extensions_mut().insert(FileContent);
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime).unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+    if disposition == Disposition::Inline {
+        return res;
     }
+
+    // Extract just the filename for the download
+    let filename = Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("download");
+    let disposition = format!("attachment; filename=\"{}\"", filename);
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).unwrap(),
+    );
+    res
+}
+
+// canonical path of a folder inside the root
+fn safe_dir(root: &Path, target: &Path) -> Result<PathBuf, (StatusCode, String)> {
+    let canonical_target = symlinks::resolve(root, target, "Folder")?;
+    if !canonical_target.is_dir() {
+        return Err((StatusCode::NOT_FOUND, "Folder not found".to_string()));
+    }
+    Ok(canonical_target)
+}
+
+async fn safe_open(
+    root: &Path,
+    target: &Path,
+) -> Result<(fs::File, String, PathBuf), (StatusCode, String)> {
+    let canonical_target = symlinks::resolve(root, target, "File")?;
     let mut f = fs::File::open(&canonical_target)
         .await
         .map_err(|_| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
@@ -425,14 +3186,12 @@ async fn safe_open(root: &Path, target: &Path) -> Result<(fs::File, String), (St
     let mut _buf = [0u8; 0];
     match f.read(&mut _buf).await {
         Ok(_) => {
-            let mime = mime_guess::from_path(&canonical_target)
-                .first_or_octet_stream()
-                .to_string();
-            Ok((f, mime))
+            let mime = content_type(&canonical_target).to_string();
+            Ok((f, mime, canonical_target))
         }
 
         Err(err) => {
-            log::error!("cannot open file {}\n{}", &canonical_root.display(), err);
+            log::error!("cannot open file {}\n{}", &canonical_target.display(), err);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Cannot open desired file.".to_string(),
@@ -441,17 +3200,30 @@ async fn safe_open(root: &Path, target: &Path) -> Result<(fs::File, String), (St
     }
 }
 
-fn error_page(msg: &str) -> String {
-    match load_error_template() {
-        Ok(template) => template.replace("{error_message}", &utils::html_escape(msg)),
-        Err(e) => {
-            log::error!("Error loading error template: {}", e);
-            // Fallback to simple error page
-            format!(
-                "<h1>Error</h1><p>Failed to load error template: {}</p><p>Error: {}</p>",
-                utils::html_escape(&e.to_string()),
-                utils::html_escape(msg)
-            )
-        }
+// streams an opened file, reads go through io_uring when that backend is running. the file
+// checked by safe_open is handed over, it is never opened again by its path
+fn file_body(file: fs::File, guard: Option<StreamGuard>) -> Body {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    let file = match file.try_into_std().map(uring::stream) {
+        Ok(Ok(stream)) => return guarded_body(stream, guard),
+        Ok(Err(file)) => fs::File::from_std(file),
+        Err(file) => file,
+    };
+    guarded_body(ReaderStream::new(file), guard)
+}
+
+// keeps the download slot until the body is done or dropped
+fn guarded_body<S>(stream: S, guard: Option<StreamGuard>) -> Body
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+{
+    let stream = stream.map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    // --limit-rate, the slot is kept while the download is held back
+    if throttle::enabled() {
+        return Body::from_stream(throttle::limit(stream));
     }
+    Body::from_stream(stream)
 }
@@ -0,0 +1,168 @@
+use crate::{
+    auth::User,
+    base_path,
+    errors::error_response,
+    send_file,
+    templates::{self, Theme},
+    utils, AppState, Disposition,
+};
+use axum::{
+    http::{HeaderMap, Method, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+// bigger files are left to the download, the page holds the whole file twice
+const MAX_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ViewParams {
+    // shows the source instead of the rendered page
+    raw: Option<String>,
+}
+
+impl ViewParams {
+    pub fn raw(&self) -> bool {
+        self.raw.is_some()
+    }
+}
+
+// fields of the markdown page
+#[derive(Serialize)]
+struct MarkdownPage<'a> {
+    name: &'a str,
+    // the rendered file, inserted as it is
+    html: String,
+    source: &'a str,
+    raw: bool,
+    // switches between the rendered page and the source
+    toggle: String,
+    download: String,
+    // the listing of the folder the file is in
+    back: String,
+}
+
+pub fn is_markdown(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".md") || path.ends_with(".markdown")
+}
+
+// /view of a .md file, rendered into the markdown page. the file goes through the same checks
+// as a download and counts as one
+pub async fn view(
+    state: AppState,
+    addr: SocketAddr,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    path: String,
+    params: ViewParams,
+) -> Response {
+    let theme = Theme::of(&headers);
+    // the page is made of the content of the file, HEAD included
+    let res = send_file(
+        state,
+        addr,
+        user,
+        &Method::GET,
+        headers,
+        path.clone(),
+        Disposition::Inline,
+    )
+    .await;
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+    let Ok(bytes) = axum::body::to_bytes(res.into_body(), MAX_SIZE).await else {
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "The file is too big to be shown, download it instead",
+        );
+    };
+    let source = String::from_utf8_lossy(&bytes);
+
+    let encoded = utils::encode_path(&path);
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let raw = params.raw();
+    let page = MarkdownPage {
+        name,
+        html: if raw { String::new() } else { to_html(&source) },
+        source: &source,
+        raw,
+        toggle: if raw {
+            base_path::link(&format!("/view/{}", encoded))
+        } else {
+            base_path::link(&format!("/view/{}?raw=1", encoded))
+        },
+        download: base_path::link(&format!("/download/{}", encoded)),
+        back: match path.rsplit_once('/') {
+            Some((folder, _)) => {
+                base_path::link(&format!("/browse/{}", utils::encode_path(folder)))
+            }
+            None => base_path::link("/"),
+        },
+    };
+    match templates::render("markdown.html", theme, &page) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            log::error!("Error loading markdown template: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load markdown template",
+            )
+        }
+    }
+}
+
+// HTML written in the file is shown as text and script links are dropped, so a shared file
+// cannot run code in the browser of a visitor
+fn to_html(source: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let events = Parser::new_ext(source, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if scripted(&dest_url, true) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: "#".into(),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if scripted(&dest_url, false) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: "".into(),
+            title,
+            id,
+        }),
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+// whether the url runs a script when followed, data urls only do so as links
+fn scripted(url: &str, link: bool) -> bool {
+    let scheme = url
+        .trim()
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_lowercase());
+    match scheme.as_deref() {
+        Some("javascript" | "vbscript") => true,
+        Some("data") => link,
+        _ => false,
+    }
+}
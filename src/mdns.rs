@@ -0,0 +1,66 @@
+use crate::base_path;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::error::Error;
+
+// instance name when --mdns-name is not given, the first label of the system host name
+pub fn default_name() -> String {
+    system_hostname()
+        .and_then(|name| name.split('.').next().map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "file-serve".to_string())
+}
+
+// the .local host name for an instance name, letters, digits and dashes only
+pub fn host_name(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let label = label.trim_matches('-');
+    format!(
+        "{}.local",
+        if label.is_empty() {
+            "file-serve"
+        } else {
+            label
+        }
+    )
+}
+
+// announces the server as _http._tcp (_https._tcp with TLS) on every interface, the
+// daemon answers queries on its own thread until it is dropped
+pub fn advertise(name: &str, scheme: &str, port: u16) -> Result<ServiceDaemon, Box<dyn Error>> {
+    let daemon = ServiceDaemon::new()?;
+    let service = ServiceInfo::new(
+        &format!("_{}._tcp.local.", scheme),
+        name,
+        &format!("{}.", host_name(name)),
+        "",
+        port,
+        &[("path", base_path::link("/").as_str())][..],
+    )?
+    .enable_addr_auto();
+    daemon.register(service)?;
+    Ok(daemon)
+}
+
+#[cfg(unix)]
+fn system_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+#[cfg(not(unix))]
+fn system_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
@@ -0,0 +1,270 @@
+use crate::{
+    auth::User, errors::error_response, exclude, symlinks, thumbnails, video, AppState, Located,
+};
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use exif::{Exif, In, Tag, Value};
+use image::ImageReader;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    process::Stdio,
+    sync::OnceLock,
+    time::Duration,
+};
+use tokio::process::Command;
+
+// --show-gps, the position photos were taken at is left out unless asked for
+static SHOW_GPS: OnceLock<bool> = OnceLock::new();
+
+pub fn set_show_gps(show: bool) {
+    let _ = SHOW_GPS.set(show);
+}
+
+fn show_gps() -> bool {
+    SHOW_GPS.get().copied().unwrap_or(false)
+}
+
+// how long ffprobe may take to read a video
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// what /api/meta tells about a file, fields it cannot find out are left out
+#[derive(Serialize, Default)]
+struct Meta {
+    name: String,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    // seconds, videos only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    camera: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lens: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taken: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exposure: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aperture: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iso: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focal_length: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gps: Option<Gps>,
+}
+
+#[derive(Serialize)]
+struct Gps {
+    latitude: f64,
+    longitude: f64,
+}
+
+// /api/meta/<path>, the dimensions of an image or video and the EXIF data of a photo as JSON.
+// the file goes through the checks of a download
+pub async fn metadata(
+    State(state): State<AppState>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    if !state.within_depth(&path, 1) || state.exclude.matches(&path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let file = match state.locate(&headers, user.as_deref(), &path) {
+        Ok(Located::Disk(root, below)) => {
+            match symlinks::resolve(root, &root.join(below), "File") {
+                Ok(file) => file,
+                Err((status, msg)) => return error_response(status, &msg),
+            }
+        }
+        // the entries of --archive are not read for their details
+        Ok(Located::Mounts(_)) | Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::NOT_FOUND, "File not found")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    // files outside the size and age filters are not shared, nor hidden ones
+    let meta = match fs::metadata(&file) {
+        Ok(meta)
+            if meta.is_file()
+                && state.filter.matches(meta.len(), meta.modified().ok())
+                && (state.exclude.show_hidden || !exclude::has_hidden_attribute(&meta)) =>
+        {
+            meta
+        }
+        _ => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+
+    let mut found = Meta {
+        name: path.rsplit('/').next().unwrap_or(&path).to_string(),
+        size: meta.len(),
+        modified: meta.modified().ok().map(|modified| {
+            DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, true)
+        }),
+        ..Meta::default()
+    };
+    if thumbnails::is_image(&path) {
+        let file = file.clone();
+        found = tokio::task::spawn_blocking(move || {
+            read_image(&file, &mut found);
+            found
+        })
+        .await
+        .unwrap_or_default();
+    } else if video::is_video(&path) {
+        probe_video(&file, &mut found).await;
+    }
+    Json(found).into_response()
+}
+
+// the size of the image and what its EXIF data tells about the photo
+fn read_image(file: &Path, meta: &mut Meta) {
+    if let Ok((width, height)) = ImageReader::open(file)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(image::ImageError::IoError)
+        .and_then(|reader| reader.into_dimensions())
+    {
+        meta.width = Some(width);
+        meta.height = Some(height);
+    }
+    let Ok(exif) = File::open(file)
+        .map_err(exif::Error::Io)
+        .and_then(|file| exif::Reader::new().read_from_container(&mut BufReader::new(file)))
+    else {
+        return;
+    };
+
+    meta.camera = match (text(&exif, Tag::Make), text(&exif, Tag::Model)) {
+        // the model mostly starts with the make already
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    meta.lens = text(&exif, Tag::LensModel);
+    meta.taken = text(&exif, Tag::DateTimeOriginal).or_else(|| text(&exif, Tag::DateTime));
+    meta.exposure = shown(&exif, Tag::ExposureTime);
+    meta.aperture = shown(&exif, Tag::FNumber);
+    meta.iso = shown(&exif, Tag::PhotographicSensitivity);
+    meta.focal_length = shown(&exif, Tag::FocalLength);
+    if show_gps() {
+        meta.gps = degrees(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S")
+            .zip(degrees(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"))
+            .map(|(latitude, longitude)| Gps {
+                latitude,
+                longitude,
+            });
+    }
+}
+
+// a text field without the padding cameras leave in it
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(parts) => parts
+            .first()
+            .map(|part| {
+                String::from_utf8_lossy(part)
+                    .trim_matches(['\0', ' '])
+                    .to_string()
+            })
+            .filter(|text| !text.is_empty()),
+        _ => None,
+    }
+}
+
+// a number with its unit, like 1/125 s or f/2.8
+fn shown(exif: &Exif, tag: Tag) -> Option<String> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    Some(field.display_value().with_unit(exif).to_string())
+}
+
+// a position from degrees, minutes and seconds, negative south and west
+fn degrees(exif: &Exif, tag: Tag, reference: Tag, negative: &str) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let [degrees, minutes, seconds] = parts.as_slice() else {
+        return None;
+    };
+    let value = degrees.to_f64() + minutes.to_f64() / 60.0 + seconds.to_f64() / 3600.0;
+    if text(exif, reference).is_some_and(|r| r.eq_ignore_ascii_case(negative)) {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
+
+#[derive(Deserialize)]
+struct Probe {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+// the size, codec and length of a video, read by ffprobe when it is installed
+async fn probe_video(file: &Path, meta: &mut Meta) {
+    let mut command = Command::new("ffprobe");
+    command
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries"])
+        .arg("stream=codec_name,width,height:format=duration")
+        .args(["-of", "json"])
+        .arg(file)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let output = match tokio::time::timeout(PROBE_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) if output.status.success() => output,
+        Ok(Ok(_)) => return,
+        Ok(Err(err)) => {
+            log::debug!("[META] cannot run ffprobe: {}", err);
+            return;
+        }
+        Err(_) => {
+            log::warn!("[META] ffprobe took too long on {}", file.display());
+            return;
+        }
+    };
+    let Ok(probe) = serde_json::from_slice::<Probe>(&output.stdout) else {
+        return;
+    };
+    if let Some(stream) = probe.streams.into_iter().next() {
+        meta.width = stream.width;
+        meta.height = stream.height;
+        meta.codec = stream.codec_name;
+    }
+    meta.duration = probe
+        .format
+        .and_then(|format| format.duration)
+        .and_then(|duration| duration.parse().ok());
+}
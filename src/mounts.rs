@@ -0,0 +1,74 @@
+use crate::FileRow;
+use std::path::{Path, PathBuf};
+
+// --folder NAME=PATH, folders on different paths shown as the top-level entries of the root
+// listing, which is no folder on disk then
+#[derive(Default)]
+pub struct Mounts {
+    mounts: Vec<(String, PathBuf)>,
+}
+
+impl Mounts {
+    // none when the values are a single plain folder, the classic root
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a String>) -> Result<Option<Self>, String> {
+        let values: Vec<&String> = values.into_iter().collect();
+        if values.len() < 2 && values.iter().all(|value| !value.contains('=')) {
+            return Ok(None);
+        }
+        let mut mounts: Vec<(String, PathBuf)> = Vec::new();
+        for value in values {
+            let (name, path) = value
+                .split_once('=')
+                .ok_or_else(|| format!("{} has no name, use NAME=PATH for every folder", value))?;
+            let name = name.trim_matches('/');
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return Err(format!("invalid folder name {}", name));
+            }
+            if mounts.iter().any(|(other, _)| other == name) {
+                return Err(format!("folder name {} is used twice", name));
+            }
+            let path = std::env::current_dir()
+                .map_err(|e| e.to_string())?
+                .join(path);
+            if !path.is_dir() {
+                return Err(format!("{} is not a folder", path.display()));
+            }
+            mounts.push((name.to_string(), path));
+        }
+        Ok(Some(Mounts { mounts }))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+        self.mounts
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_path()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.iter()
+            .find(|(mount, _)| *mount == name)
+            .map(|(_, path)| path)
+    }
+
+    // the folder of the mount a path below the root is in and the path below that folder
+    pub fn split<'a>(&self, path: &'a str) -> Option<(&Path, &'a str)> {
+        let path = path.trim_matches('/');
+        let (name, below) = path.split_once('/').unwrap_or((path, ""));
+        self.get(name).map(|folder| (folder, below))
+    }
+
+    // the entries of the root listing, one folder per mount
+    pub fn rows(&self) -> Vec<FileRow> {
+        self.mounts
+            .iter()
+            .map(|(name, path)| FileRow {
+                name: name.clone(),
+                size: 0,
+                modified: path.metadata().and_then(|meta| meta.modified()).ok(),
+                is_dir: true,
+                hidden: false,
+                link: false,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{access::StreamGuard, guarded_body, FileRow};
+use axum::{body::Body, http::StatusCode};
+use bytes::Bytes;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
+use tokio_util::io::ReaderStream;
+
+// --archive FILE, the entries of a ZIP or TAR file served as the root folder without
+// extracting them. the table of entries is read once at startup, a download reads its entry
+// straight from the file
+pub struct Packed {
+    file: PathBuf,
+    format: Format,
+    // by their path inside the archive, folders without an entry of their own included
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Zip,
+    Tar,
+}
+
+struct Entry {
+    size: u64,
+    modified: Option<SystemTime>,
+    is_dir: bool,
+    // index of a zip entry, offset of the data of a tar entry
+    at: u64,
+}
+
+impl Entry {
+    fn row(&self, name: &str) -> FileRow {
+        FileRow {
+            name: name.to_string(),
+            size: if self.is_dir { 0 } else { self.size },
+            modified: self.modified,
+            is_dir: self.is_dir,
+            hidden: false,
+            link: false,
+        }
+    }
+}
+
+impl Packed {
+    // reads the table of entries, blocking so it runs before the server starts
+    pub fn open(file: &Path) -> io::Result<Self> {
+        let name = file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let format = if name.ends_with(".zip") {
+            Format::Zip
+        } else if name.ends_with(".tar") {
+            Format::Tar
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only .zip and .tar files can be served",
+            ));
+        };
+        let mut packed = Packed {
+            file: file.to_path_buf(),
+            format,
+            entries: BTreeMap::new(),
+        };
+        match format {
+            Format::Zip => packed.read_zip()?,
+            Format::Tar => packed.read_tar()?,
+        }
+        Ok(packed)
+    }
+
+    fn read_zip(&mut self) -> io::Result<()> {
+        let mut zip = zip::ZipArchive::new(File::open(&self.file)?).map_err(io::Error::other)?;
+        for index in 0..zip.len() {
+            let entry = zip.by_index_raw(index).map_err(io::Error::other)?;
+            let Some(path) = entry.enclosed_name() else {
+                log::warn!(
+                    "[PACKED] skipping {}, it leaves the archive",
+                    entry.name().unwrap_or_default()
+                );
+                continue;
+            };
+            let modified = entry
+                .last_modified()
+                .and_then(|time| NaiveDateTime::try_from(time).ok())
+                .and_then(|time| Local.from_local_datetime(&time).single())
+                .map(SystemTime::from);
+            self.insert(
+                &path,
+                Entry {
+                    size: entry.size(),
+                    modified,
+                    is_dir: entry.is_dir(),
+                    at: index as u64,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn read_tar(&mut self) -> io::Result<()> {
+        let mut tar = tar::Archive::new(File::open(&self.file)?);
+        for entry in tar.entries_with_seek()? {
+            let entry = entry?;
+            let kind = entry.header().entry_type();
+            // links, devices and the like have no content to serve
+            if !kind.is_file() && !kind.is_dir() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            self.insert(
+                &path,
+                Entry {
+                    size: entry.size(),
+                    modified: entry
+                        .header()
+                        .mtime()
+                        .ok()
+                        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                    is_dir: kind.is_dir(),
+                    at: entry.raw_file_position(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // adds the entry and the folders it is in, names that would leave the archive are skipped
+    fn insert(&mut self, path: &Path, entry: Entry) {
+        let mut parts = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => match part.to_str() {
+                    Some(part) => parts.push(part),
+                    None => return,
+                },
+                Component::CurDir | Component::RootDir => {}
+                Component::ParentDir | Component::Prefix(_) => return,
+            }
+        }
+        if parts.is_empty() {
+            return;
+        }
+        for end in 1..parts.len() {
+            self.entries.entry(parts[..end].join("/")).or_insert(Entry {
+                size: 0,
+                modified: None,
+                is_dir: true,
+                at: 0,
+            });
+        }
+        self.entries.insert(parts.join("/"), entry);
+    }
+
+    // whether the path is a folder of the archive, none when there is no such entry
+    pub fn is_dir(&self, path: &str) -> Option<bool> {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return Some(true);
+        }
+        self.entries.get(path).map(|entry| entry.is_dir)
+    }
+
+    // the entries of a folder, none when it is no folder of the archive
+    pub fn rows(&self, dir: &str) -> Option<Vec<FileRow>> {
+        let rows = self.rows_below(dir)?;
+        Some(
+            rows.into_iter()
+                .filter(|row| !row.name.contains('/'))
+                .collect(),
+        )
+    }
+
+    // every entry below a folder, named by its path below it
+    pub fn rows_below(&self, dir: &str) -> Option<Vec<FileRow>> {
+        if self.is_dir(dir) != Some(true) {
+            return None;
+        }
+        let dir = dir.trim_matches('/');
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir)
+        };
+        let rows = self
+            .entries
+            .range(prefix.clone()..)
+            .take_while(|(path, _)| path.starts_with(&prefix))
+            .map(|(path, entry)| entry.row(&path[prefix.len()..]))
+            .collect();
+        Some(rows)
+    }
+
+    // the entry at the path, named by its file name
+    pub fn row(&self, path: &str) -> Option<FileRow> {
+        let path = path.trim_matches('/');
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.entries.get(path).map(|entry| entry.row(name))
+    }
+
+    // the content of a file entry, read from the archive as it is sent
+    pub async fn body(
+        &self,
+        path: &str,
+        guard: Option<StreamGuard>,
+    ) -> Result<Body, (StatusCode, String)> {
+        let entry = self
+            .entries
+            .get(path.trim_matches('/'))
+            .filter(|entry| !entry.is_dir)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, "File not found".to_string()))?;
+        let failed = |err: io::Error| {
+            log::error!("cannot read {} from {}\n{}", path, self.file.display(), err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Cannot open desired file.".to_string(),
+            )
+        };
+
+        let body = match self.format {
+            Format::Tar => {
+                let mut file = tokio::fs::File::open(&self.file).await.map_err(failed)?;
+                file.seek(io::SeekFrom::Start(entry.at))
+                    .await
+                    .map_err(failed)?;
+                guarded_body(ReaderStream::new(file.take(entry.size)), guard)
+            }
+            // zip entries are mostly compressed, they are inflated on the blocking pool
+            Format::Zip => {
+                let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(4);
+                let (file, index) = (self.file.clone(), entry.at as usize);
+                tokio::task::spawn_blocking(move || {
+                    if let Err(err) = send_zip_entry(&file, index, &tx) {
+                        let _ = tx.blocking_send(Err(err));
+                    }
+                });
+                let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+                    rx.recv().await.map(|chunk| (chunk, rx))
+                });
+                guarded_body(stream, guard)
+            }
+        };
+        Ok(body)
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+}
+
+fn send_zip_entry(
+    file: &Path,
+    index: usize,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<()> {
+    let mut zip = zip::ZipArchive::new(File::open(file)?).map_err(io::Error::other)?;
+    let mut entry = zip.by_index(index).map_err(io::Error::other)?;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = entry.read(&mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        // the client went away
+        if tx
+            .blocking_send(Ok(Bytes::copy_from_slice(&buf[..read])))
+            .is_err()
+        {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{base_path, errors::error_response, utils};
+use axum::{
+    extract::{Path as AxumPath, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use qrcode::{render::svg, QrCode};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+// scheme, address and port of the link printed at startup, the one other devices on the network
+// can open. the QR codes of the pages start with it
+static ORIGIN: OnceLock<String> = OnceLock::new();
+
+pub fn set_origin(origin: String) {
+    let _ = ORIGIN.set(origin);
+}
+
+#[derive(Deserialize)]
+pub struct QrParams {
+    // the folder below the root, the root itself when empty
+    #[serde(default)]
+    path: String,
+}
+
+// /qr.svg?path=<folder>, the link of the server or of one of its folders as a QR code, to open
+// it on a phone from the page
+pub async fn folder(headers: HeaderMap, Query(params): Query<QrParams>) -> Response {
+    // Security check: prevent directory traversal attacks
+    if params.path.contains("..") || params.path.starts_with('/') || params.path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid folder path");
+    }
+    let path = if params.path.is_empty() {
+        base_path::link("/")
+    } else {
+        base_path::link(&format!("/browse/{}", utils::encode_path(&params.path)))
+    };
+    code(&format!("{}{}", origin(&headers), path))
+}
+
+// /qr/file/<path>, the download link of a file as a QR code, scanning it downloads the file
+// without going through the folders on a small screen
+pub async fn file(headers: HeaderMap, AxumPath(path): AxumPath<String>) -> Response {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    let path = base_path::link(&format!("/download/{}", utils::encode_path(&path)));
+    code(&format!("{}{}", origin(&headers), path))
+}
+
+// the printed link, without one (a Unix socket behind a proxy) the host the browser asked
+fn origin(headers: &HeaderMap) -> String {
+    if let Some(origin) = ORIGIN.get() {
+        return origin.clone();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    format!("http://{}", host)
+}
+
+fn code(link: &str) -> Response {
+    let Ok(code) = QrCode::new(link) else {
+        return error_response(
+            StatusCode::URI_TOO_LONG,
+            "The link is too long for a QR code",
+        );
+    };
+    let image = code.render::<svg::Color>().min_dimensions(240, 240).build();
+    let mut res = image.into_response();
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("image/svg+xml"),
+    );
+    res
+}
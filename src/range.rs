@@ -0,0 +1,84 @@
+use crate::errors::error_response;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+
+// the part of a file asked for with Range: bytes=..., players fetch videos a piece at a time
+// and seek by asking for another one
+pub enum Requested {
+    Whole,
+    // first and last byte, both included
+    Part(u64, u64),
+    // the range starts past the end of the file
+    Unsatisfiable,
+}
+
+// a missing or malformed header and several ranges at once get the whole file
+pub fn requested(headers: &HeaderMap, len: u64) -> Requested {
+    let Some(spec) = headers
+        .get(header::RANGE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().strip_prefix("bytes="))
+    else {
+        return Requested::Whole;
+    };
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return Requested::Whole;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    // bytes=-500, the last 500 bytes
+    if start.is_empty() {
+        return match end.parse::<u64>() {
+            Ok(0) => Requested::Unsatisfiable,
+            Ok(_) if len == 0 => Requested::Unsatisfiable,
+            Ok(suffix) => Requested::Part(len.saturating_sub(suffix), len - 1),
+            Err(_) => Requested::Whole,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return Requested::Whole;
+    };
+    // bytes=500- runs to the end, bytes=500-999 is cut to the size of the file
+    let end = if end.is_empty() {
+        None
+    } else {
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => Some(end),
+            _ => return Requested::Whole,
+        }
+    };
+    if start >= len {
+        return Requested::Unsatisfiable;
+    }
+    Requested::Part(start, end.map_or(len - 1, |end| end.min(len - 1)))
+}
+
+// marks the response as sending bytes `start` to `end` of a file of `len` bytes
+pub fn partial(res: &mut Response, start: u64, end: u64, len: u64) {
+    *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+    let headers = res.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)) {
+        headers.insert(header::CONTENT_RANGE, value);
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(end - start + 1));
+}
+
+// the answer to a range past the end, it tells the size of the file
+pub fn unsatisfiable(len: u64) -> Response {
+    let mut res = error_response(
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        "The requested range is past the end of the file",
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+        res.headers_mut().insert(header::CONTENT_RANGE, value);
+    }
+    res
+}
+
+// tells clients they can ask for parts of the file
+pub fn accept(res: &mut Response) {
+    res.headers_mut()
+        .insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+}
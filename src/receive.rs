@@ -0,0 +1,132 @@
+use crate::{
+    auth::User,
+    base_path,
+    errors::{self, error_response},
+    templates::{self, Theme},
+    upload, AppState,
+};
+use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit, Multipart, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::SocketAddr,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// uploads into the drop box never clash with a file already there, and all files in the folder
+// together stay below the quota
+pub struct DropBox {
+    quota: Option<u64>,
+    used: AtomicU64,
+}
+
+impl DropBox {
+    // counts what the folder already holds, blocking so it runs before the server starts
+    pub fn new(dir: &Path, quota: Option<u64>) -> io::Result<Self> {
+        Ok(DropBox {
+            quota,
+            used: AtomicU64::new(folder_size(dir)?),
+        })
+    }
+
+    // takes bytes of the quota for an upload, false when the drop box would get too full
+    pub fn reserve(&self, bytes: u64) -> bool {
+        let Some(quota) = self.quota else {
+            return true;
+        };
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(bytes).filter(|total| *total <= quota)
+            })
+            .is_ok()
+    }
+
+    // gives back the bytes of a failed upload
+    pub fn release(&self, bytes: u64) {
+        if self.quota.is_some() {
+            self.used.fetch_sub(bytes, Ordering::SeqCst);
+        }
+    }
+}
+
+fn folder_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        size += if meta.is_dir() {
+            folder_size(&entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(size)
+}
+
+#[derive(Deserialize)]
+pub struct ReceiveParams {
+    received: Option<String>,
+}
+
+// file-serve receive <DIR>, a drop box: files can be sent into the folder but it is never
+// listed and nothing can be downloaded
+pub fn routes() -> Router<AppState> {
+    // uploads are streamed to disk, no need for the default body limit
+    Router::new()
+        .route(
+            "/",
+            get(drop_box)
+                .post(receive)
+                .layer(DefaultBodyLimit::disable()),
+        )
+        .fallback(errors::not_found)
+}
+
+// fields of the drop box page
+#[derive(Serialize)]
+struct ReceivePage {
+    received: bool,
+    // where the form posts the files
+    action: String,
+}
+
+async fn drop_box(headers: HeaderMap, Query(params): Query<ReceiveParams>) -> Response {
+    page(&headers, params.received.is_some(), base_path::link("/"))
+}
+
+// the form of the drop box, also shown by the upload links of a served folder
+pub fn page(headers: &HeaderMap, received: bool, action: String) -> Response {
+    let page = ReceivePage { received, action };
+    match templates::render("receive.html", Theme::of(headers), &page) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            log::error!("Error loading receive template: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load receive template",
+            )
+        }
+    }
+}
+
+// stores the files like the upload form of a listing, then comes back to the form
+async fn receive(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Response {
+    let res = upload::upload_files(state, addr, user, headers, None, multipart).await;
+    if res.status().is_redirection() {
+        return Redirect::to(&base_path::link("/?received=1")).into_response();
+    }
+    res
+}
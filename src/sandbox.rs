@@ -0,0 +1,105 @@
+use std::path::Path;
+
+// paths outside the share that still have to be readable after the restriction
+// (timezone data used by chrono when formatting modification dates and the resolver
+// configuration used by outgoing connections, e.g. to the OpenID provider)
+#[cfg(any(target_os = "linux", target_os = "openbsd"))]
+const SYSTEM_READ_PATHS: [&str; 6] = [
+    "/etc/localtime",
+    "/usr/share/zoneinfo",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/nsswitch.conf",
+    "/etc/gai.conf",
+];
+
+// restricts the whole process to read only inside the given paths and to write only inside
+// the write paths, landlock based
+#[cfg(target_os = "linux")]
+pub fn restrict(
+    read_paths: &[&Path],
+    write_paths: &[&Path],
+) -> Result<(), Box<dyn std::error::Error>> {
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr,
+        RulesetStatus, ABI,
+    };
+
+    let abi = ABI::V2;
+    let system_paths = SYSTEM_READ_PATHS.iter().map(Path::new);
+
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .create()?
+        .add_rules(path_beneath_rules(
+            read_paths.iter().copied().chain(system_paths),
+            AccessFs::from_read(abi),
+        ))?
+        .add_rules(path_beneath_rules(
+            write_paths.iter().copied(),
+            AccessFs::from_read(abi) | AccessFs::from_write(abi),
+        ))?
+        .restrict_self()?;
+
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => Ok(()),
+        RulesetStatus::PartiallyEnforced => {
+            log::warn!("Landlock sandbox only partially enforced by the running kernel.");
+            Ok(())
+        }
+        RulesetStatus::NotEnforced => Err("Landlock is not supported by the running kernel".into()),
+    }
+}
+
+// restricts the whole process to read only inside the given paths and to write only inside
+// the write paths, unveil/pledge based
+#[cfg(target_os = "openbsd")]
+pub fn restrict(
+    read_paths: &[&Path],
+    write_paths: &[&Path],
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let read = CString::new("r")?;
+    let write = CString::new("rwc")?;
+    let system_paths = SYSTEM_READ_PATHS.iter().map(Path::new);
+    let rules = read_paths
+        .iter()
+        .copied()
+        .chain(system_paths)
+        .map(|path| (path, &read))
+        .chain(write_paths.iter().map(|path| (*path, &write)));
+    for (path, permissions) in rules {
+        if !path.exists() {
+            continue;
+        }
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::unveil(c_path.as_ptr(), permissions.as_ptr()) } != 0 {
+            return Err(format!("unveil failed for {}", path.display()).into());
+        }
+    }
+    // lock the unveil list
+    if unsafe { libc::unveil(std::ptr::null(), std::ptr::null()) } != 0 {
+        return Err("Failed to lock unveil".into());
+    }
+
+    // mcast for the mDNS announcement, which joins the group on new interfaces
+    let promises = if write_paths.is_empty() {
+        CString::new("stdio rpath inet mcast")?
+    } else {
+        CString::new("stdio rpath wpath cpath inet mcast")?
+    };
+    if unsafe { libc::pledge(promises.as_ptr(), std::ptr::null()) } != 0 {
+        return Err("pledge failed".into());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "openbsd")))]
+pub fn restrict(
+    _read_paths: &[&Path],
+    _write_paths: &[&Path],
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Filesystem sandboxing is not supported on this platform".into())
+}
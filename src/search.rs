@@ -0,0 +1,205 @@
+use crate::{
+    auth::User, base_path, errors::error_response, index_page, listing, read_rows, safe_dir,
+    stream_page, templates::Theme, utils, visibility::Anonymous, AppState, FileRow, LinkStyle,
+    Located, SearchForm,
+};
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Extension,
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashSet, VecDeque},
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+// a search stops at whichever limit comes first, the page then asks for a narrower one
+const MAX_RESULTS: usize = 500;
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+    // folder to search, below the root
+    #[serde(default)]
+    path: String,
+}
+
+// /search?q=...&path=..., the entries below the folder whose name contains q, ignoring case
+pub async fn search(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParams>,
+) -> Response {
+    let query = params.q.trim().to_string();
+    let folder = params.path.trim_matches('/').to_string();
+    log::info!(
+        "[SEARCH] Client: {} | User: {} | {:?} in /{}",
+        addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
+        query,
+        folder
+    );
+
+    if query.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "Enter a name to search for");
+    }
+    // Security check: prevent directory traversal attacks
+    if folder.contains("..") || folder.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid folder path");
+    }
+    if !state.within_depth(&folder, 0) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Folder is deeper than the allowed browse depth",
+        );
+    }
+    if state.exclude.matches(&folder) {
+        return error_response(StatusCode::NOT_FOUND, "Folder not found");
+    }
+    let lowercase = query.to_lowercase();
+    let anonymous = anonymous.is_some();
+    // the folders to walk, each with the root it is below and its path below the one searched
+    let (matches, queue) = match state.locate(&headers, user.as_deref(), &folder) {
+        Ok(Located::Disk(root, below)) => match safe_dir(root, &root.join(below)) {
+            Ok(dir) => (
+                Vec::new(),
+                VecDeque::from([(root.to_path_buf(), dir, String::new())]),
+            ),
+            Err((status, msg)) => return error_response(status, &msg),
+        },
+        // the mounts are walked like the folders of a root
+        Ok(Located::Mounts(mounts)) => {
+            let mut rows = mounts.rows();
+            state.shared_rows(&mut rows, "", anonymous);
+            let queue = rows
+                .iter()
+                .filter_map(|row| {
+                    let path = mounts.get(&row.name)?;
+                    Some((
+                        path.to_path_buf(),
+                        path.canonicalize().ok()?,
+                        row.name.clone(),
+                    ))
+                })
+                .collect();
+            rows.retain(|row| row.name.to_lowercase().contains(&lowercase));
+            (rows, queue)
+        }
+        // the whole table of the archive is known, nothing left to walk
+        Ok(Located::Packed(packed, below)) => {
+            let Some(mut rows) = packed.rows_below(below) else {
+                return error_response(StatusCode::NOT_FOUND, "Folder not found");
+            };
+            state.shared_rows(&mut rows, &folder, anonymous);
+            rows.retain(|row| {
+                let name = row.name.rsplit('/').next().unwrap_or_default();
+                name.to_lowercase().contains(&lowercase)
+            });
+            rows.truncate(MAX_RESULTS);
+            (rows, VecDeque::new())
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+
+    let found = {
+        let state = state.clone();
+        let folder = folder.clone();
+        tokio::task::spawn_blocking(move || {
+            find(&state, matches, queue, &folder, &lowercase, anonymous)
+        })
+        .await
+    };
+    let Ok((rows, truncated)) = found else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Search failed");
+    };
+
+    let count = rows.len();
+    listing::respond(listing::format(&headers), rows, &folder, None, |rows| {
+        let mut page = index_page(
+            rows,
+            &folder,
+            LinkStyle::Server,
+            false,
+            state.shares.is_some(),
+            None,
+        );
+        // the results are no folder of their own, back leads to the one searched
+        page.back = Some(if folder.is_empty() {
+            base_path::link("/")
+        } else {
+            base_path::link(&format!("/browse/{}", utils::encode_path(&folder)))
+        });
+        page.selection_action = None;
+        page.search = Some(SearchForm {
+            path: folder.clone(),
+            query: query.clone(),
+            results: true,
+            truncated,
+            count,
+        });
+        stream_page(page, Theme::of(&headers))
+    })
+}
+
+// walks the folder breadth first, so the matches closest to it come first. the rows are named
+// by their path below the folder and only hold what the listings would show, links are
+// followed like in the listings. also returns whether a limit stopped the walk
+fn find(
+    state: &AppState,
+    mut found: Vec<FileRow>,
+    mut queue: VecDeque<(PathBuf, PathBuf, String)>,
+    folder: &str,
+    query: &str,
+    anonymous: bool,
+) -> (Vec<FileRow>, bool) {
+    let deadline = Instant::now() + TIME_LIMIT;
+    // folders reached again through a link are searched once
+    let mut seen: HashSet<PathBuf> = queue.iter().map(|(_, dir, _)| dir.clone()).collect();
+
+    while let Some((root, dir, below)) = queue.pop_front() {
+        if Instant::now() > deadline {
+            return (found, true);
+        }
+        // unreadable folders are skipped, the search goes on with the others
+        let Ok(mut rows) = read_rows(&root, &dir) else {
+            continue;
+        };
+        let current_path = match (folder.is_empty(), below.is_empty()) {
+            (true, _) => below.clone(),
+            (false, true) => folder.to_string(),
+            (false, false) => format!("{}/{}", folder, below),
+        };
+        state.shared_rows(&mut rows, &current_path, anonymous);
+
+        for row in rows {
+            let name = if below.is_empty() {
+                row.name.clone()
+            } else {
+                format!("{}/{}", below, row.name)
+            };
+            if row.is_dir
+                && let Ok(sub) = dir.join(&row.name).canonicalize()
+                && seen.insert(sub.clone())
+            {
+                queue.push_back((root.clone(), sub, name.clone()));
+            }
+            if row.name.to_lowercase().contains(query) {
+                if found.len() == MAX_RESULTS {
+                    return (found, true);
+                }
+                found.push(FileRow { name, ..row });
+            }
+        }
+    }
+    (found, false)
+}
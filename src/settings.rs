@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// what can change while the server runs, from the /admin panel. the flags only give the
+// values it starts with
+pub struct Settings {
+    // POST /upload, --allow-upload
+    upload: AtomicBool,
+    // PUT, MKCOL, DELETE, MOVE and COPY on /dav, --allow-write
+    write: bool,
+    // the trash button of the listing, --allow-delete
+    delete: bool,
+    // nothing is written into the served folders, whatever the ones above allow
+    read_only: AtomicBool,
+}
+
+impl Settings {
+    pub fn new(upload: bool, write: bool, delete: bool) -> Self {
+        Settings {
+            upload: AtomicBool::new(upload),
+            write,
+            delete,
+            read_only: AtomicBool::new(false),
+        }
+    }
+
+    pub fn upload(&self) -> bool {
+        self.upload.load(Ordering::Relaxed) && !self.read_only()
+    }
+
+    pub fn write(&self) -> bool {
+        self.write && !self.read_only()
+    }
+
+    pub fn delete(&self) -> bool {
+        self.delete && !self.read_only()
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    // whether uploads are turned on, read-only mode aside
+    pub fn upload_allowed(&self) -> bool {
+        self.upload.load(Ordering::Relaxed)
+    }
+
+    pub fn write_allowed(&self) -> bool {
+        self.write
+    }
+
+    pub fn delete_allowed(&self) -> bool {
+        self.delete
+    }
+
+    pub fn set_upload(&self, upload: bool) {
+        self.upload.store(upload, Ordering::Relaxed);
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+}
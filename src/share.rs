@@ -0,0 +1,809 @@
+use crate::{
+    auth::User,
+    base_path, download_file,
+    errors::error_response,
+    filter, read_rows, receive, render_index, safe_dir,
+    templates::{self, Theme},
+    upload, when_sent, AppState, LinkStyle, Located,
+};
+use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit, Multipart, Path as AxumPath, Query, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Extension, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Local};
+use hmac::{Hmac, KeyInit, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub const DEFAULT_TTL: &str = "1d";
+
+// links to a single file or folder, /s/<token>, and links to send files into a folder,
+// /u/<token>. the token carries the path, the --host name of its root, the expiry and the
+// download or upload limit signed with the server secret, only the counts are kept
+pub struct ShareLinks {
+    secret: Vec<u8>,
+    // downloads per token signature of links with a download limit, uploads of upload links
+    downloads: Mutex<HashMap<String, Downloads>>,
+    // links handed out since the start, listed on /admin until they expire
+    issued: Mutex<Vec<Issued>>,
+    // token signatures revoked on /admin and when they would have expired
+    revoked: Mutex<HashMap<String, u64>>,
+}
+
+// what a token opens, the kinds are signed apart so a share cannot be used to upload
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Share,
+    Upload,
+}
+
+impl Kind {
+    fn scope(self) -> &'static [u8] {
+        match self {
+            Kind::Share => b"share\n",
+            Kind::Upload => b"upload\n",
+        }
+    }
+
+    fn route(self) -> &'static str {
+        match self {
+            Kind::Share => "/s/",
+            Kind::Upload => "/u/",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Share => "share link",
+            Kind::Upload => "upload link",
+        }
+    }
+}
+
+// a link handed out by this server
+#[derive(Clone)]
+pub struct Issued {
+    pub kind: Kind,
+    pub path: String,
+    pub link: String,
+    pub expires: u64,
+    // downloads of a share, uploads of an upload link
+    pub max_downloads: Option<u32>,
+    id: String,
+}
+
+struct Downloads {
+    // dropped once the link expired
+    expires: u64,
+    completed: u32,
+    // running transfers hold a slot so a one-time link cannot be fetched twice at once
+    running: u32,
+}
+
+// slot of a running download, counted as a download only when the whole file was sent. an
+// upload link takes one for every upload, counted once the files were stored
+struct DownloadSlot {
+    shares: Arc<ShareLinks>,
+    id: String,
+    completed: bool,
+}
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        let mut downloads = self.shares.downloads.lock().unwrap();
+        if let Some(entry) = downloads.get_mut(&self.id) {
+            entry.running -= 1;
+            if self.completed {
+                entry.completed += 1;
+            }
+        }
+    }
+}
+
+// a verified share, path is relative to the served folder
+pub struct Share {
+    // the --host name whose root the path is below, empty for the default root
+    host: String,
+    path: String,
+    expires: u64,
+    max_downloads: Option<u32>,
+    id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ShareParams {
+    ttl: Option<String>,
+    max: Option<u32>,
+    once: Option<String>,
+}
+
+// fields of the page showing a new link
+#[derive(Serialize)]
+struct SharePage<'a> {
+    name: &'a str,
+    link: &'a str,
+    expires: &'a str,
+    limit: &'a str,
+    ttl: &'a str,
+    max: Option<u32>,
+    once: bool,
+    // an upload link, the limit counts uploads
+    upload: bool,
+    action: String,
+}
+
+impl ShareLinks {
+    pub fn new(secret: &str) -> Self {
+        ShareLinks {
+            secret: secret.as_bytes().to_vec(),
+            downloads: Mutex::new(HashMap::new()),
+            issued: Mutex::new(Vec::new()),
+            revoked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn mac(&self, kind: Kind, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        // keeps share tokens apart from signed download links and from each other
+        mac.update(kind.scope());
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    // /s/<token> for the path relative to the served folder, /u/<token> for an upload link into
+    // the folder. host is the --host name the folder was picked by, the link opens the same
+    // root whatever name it is reached by. max_downloads of None is unlimited
+    pub fn mint(
+        &self,
+        kind: Kind,
+        host: Option<&str>,
+        path: &str,
+        ttl: Duration,
+        max_downloads: Option<u32>,
+    ) -> String {
+        let expires = unix_now() + ttl.as_secs();
+        let payload = format!(
+            "{}\n{}\n{}\n{}",
+            expires,
+            max_downloads.unwrap_or(0),
+            host.unwrap_or_default(),
+            path.trim_matches('/')
+        );
+        let sig = URL_SAFE_NO_PAD.encode(self.mac(kind, &payload).finalize().into_bytes());
+        let link = base_path::link(&format!(
+            "{}{}.{}",
+            kind.route(),
+            URL_SAFE_NO_PAD.encode(payload),
+            sig
+        ));
+
+        let now = unix_now();
+        let mut issued = self.issued.lock().unwrap();
+        issued.retain(|issued| issued.expires >= now);
+        issued.push(Issued {
+            kind,
+            path: path.trim_matches('/').to_string(),
+            link: link.clone(),
+            expires,
+            max_downloads,
+            id: sig,
+        });
+        link
+    }
+
+    // the share of a token whose signature is valid, expired or not
+    fn verify(&self, kind: Kind, token: &str) -> Option<Share> {
+        let (payload, sig) = token.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|p| String::from_utf8(p).ok())?;
+        let sig = URL_SAFE_NO_PAD.decode(sig).ok()?;
+        self.mac(kind, &payload).verify_slice(&sig).ok()?;
+
+        let mut fields = payload.splitn(4, '\n');
+        let (Some(expires), Some(max), Some(host), Some(path)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return None;
+        };
+        let max = max.parse::<u32>().ok()?;
+        Some(Share {
+            host: host.to_string(),
+            path: path.to_string(),
+            expires: expires.parse::<u64>().ok()?,
+            max_downloads: (max > 0).then_some(max),
+            id: URL_SAFE_NO_PAD.encode(sig),
+        })
+    }
+
+    pub fn open(&self, kind: Kind, token: &str) -> Result<Share, (StatusCode, String)> {
+        let share = self
+            .verify(kind, token)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unknown {}", kind.name())))?;
+        if share.expires < unix_now() {
+            return Err((
+                StatusCode::GONE,
+                format!("This {} has expired", kind.name()),
+            ));
+        }
+        if self.revoked.lock().unwrap().contains_key(&share.id) {
+            return Err((
+                StatusCode::GONE,
+                format!("This {} was revoked", kind.name()),
+            ));
+        }
+        if !self.can_download(&share) {
+            return Err((StatusCode::GONE, used_up(kind)));
+        }
+        Ok(share)
+    }
+
+    fn can_download(&self, share: &Share) -> bool {
+        let Some(max) = share.max_downloads else {
+            return true;
+        };
+        let downloads = self.downloads.lock().unwrap();
+        downloads
+            .get(&share.id)
+            .is_none_or(|entry| entry.completed + entry.running < max)
+    }
+
+    // takes a download slot of a limited link, fails when all downloads are used or running
+    fn start_download(
+        self: &Arc<Self>,
+        kind: Kind,
+        share: &Share,
+    ) -> Result<Option<DownloadSlot>, (StatusCode, String)> {
+        let Some(max) = share.max_downloads else {
+            return Ok(None);
+        };
+        let now = unix_now();
+        let mut downloads = self.downloads.lock().unwrap();
+        downloads.retain(|_, entry| entry.expires >= now || entry.running > 0);
+        let entry = downloads.entry(share.id.clone()).or_insert(Downloads {
+            expires: share.expires,
+            completed: 0,
+            running: 0,
+        });
+        if entry.completed + entry.running >= max {
+            return Err((StatusCode::GONE, used_up(kind)));
+        }
+        entry.running += 1;
+        Ok(Some(DownloadSlot {
+            shares: self.clone(),
+            id: share.id.clone(),
+            completed: false,
+        }))
+    }
+
+    // the links handed out that did not expire yet, the newest first
+    pub fn issued(&self) -> Vec<Issued> {
+        let now = unix_now();
+        let mut issued = self.issued.lock().unwrap();
+        issued.retain(|issued| issued.expires >= now);
+        issued.iter().rev().cloned().collect()
+    }
+
+    pub fn is_revoked(&self, issued: &Issued) -> bool {
+        self.revoked.lock().unwrap().contains_key(&issued.id)
+    }
+
+    // completed downloads of a link with a download limit, uploads of an upload link
+    pub fn downloads(&self, issued: &Issued) -> u32 {
+        self.downloads
+            .lock()
+            .unwrap()
+            .get(&issued.id)
+            .map_or(0, |entry| entry.completed)
+    }
+
+    // stops a link from opening until it would have expired anyway, any link of this server can
+    // be revoked, the ones of an earlier run too. returns the shared path
+    pub fn revoke(&self, link: &str) -> Result<String, String> {
+        let (kinds, token) = match [Kind::Share, Kind::Upload]
+            .into_iter()
+            .find_map(|kind| Some((kind, link.split_once(kind.route())?.1)))
+        {
+            Some((kind, token)) => (vec![kind], token),
+            None => (vec![Kind::Share, Kind::Upload], link),
+        };
+        let token = token.split(['/', '?', '#']).next().unwrap_or_default();
+        let share = kinds
+            .into_iter()
+            .find_map(|kind| self.verify(kind, token.trim()))
+            .ok_or_else(|| "not a share link of this server".to_string())?;
+        let now = unix_now();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.retain(|_, expires| *expires >= now);
+        revoked.insert(share.id, share.expires);
+        Ok(share.path)
+    }
+}
+
+// the request headers with the Host the link was handed out for, the folder is looked up below
+// that root and not the one of the name the client used
+fn pinned_host(mut headers: HeaderMap, share: &Share) -> HeaderMap {
+    match HeaderValue::from_str(&share.host) {
+        Ok(host) if !share.host.is_empty() => {
+            headers.insert(header::HOST, host);
+        }
+        _ => {
+            headers.remove(header::HOST);
+        }
+    }
+    headers
+}
+
+fn used_up(kind: Kind) -> String {
+    match kind {
+        Kind::Share => "This share link has no downloads left".to_string(),
+        Kind::Upload => "This upload link was already used".to_string(),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// the links themselves, outside the login: the token is checked by the handlers
+pub fn routes(upload: bool) -> Router<AppState> {
+    let app = Router::new()
+        .route("/s/{token}", get(open_share))
+        .route("/s/{token}/{*path}", get(open_share_path));
+    if !upload {
+        return app;
+    }
+    // uploads are streamed to disk, no need for the default body limit
+    app.route(
+        "/u/{token}",
+        get(upload_form)
+            .post(upload_files)
+            .layer(DefaultBodyLimit::disable()),
+    )
+}
+
+pub async fn open_share(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    AxumPath(token): AxumPath<String>,
+) -> Response {
+    open(state, addr, method, headers, token, String::new()).await
+}
+
+pub async fn open_share_path(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    AxumPath((token, path)): AxumPath<(String, String)>,
+) -> Response {
+    open(state, addr, method, headers, token, path).await
+}
+
+// lists a shared folder or sends a shared file, path is relative to the share
+async fn open(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    token: String,
+    path: String,
+) -> Response {
+    let Some(shares) = state.shares.clone() else {
+        return error_response(StatusCode::NOT_FOUND, "Sharing is not enabled");
+    };
+    let share = match shares.open(Kind::Share, &token) {
+        Ok(share) => share,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let headers = pinned_host(headers, &share);
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+
+    let full_path = [share.path.as_str(), path.trim_matches('/')]
+        .iter()
+        .filter(|p| !p.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("/");
+    if state.exclude.matches(&full_path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let (root, below) = match state.locate(&headers, None, &full_path) {
+        Ok(Located::Disk(root, below)) => (root, below),
+        Ok(Located::Mounts(_)) | Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::NOT_FOUND, "File not found")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let target = root.join(below);
+
+    if target.is_dir() {
+        if !state.within_depth(&full_path, 0) {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "Folder is deeper than the allowed browse depth",
+            );
+        }
+        let dir = match safe_dir(root, &target) {
+            Ok(dir) => dir,
+            Err((status, msg)) => return error_response(status, &msg),
+        };
+        log::info!("[SHARE] Client: {} | list {}", addr, dir.display());
+
+        let root = root.to_path_buf();
+        let rows = tokio::task::spawn_blocking(move || read_rows(&root, &dir))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        let mut rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                let msg = format!("Failed to read directory: {}", e);
+                return error_response(crate::errors::io_error_status(&e), &msg);
+            }
+        };
+        state.shared_rows(&mut rows, &full_path, false);
+        let prefix = base_path::link(&format!("/s/{}", token));
+        return Html(render_index(
+            rows,
+            path.trim_matches('/'),
+            LinkStyle::Share(&prefix),
+            false,
+            false,
+            None,
+            Theme::of(&headers),
+        ))
+        .into_response();
+    }
+
+    let slot = match shares.start_download(Kind::Share, &share) {
+        Ok(slot) => slot,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    log::info!("[SHARE] Client: {} | download {}", addr, target.display());
+    let head = method == Method::HEAD;
+    let res = download_file(
+        State(state),
+        ConnectInfo(addr),
+        None,
+        method,
+        headers,
+        AxumPath(full_path),
+    )
+    .await;
+    match slot {
+        Some(slot) if completes_download(&res) && !head => count_when_sent(res, slot, addr),
+        // a failed download, a HEAD or a range before the end frees its slot right away
+        _ => res,
+    }
+}
+
+// the whole file, or the range up to its last byte a resumed download ends with
+fn completes_download(res: &Response) -> bool {
+    match res.status() {
+        StatusCode::OK => true,
+        StatusCode::PARTIAL_CONTENT => res
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("bytes "))
+            .and_then(|v| {
+                let (range, total) = v.split_once('/')?;
+                let (_, end) = range.split_once('-')?;
+                Some(end.parse::<u64>().ok()? + 1 == total.parse::<u64>().ok()?)
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+// marks the slot completed once the body reached its end, an aborted transfer does not count
+fn count_when_sent(res: Response, slot: DownloadSlot, addr: SocketAddr) -> Response {
+    when_sent(res, move || {
+        // the whole slot moves into the closure, dropping it unsent frees it uncounted
+        let mut slot = slot;
+        log::info!("[SHARE] Client: {} | download completed", addr);
+        slot.completed = true;
+    })
+}
+
+// page with a new share link for the file or folder, ?ttl=7d&max=3 change the limits
+pub async fn share_page(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+    params: Query<ShareParams>,
+) -> Response {
+    link_page(Kind::Share, state, addr, user, headers, path, params)
+}
+
+// page with a new upload link into the folder, good for a single upload unless ?max= says
+// otherwise
+pub async fn upload_link_page(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+    params: Query<ShareParams>,
+) -> Response {
+    let path = path.map_or(String::new(), |AxumPath(path)| path);
+    link_page(Kind::Upload, state, addr, user, headers, path, params)
+}
+
+fn link_page(
+    kind: Kind,
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    path: String,
+    Query(params): Query<ShareParams>,
+) -> Response {
+    let Some(shares) = state.shares.clone() else {
+        return error_response(StatusCode::NOT_FOUND, "Sharing is not enabled");
+    };
+    if kind == Kind::Upload && !state.settings.upload() {
+        return error_response(StatusCode::FORBIDDEN, "Uploads are turned off");
+    }
+    if kind == Kind::Upload && !state.may_write(user.as_deref()) {
+        return error_response(StatusCode::FORBIDDEN, "Your account cannot upload files");
+    }
+    // shares are opened without a login, so they always point into the shared root
+    if user
+        .as_ref()
+        .is_some_and(|Extension(user)| user.root.is_some())
+    {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Files of a home folder cannot be shared",
+        );
+    }
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+    let exists = match state.locate(&headers, None, &path) {
+        // files are sent into folders only
+        Ok(Located::Disk(root, below)) => match kind {
+            Kind::Share => root.join(below).exists(),
+            Kind::Upload => root.join(below).is_dir(),
+        },
+        // the list of --folder mounts is no folder that could be shared
+        Ok(Located::Mounts(_)) => false,
+        // links into the archive are not handed out
+        Ok(Located::Packed(..)) => false,
+        Err(_) => false,
+    };
+    if state.exclude.matches(&path) || !exists {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+
+    let ttl_text = params.ttl.unwrap_or_else(|| DEFAULT_TTL.to_string());
+    let ttl = match filter::parse_age(&ttl_text) {
+        Ok(ttl) => ttl,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err),
+    };
+    // a one-time link is a link with a single download, an upload link is one unless asked
+    let max_downloads = match (params.once.is_some(), params.max, kind) {
+        (true, _, _) => Some(1),
+        (false, None, Kind::Upload) => Some(1),
+        (false, max, _) => max.filter(|max| *max > 0),
+    };
+    let link = shares.mint(kind, state.host_name(&headers), &path, ttl, max_downloads);
+
+    log::info!(
+        "[SHARE] Client: {} | User: {} | {} {} for {}",
+        addr,
+        user.as_ref()
+            .map_or("-".to_string(), |Extension(user)| user.to_string()),
+        match kind {
+            Kind::Share => "shared",
+            Kind::Upload => "upload link into",
+        },
+        if path.is_empty() { "/" } else { &path },
+        ttl_text
+    );
+
+    let expires = DateTime::<Local>::from(SystemTime::now() + ttl)
+        .format("%Y-%m-%d %H:%M")
+        .to_string();
+    let what = match kind {
+        Kind::Share => "download",
+        Kind::Upload => "upload",
+    };
+    let limit = match max_downloads {
+        Some(1) => format!("one {}", what),
+        Some(max) => format!("{} {}s", max, what),
+        None => format!("unlimited {}s", what),
+    };
+    let encoded: Vec<String> = path
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+        .collect();
+    let action = match kind {
+        Kind::Share => format!("/share/{}", encoded.join("/")),
+        Kind::Upload if encoded.is_empty() => "/upload-link".to_string(),
+        Kind::Upload => format!("/upload-link/{}", encoded.join("/")),
+    };
+
+    let page = SharePage {
+        name: if path.is_empty() { "home" } else { &path },
+        link: &link,
+        expires: &expires,
+        limit: &limit,
+        ttl: &ttl_text,
+        max: max_downloads,
+        once: max_downloads == Some(1),
+        upload: kind == Kind::Upload,
+        action: base_path::link(&action),
+    };
+    match templates::render("share.html", Theme::of(&headers), &page) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            log::error!("Error loading share template: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load share template",
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    received: Option<String>,
+}
+
+// /u/<token>, the form of the drop box for the folder of an upload link
+pub async fn upload_form(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AxumPath(token): AxumPath<String>,
+    Query(params): Query<UploadParams>,
+) -> Response {
+    // the thanks are shown after the last upload of a one-time link as well
+    if params.received.is_none() {
+        let Some(shares) = state.shares.clone() else {
+            return error_response(StatusCode::NOT_FOUND, "Sharing is not enabled");
+        };
+        if let Err((status, msg)) = shares.open(Kind::Upload, &token) {
+            return error_response(status, &msg);
+        }
+    }
+    let action = base_path::link(&format!("/u/{}", token));
+    receive::page(&headers, params.received.is_some(), action)
+}
+
+// stores the files sent with an upload link in its folder, like the upload form of the listing
+pub async fn upload_files(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    AxumPath(token): AxumPath<String>,
+    multipart: Multipart,
+) -> Response {
+    let Some(shares) = state.shares.clone() else {
+        return error_response(StatusCode::NOT_FOUND, "Sharing is not enabled");
+    };
+    let share = match shares.open(Kind::Upload, &token) {
+        Ok(share) => share,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let headers = pinned_host(headers, &share);
+    // a one-time link cannot take two uploads at once
+    let slot = match shares.start_download(Kind::Upload, &share) {
+        Ok(slot) => slot,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    log::info!("[SHARE] Client: {} | upload into /{}", addr, share.path);
+    let folder = (!share.path.is_empty()).then(|| AxumPath(share.path.clone()));
+    let res = upload::store_files(
+        State(state),
+        ConnectInfo(addr),
+        None,
+        headers,
+        folder,
+        multipart,
+    )
+    .await;
+    if !res.status().is_redirection() {
+        return res;
+    }
+    if let Some(mut slot) = slot {
+        slot.completed = true;
+    }
+    Redirect::to(&base_path::link(&format!("/u/{}?received=1", token))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    // the token of a link minted without a --base-path
+    fn token(link: &str, kind: Kind) -> &str {
+        link.strip_prefix(kind.route()).unwrap()
+    }
+
+    #[test]
+    fn share_token_opens_its_path() {
+        let shares = ShareLinks::new("secret");
+        let link = shares.mint(Kind::Share, Some("photos.lan"), "/2024/", DAY, Some(3));
+        let share = shares
+            .open(Kind::Share, token(&link, Kind::Share))
+            .ok()
+            .unwrap();
+        assert_eq!(share.host, "photos.lan");
+        assert_eq!(share.path, "2024");
+        assert_eq!(share.max_downloads, Some(3));
+    }
+
+    #[test]
+    fn share_token_is_not_an_upload_token() {
+        let shares = ShareLinks::new("secret");
+        let link = shares.mint(Kind::Share, None, "inbox", DAY, None);
+        assert!(shares
+            .verify(Kind::Upload, token(&link, Kind::Share))
+            .is_none());
+
+        let link = shares.mint(Kind::Upload, None, "inbox", DAY, None);
+        assert!(shares
+            .verify(Kind::Share, token(&link, Kind::Upload))
+            .is_none());
+        assert!(shares
+            .verify(Kind::Upload, token(&link, Kind::Upload))
+            .is_some());
+    }
+
+    #[test]
+    fn changed_token_fails() {
+        let shares = ShareLinks::new("secret");
+        let link = shares.mint(Kind::Share, None, "public", DAY, None);
+        let (payload, sig) = token(&link, Kind::Share).split_once('.').unwrap();
+
+        let payload = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+        let other = URL_SAFE_NO_PAD.encode(payload.replace("public", "private"));
+        assert!(shares
+            .verify(Kind::Share, &format!("{}.{}", other, sig))
+            .is_none());
+        assert!(shares.verify(Kind::Share, "garbage").is_none());
+        assert!(ShareLinks::new("other")
+            .verify(Kind::Share, token(&link, Kind::Share))
+            .is_none());
+    }
+
+    #[test]
+    fn expired_token_is_gone() {
+        let shares = ShareLinks::new("secret");
+        let payload = format!("{}\n0\n\nold", unix_now() - 1);
+        let sig = URL_SAFE_NO_PAD.encode(shares.mac(Kind::Share, &payload).finalize().into_bytes());
+        let expired = format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), sig);
+        assert!(matches!(
+            shares.open(Kind::Share, &expired),
+            Err((StatusCode::GONE, _))
+        ));
+    }
+}
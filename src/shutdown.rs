@@ -0,0 +1,172 @@
+use crate::{auth_log, errors::error_response, when_sent, AppState};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+use tokio::sync::{oneshot, Notify};
+
+// how long the requests still running may go on once the server stops accepting new ones
+pub const DRAIN: Duration = Duration::from_secs(10);
+
+// stops the server on its own once it is no longer needed, after --max-downloads complete
+// downloads or after --timeout
+pub struct Shutdown {
+    // complete downloads left, none without a limit
+    remaining: Option<AtomicU32>,
+    stop: Notify,
+}
+
+impl Shutdown {
+    pub fn new(max_downloads: Option<u32>) -> Self {
+        Shutdown {
+            remaining: max_downloads.map(AtomicU32::new),
+            stop: Notify::new(),
+        }
+    }
+
+    // whether the last allowed download was sent, requests can still arrive while the server
+    // shuts down
+    pub fn exhausted(&self) -> bool {
+        self.remaining
+            .as_ref()
+            .is_some_and(|remaining| remaining.load(Ordering::SeqCst) == 0)
+    }
+
+    // counts the response as a download once its whole body was sent
+    pub fn counted(self: &Arc<Self>, res: Response) -> Response {
+        if self.remaining.is_none() || !res.status().is_success() {
+            return res;
+        }
+        let shutdown = self.clone();
+        when_sent(res, move || shutdown.count_download())
+    }
+
+    fn count_download(&self) {
+        let Some(remaining) = &self.remaining else {
+            return;
+        };
+        // downloads running in parallel can finish after the last one counted
+        let left = remaining.fetch_sub(1, Ordering::SeqCst).saturating_sub(1);
+        log::info!("[SHUTDOWN] download completed, {} left", left);
+        if left == 0 {
+            self.stop("Download limit reached");
+        }
+    }
+
+    pub async fn stop_after(&self, timeout: Duration) {
+        tokio::time::sleep(timeout).await;
+        self.stop("Timeout reached");
+    }
+
+    // Ctrl+C or SIGTERM, the downloads running get the drain window instead of being cut
+    pub async fn stop_on_signal(&self) {
+        #[cfg(unix)]
+        let reason = {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminate) => tokio::select! {
+                    _ = tokio::signal::ctrl_c() => "Interrupted",
+                    _ = terminate.recv() => "Terminated",
+                },
+                Err(err) => {
+                    log::warn!("[SHUTDOWN] cannot listen for SIGTERM: {}", err);
+                    let _ = tokio::signal::ctrl_c().await;
+                    "Interrupted"
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let reason = {
+            let _ = tokio::signal::ctrl_c().await;
+            "Interrupted"
+        };
+        self.stop(reason);
+    }
+
+    pub fn stop(&self, reason: &str) {
+        log::info!("[SHUTDOWN] {}, stopping", reason);
+        println!("{}, stopping.", reason);
+        // the permit is kept when the server does not wait yet
+        self.stop.notify_one();
+    }
+
+    // resolves once the server should stop
+    pub async fn wait(&self) {
+        self.stop.notified().await;
+    }
+}
+
+// --shutdown-token, digest of the token POST /api/shutdown has to send. comparing digests does
+// not leak how much of a token matched
+static TOKEN: OnceLock<[u8; 32]> = OnceLock::new();
+
+pub fn set_token(token: &str) {
+    let _ = TOKEN.set(Sha256::digest(token.as_bytes()).into());
+}
+
+pub fn remote_enabled() -> bool {
+    TOKEN.get().is_some()
+}
+
+// POST /api/shutdown with Authorization: Bearer <token>, for scripts that started the server
+// and want it gone without looking for its process
+pub async fn remote(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(expected) = TOKEN.get() else {
+        return error_response(StatusCode::NOT_FOUND, "Remote shutdown is off");
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|token| <[u8; 32]>::from(Sha256::digest(token.trim().as_bytes())));
+    if given.as_ref() != Some(expected) {
+        log::warn!("[SHUTDOWN] Client: {} | refused, wrong token", addr);
+        auth_log::failed(addr.ip(), None);
+        return error_response(StatusCode::UNAUTHORIZED, "Wrong shutdown token");
+    }
+    log::info!("[SHUTDOWN] Client: {} | asked to stop", addr);
+    state.shutdown.stop("Stopped by a request");
+    (StatusCode::ACCEPTED, "Stopping\n").into_response()
+}
+
+// splits the shutdown of a server: it stops accepting once the first future resolves, the second
+// resolves when the drain window after that is over and the requests left are cut
+pub fn with_drain(
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> (
+    impl Future<Output = ()> + Send + 'static,
+    impl Future<Output = ()> + Send + 'static,
+) {
+    let (stopping, stopped) = oneshot::channel();
+    let shutdown = async move {
+        shutdown.await;
+        let _ = stopping.send(());
+    };
+    let drained = async move {
+        // the server ended without a shutdown, nothing to drain
+        if stopped.await.is_err() {
+            return std::future::pending().await;
+        }
+        tokio::time::sleep(DRAIN).await;
+        log::warn!(
+            "[SHUTDOWN] requests still running after {}s are cut",
+            DRAIN.as_secs()
+        );
+    };
+    (shutdown, drained)
+}
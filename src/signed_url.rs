@@ -0,0 +1,114 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, KeyInit, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// time limited download links, checked with the server secret only so nothing is stored
+pub struct UrlSigner {
+    secret: Vec<u8>,
+}
+
+impl UrlSigner {
+    pub fn new(secret: &str) -> Self {
+        UrlSigner {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    fn mac(&self, path: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(path.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+
+    // link to /download/<file> valid for ttl seconds, file is relative to the served folder
+    pub fn sign(&self, file: &str, ttl: u64) -> String {
+        let path = format!("/download/{}", file.trim_start_matches('/'));
+        let expires = unix_now() + ttl;
+        let sig = URL_SAFE_NO_PAD.encode(self.mac(&path, expires).finalize().into_bytes());
+        let encoded: Vec<String> = path
+            .split('/')
+            .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+            .collect();
+        format!("{}?exp={}&sig={}", encoded.join("/"), expires, sig)
+    }
+
+    // checks the exp and sig parameters against the decoded request path
+    pub fn verify(&self, path: &str, query: Option<&str>) -> bool {
+        let mut expires = None;
+        let mut sig = None;
+        for (name, value) in query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+        {
+            match name {
+                "exp" => expires = value.parse::<u64>().ok(),
+                "sig" => sig = URL_SAFE_NO_PAD.decode(value).ok(),
+                _ => {}
+            }
+        }
+        let (Some(expires), Some(sig)) = (expires, sig) else {
+            return false;
+        };
+        expires >= unix_now() && self.mac(path, expires).verify_slice(&sig).is_ok()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use percent_encoding::percent_decode_str;
+
+    // the decoded path and the query of a signed link, as the login middleware sees them
+    fn split(link: &str) -> (String, String) {
+        let (path, query) = link.split_once('?').unwrap();
+        let path = percent_decode_str(path).decode_utf8().unwrap().to_string();
+        (path, query.to_string())
+    }
+
+    #[test]
+    fn signed_link_passes() {
+        let signer = UrlSigner::new("secret");
+        let (path, query) = split(&signer.sign("docs/report 1.pdf", 60));
+        assert_eq!(path, "/download/docs/report 1.pdf");
+        assert!(signer.verify(&path, Some(&query)));
+    }
+
+    #[test]
+    fn expired_link_fails() {
+        let signer = UrlSigner::new("secret");
+        let path = "/download/report.pdf";
+        let expires = unix_now() - 1;
+        let sig = URL_SAFE_NO_PAD.encode(signer.mac(path, expires).finalize().into_bytes());
+        let query = format!("exp={}&sig={}", expires, sig);
+        assert!(!signer.verify(path, Some(&query)));
+    }
+
+    #[test]
+    fn tampered_link_fails() {
+        let signer = UrlSigner::new("secret");
+        let (path, query) = split(&signer.sign("report.pdf", 60));
+        let (exp, sig) = query.split_once('&').unwrap();
+        let expires: u64 = exp.strip_prefix("exp=").unwrap().parse().unwrap();
+
+        let later = format!("exp={}&{}", expires + 3600, sig);
+        assert!(!signer.verify(&path, Some(&later)));
+        let other_sig = format!("{}&sig={}", exp, URL_SAFE_NO_PAD.encode([0u8; 32]));
+        assert!(!signer.verify(&path, Some(&other_sig)));
+        assert!(!signer.verify("/download/other.pdf", Some(&query)));
+        assert!(!signer.verify(&path, Some(exp)));
+        assert!(!signer.verify(&path, None));
+        assert!(!UrlSigner::new("other").verify(&path, Some(&query)));
+    }
+}
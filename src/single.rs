@@ -0,0 +1,46 @@
+use crate::{auth::User, base_path, download_file, errors::error_response, utils, AppState};
+use axum::{
+    extract::{ConnectInfo, Path as AxumPath, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Extension, Router,
+};
+use std::{net::SocketAddr, sync::Arc};
+
+// file-serve share <FILE>, the file is the only thing served from its folder. the server
+// stops after --max-downloads of it like any other
+#[derive(Clone)]
+struct SingleFile {
+    name: Arc<str>,
+}
+
+pub fn routes(name: &str) -> Router<AppState> {
+    Router::new()
+        .route("/", get(send))
+        .route("/{*path}", get(send))
+        .layer(Extension(SingleFile { name: name.into() }))
+}
+
+// the link names the file so downloads keep its name, / leads there
+async fn send(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    method: Method,
+    headers: HeaderMap,
+    Extension(single): Extension<SingleFile>,
+    path: Option<AxumPath<String>>,
+) -> Response {
+    let Some(AxumPath(path)) = path else {
+        return Redirect::to(&base_path::link(&format!(
+            "/{}",
+            utils::encode_path(&single.name)
+        )))
+        .into_response();
+    };
+    if path != *single.name {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    download_file(state, addr, user, method, headers, AxumPath(path)).await
+}
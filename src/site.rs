@@ -0,0 +1,117 @@
+use crate::{
+    auth::User,
+    base_path,
+    errors::error_response,
+    list_files,
+    listing::{LayoutParams, PageParams, SortParams},
+    send_file,
+    visibility::Anonymous,
+    AppState, Disposition, Located,
+};
+use axum::{
+    extract::{ConnectInfo, Path as AxumPath, Query, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use percent_encoding::percent_decode_str;
+use std::net::SocketAddr;
+
+// --site, the folder as a static website: a folder gets its index.html and files are shown by
+// the browser instead of downloaded. a folder without an index.html falls back to its listing.
+// serves / and every path the other routes leave over
+#[allow(clippy::too_many_arguments)]
+pub async fn send(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    anonymous: Option<Extension<Anonymous>>,
+    method: Method,
+    headers: HeaderMap,
+    sort: Query<SortParams>,
+    paging: Query<PageParams>,
+    layout: Query<LayoutParams>,
+    uri: Uri,
+) -> Response {
+    let Ok(path) = percent_decode_str(uri.path()).decode_utf8() else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    };
+    let path = path.trim_start_matches('/').to_string();
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+
+    let folder = path.trim_end_matches('/');
+    let is_dir = match state.locate(&headers, user.as_deref(), folder) {
+        Ok(Located::Disk(root, below)) => {
+            let target = root.join(below);
+            (target.is_dir() || target.is_file()).then(|| target.is_dir())
+        }
+        Ok(Located::Mounts(_)) => Some(true),
+        Ok(Located::Packed(packed, below)) => packed.is_dir(below),
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    match is_dir {
+        Some(false) => {
+            send_file(
+                state,
+                addr,
+                user,
+                &method,
+                headers,
+                path,
+                Disposition::Inline,
+            )
+            .await
+        }
+        // relative links of the page are resolved against the folder only with the slash
+        Some(true) if !uri.path().ends_with('/') => {
+            Redirect::permanent(&base_path::link(&format!("{}/", uri.path()))).into_response()
+        }
+        Some(true) => {
+            let index = if folder.is_empty() {
+                INDEX.to_string()
+            } else {
+                format!("{}/{}", folder, INDEX)
+            };
+            if has_file(&state, &headers, user.as_deref(), &index) {
+                return send_file(
+                    state,
+                    addr,
+                    user,
+                    &method,
+                    headers,
+                    index,
+                    Disposition::Inline,
+                )
+                .await;
+            }
+            let folder = (!folder.is_empty()).then(|| AxumPath(folder.to_string()));
+            list_files(
+                State(state),
+                ConnectInfo(addr),
+                user,
+                anonymous,
+                headers,
+                sort,
+                paging,
+                layout,
+                folder,
+            )
+            .await
+        }
+        None => error_response(StatusCode::NOT_FOUND, "File not found"),
+    }
+}
+
+// the page of a folder
+const INDEX: &str = "index.html";
+
+fn has_file(state: &AppState, headers: &HeaderMap, user: Option<&User>, path: &str) -> bool {
+    match state.locate(headers, user, path) {
+        Ok(Located::Disk(root, below)) => root.join(below).is_file(),
+        Ok(Located::Packed(packed, below)) => packed.is_dir(below) == Some(false),
+        Ok(Located::Mounts(_)) | Err(_) => false,
+    }
+}
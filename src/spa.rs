@@ -0,0 +1,71 @@
+use crate::{assets, auth::User, send_file, AppState, Disposition, Located};
+use axum::{
+    extract::{ConnectInfo, Path as AxumPath, State},
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::get,
+    Extension, Router,
+};
+use std::net::SocketAddr;
+
+// the page every route of the app starts from
+const INDEX: &str = "index.html";
+
+// --spa, a built single page app: a path naming one of its files gets that file and every other
+// one gets the index.html of the folder, so the app routes it in the browser
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(send))
+        .route("/{*path}", get(send))
+}
+
+async fn send(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    method: Method,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+) -> Response {
+    let path = path.map_or(String::new(), |AxumPath(path)| path);
+    if is_file(&state, &headers, user.as_deref(), &path) {
+        return send_file(
+            state,
+            addr,
+            user,
+            &method,
+            headers,
+            path,
+            Disposition::Inline,
+        )
+        .await;
+    }
+    // the pages of the server, the login among them, keep their stylesheet unless the app has
+    // an asset of that name itself
+    if let Some(name) = path.strip_prefix("assets/")
+        && assets::exists(name)
+    {
+        return assets::asset(AxumPath(name.to_string())).await;
+    }
+    send_file(
+        state,
+        addr,
+        user,
+        &method,
+        headers,
+        INDEX.to_string(),
+        Disposition::Inline,
+    )
+    .await
+}
+
+fn is_file(state: &AppState, headers: &HeaderMap, user: Option<&User>, path: &str) -> bool {
+    if path.is_empty() || path.contains("..") || path.starts_with(['/', '\\']) {
+        return false;
+    }
+    match state.locate(headers, user, path) {
+        Ok(Located::Disk(root, below)) => root.join(below).is_file(),
+        Ok(Located::Packed(packed, below)) => packed.row(below).is_some_and(|row| !row.is_dir),
+        Ok(Located::Mounts(_)) | Err(_) => false,
+    }
+}
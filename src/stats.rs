@@ -0,0 +1,206 @@
+use crate::{
+    errors::error_response,
+    listing::{self, Format},
+    templates::{self, Theme},
+    utils,
+};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+// the statistics are written this long after a download at the latest
+pub const SAVE_EVERY: Duration = Duration::from_secs(30);
+
+// --stats, what was downloaded, how often and by how many clients. kept in memory, and in
+// --stats-file across restarts
+struct Stats {
+    files: Mutex<HashMap<String, FileStats>>,
+    file: Option<PathBuf>,
+    // changed since the last save
+    dirty: AtomicBool,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct FileStats {
+    // complete downloads of the whole file
+    downloads: u64,
+    // every byte sent, parts and interrupted downloads included
+    bytes: u64,
+    clients: BTreeSet<IpAddr>,
+    last: Option<DateTime<Utc>>,
+}
+
+static STATS: OnceLock<Stats> = OnceLock::new();
+
+// the statistics of the file are read back when it exists
+pub fn enable(file: Option<PathBuf>) -> Result<(), String> {
+    let files = match &file {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("cannot read {}: {}", path.display(), e))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(format!("cannot read {}: {}", path.display(), err)),
+        },
+        None => HashMap::new(),
+    };
+    let _ = STATS.set(Stats {
+        files: Mutex::new(files),
+        file,
+        dirty: AtomicBool::new(false),
+    });
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    STATS.get().is_some()
+}
+
+// a download of the file that ended, `complete` when the whole file got to the client
+pub fn record(path: &str, client: IpAddr, bytes: u64, complete: bool) {
+    let Some(stats) = STATS.get() else {
+        return;
+    };
+    let mut files = stats.files.lock().unwrap_or_else(|e| e.into_inner());
+    let file = files.entry(path.to_string()).or_default();
+    if complete {
+        file.downloads += 1;
+    }
+    file.bytes += bytes;
+    file.clients.insert(client);
+    file.last = Some(Utc::now());
+    stats.dirty.store(true, Ordering::Relaxed);
+}
+
+// writes the statistics to --stats-file when they changed
+pub fn save() {
+    let Some(stats) = STATS.get() else {
+        return;
+    };
+    let Some(path) = &stats.file else {
+        return;
+    };
+    if !stats.dirty.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let json = {
+        let files = stats.files.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::to_string_pretty(&*files)
+    };
+    if let Err(err) = json
+        .map_err(io::Error::other)
+        .and_then(|json| write(path, &json))
+    {
+        log::warn!("[STATS] cannot write {}: {}", path.display(), err);
+        stats.dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+// through a temporary file, a crash while writing leaves the previous statistics
+fn write(path: &Path, json: &str) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, json)?;
+    fs::rename(&temporary, path)
+}
+
+pub async fn save_every(period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        tokio::task::spawn_blocking(save);
+    }
+}
+
+// a file of /stats and /api/stats
+#[derive(Serialize)]
+struct Row {
+    path: String,
+    downloads: u64,
+    bytes: u64,
+    size: String,
+    clients: usize,
+    last: Option<String>,
+}
+
+// fields of the statistics page
+#[derive(Serialize)]
+struct StatsPage {
+    rows: Vec<Row>,
+    downloads: u64,
+    size: String,
+}
+
+// /stats and /api/stats, the files downloaded the most first. JSON for Accept:
+// application/json, which /api/stats always is
+pub async fn page(headers: HeaderMap) -> Response {
+    render(&headers, listing::format(&headers))
+}
+
+pub async fn api(headers: HeaderMap) -> Response {
+    render(&headers, Format::Json)
+}
+
+fn render(headers: &HeaderMap, format: Format) -> Response {
+    let Some(stats) = STATS.get() else {
+        return error_response(StatusCode::NOT_FOUND, "Statistics are off");
+    };
+    let mut rows: Vec<Row> = stats
+        .files
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(path, file)| Row {
+            path: path.clone(),
+            downloads: file.downloads,
+            bytes: file.bytes,
+            size: utils::bytes_to_human_size(file.bytes),
+            clients: file.clients.len(),
+            last: file.last.map(|last| match format {
+                Format::Json => last.to_rfc3339_opts(SecondsFormat::Secs, true),
+                Format::Html | Format::Text => DateTime::<Local>::from(last)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            }),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.downloads
+            .cmp(&a.downloads)
+            .then(b.bytes.cmp(&a.bytes))
+            .then(a.path.cmp(&b.path))
+    });
+    let page = StatsPage {
+        downloads: rows.iter().map(|row| row.downloads).sum(),
+        size: utils::bytes_to_human_size(rows.iter().map(|row| row.bytes).sum()),
+        rows,
+    };
+    match format {
+        Format::Json => Json(page.rows).into_response(),
+        Format::Html | Format::Text => {
+            match templates::render("stats.html", Theme::of(headers), &page) {
+                Ok(html) => Html(html).into_response(),
+                Err(e) => {
+                    log::error!("Error loading stats template: {}", e);
+                    error_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to load stats template",
+                    )
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use axum::http::StatusCode;
+use std::{
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+// which symbolic links are followed, --follow-symlinks
+#[derive(Clone, Copy, PartialEq)]
+pub enum SymlinkPolicy {
+    // links are left out of the listings and cannot be opened
+    Never,
+    // links are followed when they lead to a place inside the shared folder, the default
+    InsideRoot,
+    // links are followed wherever they lead
+    Always,
+}
+
+static POLICY: OnceLock<SymlinkPolicy> = OnceLock::new();
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(SymlinkPolicy::Never),
+            "inside-root" => Ok(SymlinkPolicy::InsideRoot),
+            "always" => Ok(SymlinkPolicy::Always),
+            _ => Err(format!("unknown symlink policy {}", s)),
+        }
+    }
+}
+
+pub fn set_policy(policy: SymlinkPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+pub fn policy() -> SymlinkPolicy {
+    *POLICY.get_or_init(|| SymlinkPolicy::InsideRoot)
+}
+
+// the real path of a target below the root, when the policy lets its links be followed.
+// `what` names it in the not found error
+pub fn resolve(root: &Path, target: &Path, what: &str) -> Result<PathBuf, (StatusCode, String)> {
+    let not_found = || (StatusCode::NOT_FOUND, format!("{} not found", what));
+    let canonical_root = root.canonicalize().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to access root".to_string(),
+        )
+    })?;
+    let canonical_target = target.canonicalize().map_err(|_| not_found())?;
+    match policy() {
+        SymlinkPolicy::Never => {
+            let below = target.strip_prefix(root).map_err(|_| not_found())?;
+            let mut path = root.to_path_buf();
+            for part in below.components() {
+                path.push(part);
+                if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+                    return Err((
+                        StatusCode::FORBIDDEN,
+                        "Symbolic links are not followed on this server".to_string(),
+                    ));
+                }
+            }
+        }
+        SymlinkPolicy::InsideRoot => {
+            if !canonical_target.starts_with(&canonical_root) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "The link leads outside the shared folder".to_string(),
+                ));
+            }
+        }
+        SymlinkPolicy::Always => {}
+    }
+    Ok(canonical_target)
+}
+
+// the metadata of what a link in a listed folder leads to, none when it is not followed or
+// broken
+pub fn follow(link: &Path, canonical_root: &Path) -> Option<Metadata> {
+    match policy() {
+        SymlinkPolicy::Never => return None,
+        SymlinkPolicy::InsideRoot => {
+            if !link.canonicalize().ok()?.starts_with(canonical_root) {
+                return None;
+            }
+        }
+        SymlinkPolicy::Always => {}
+    }
+    fs::metadata(link).ok()
+}
@@ -0,0 +1,78 @@
+use std::{
+    ffi::{CStr, CString, OsStr},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+// validates local system accounts through PAM
+pub struct SystemAuth {
+    service: String,
+    // serve the home directory of each user instead of the shared root
+    home_roots: bool,
+}
+
+impl SystemAuth {
+    pub fn new(service: &str, home_roots: bool) -> Self {
+        SystemAuth {
+            service: service.to_string(),
+            home_roots,
+        }
+    }
+
+    // checks the password, returns the home directory to serve when enabled
+    pub async fn authenticate(
+        &self,
+        user: &str,
+        password: &str,
+    ) -> Result<Option<PathBuf>, String> {
+        if user.is_empty() || password.is_empty() {
+            return Err("empty user name or password".to_string());
+        }
+
+        // PAM modules block, some of them on purpose after a failure
+        let (service, login, passwd) =
+            (self.service.clone(), user.to_string(), password.to_string());
+        tokio::task::spawn_blocking(move || check_password(&service, &login, &passwd))
+            .await
+            .map_err(|e| e.to_string())??;
+
+        if !self.home_roots {
+            return Ok(None);
+        }
+        home_dir(user)
+            .map(Some)
+            .ok_or_else(|| format!("{} has no home directory", user))
+    }
+}
+
+fn check_password(service: &str, user: &str, password: &str) -> Result<(), String> {
+    let mut authenticator = pam::Authenticator::with_password(service)
+        .map_err(|e| format!("cannot start PAM service {}: {}", service, e))?;
+    authenticator.get_handler().set_credentials(user, password);
+    authenticator
+        .authenticate()
+        .map_err(|e| format!("PAM authentication of {} failed: {}", user, e))
+}
+
+// home directory from the system user database
+fn home_dir(user: &str) -> Option<PathBuf> {
+    let name = CString::new(user).ok()?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
@@ -0,0 +1,73 @@
+use socket2::Socket;
+use std::{
+    env, io,
+    os::{
+        fd::{FromRawFd, IntoRawFd},
+        unix::net::UnixListener,
+    },
+};
+
+// the first socket passed by systemd, the ones after it are left alone
+const LISTEN_FDS_START: i32 = 3;
+
+// a socket systemd listens on for the server, see systemd.socket(5)
+pub enum Inherited {
+    Tcp(std::net::TcpListener),
+    Unix(UnixListener),
+}
+
+impl Inherited {
+    pub fn port(&self) -> Option<u16> {
+        match self {
+            Inherited::Tcp(listener) => listener.local_addr().ok().map(|local| local.port()),
+            Inherited::Unix(_) => None,
+        }
+    }
+}
+
+// the socket of a socket activation, LISTEN_FDS and LISTEN_PID naming this process. the
+// variables are removed so programs started by the server do not take them for theirs
+pub fn listener() -> Option<io::Result<Inherited>> {
+    let pid = env::var("LISTEN_PID").ok()?;
+    let fds = env::var("LISTEN_FDS").ok()?;
+    if pid.parse::<u32>().ok()? != std::process::id() {
+        return None;
+    }
+    let fds = fds.parse::<i32>().ok().filter(|fds| *fds > 0)?;
+    // SAFETY: the environment is changed before the runtime starts its threads
+    unsafe {
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+    }
+    if fds > 1 {
+        log::warn!(
+            "[SYSTEMD] {} sockets passed, only the first one is used",
+            fds
+        );
+    }
+    // SAFETY: with LISTEN_PID naming this process the descriptor belongs to it and nothing
+    // else takes it
+    let socket = unsafe { Socket::from_raw_fd(LISTEN_FDS_START) };
+    Some(inherit(socket))
+}
+
+fn inherit(socket: Socket) -> io::Result<Inherited> {
+    // the descriptor is not passed on to ffmpeg and the like
+    socket.set_cloexec(true)?;
+    socket.set_nonblocking(true)?;
+    let local = socket.local_addr()?;
+    if local.as_socket().is_some() {
+        return Ok(Inherited::Tcp(socket.into()));
+    }
+    if local.is_unix() {
+        // SAFETY: the descriptor is a listening Unix socket owned by `socket`, handed over
+        return Ok(Inherited::Unix(unsafe {
+            UnixListener::from_raw_fd(socket.into_raw_fd())
+        }));
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the socket of systemd is neither TCP nor a Unix socket",
+    ))
+}
@@ -0,0 +1,181 @@
+use crate::base_path;
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+use tera::{Context, Tera};
+
+// the default pages are built into the binary, so it runs from any folder
+pub const BUILT_IN: [(&str, &str); 9] = [
+    ("index.html", include_str!("../templates/index.html")),
+    ("error.html", include_str!("../templates/error.html")),
+    ("login.html", include_str!("../templates/login.html")),
+    ("share.html", include_str!("../templates/share.html")),
+    ("receive.html", include_str!("../templates/receive.html")),
+    ("markdown.html", include_str!("../templates/markdown.html")),
+    ("video.html", include_str!("../templates/video.html")),
+    ("stats.html", include_str!("../templates/stats.html")),
+    ("admin.html", include_str!("../templates/admin.html")),
+];
+
+// --templates, files in it replace the built-in page of the same name
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// --theme, the theme of visitors who did not pick one with the toggle
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+// cookie set by the theme toggle of the listing
+const THEME_COOKIE: &str = "file_serve_theme";
+
+// colors of the pages, auto follows the system setting of the visitor
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    Auto,
+}
+
+impl Theme {
+    pub fn parse(name: &str) -> Option<Theme> {
+        match name {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            "auto" => Some(Theme::Auto),
+            _ => None,
+        }
+    }
+
+    // the theme picked with the toggle, else --theme
+    pub fn of(headers: &HeaderMap) -> Theme {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == THEME_COOKIE)
+            .and_then(|(_, value)| Theme::parse(value))
+            .unwrap_or_else(Theme::default_theme)
+    }
+
+    pub fn default_theme() -> Theme {
+        *THEME.get_or_init(|| Theme::Auto)
+    }
+}
+
+// --title, --logo and --footer, shown on every page, unset ones keep the default texts
+#[derive(Default, Serialize)]
+pub struct Branding {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_title: Option<String>,
+    // the image as a data URL, so exported pages carry it too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    // HTML, inserted as it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+}
+
+static BRANDING: OnceLock<Branding> = OnceLock::new();
+
+// a parsed page with the modification time of its file, none for a built-in page
+type Parsed = (Option<SystemTime>, Arc<Tera>);
+
+lazy_static::lazy_static! {
+    static ref CACHE: Mutex<HashMap<String, Parsed>> = Mutex::new(HashMap::new());
+}
+
+pub fn set_dir(dir: PathBuf) {
+    let _ = DIR.set(dir);
+}
+
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn set_branding(branding: Branding) {
+    let _ = BRANDING.set(branding);
+}
+
+// the logo file as a data URL, it has to be an image
+pub fn logo_url(path: &Path) -> Result<String, String> {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    if mime.type_() != mime_guess::mime::IMAGE {
+        return Err(format!("{} is not an image", path.display()));
+    }
+    let data = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    Ok(format!("data:{};base64,{}", mime, STANDARD.encode(data)))
+}
+
+pub fn dir() -> &'static Path {
+    DIR.get_or_init(|| PathBuf::from("templates"))
+}
+
+// renders a page with the fields of `page`, the theme, the branding, the --base-path and the
+// assets folder as variables, values are HTML-escaped
+pub fn render(name: &str, theme: Theme, page: &impl Serialize) -> Result<String, String> {
+    let tera = load(name)?;
+    let mut context = Context::from_serialize(page).map_err(|e| e.to_string())?;
+    context.insert("theme", &theme);
+    context.insert("base", base_path::get());
+    if !context.contains_key("assets") {
+        context.insert("assets", &base_path::link("/assets"));
+    }
+    if let Some(branding) = BRANDING.get() {
+        context.extend(Context::from_serialize(branding).map_err(|e| e.to_string())?);
+    }
+    tera.render(name, &context)
+        .map_err(|e| format!("cannot render {}: {}", name, e))
+}
+
+// whether there is a page of that name, 404.html and the like only come from the folder
+pub fn exists(name: &str) -> bool {
+    built_in(name).is_some() || dir().join(name).is_file()
+}
+
+// parses the page, for the doctor
+pub fn check(name: &str) -> Result<(), String> {
+    load(name).map(|_| ())
+}
+
+// the page from the template folder when it has one, else the built-in one. a file is parsed
+// again once it changed, edits show up without a restart
+fn load(name: &str) -> Result<Arc<Tera>, String> {
+    let path = dir().join(name);
+    // the sandbox hides a folder it was not given, which looks like a missing file too
+    let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((cached, tera)) = cache.get(name)
+        && *cached == modified
+    {
+        return Ok(tera.clone());
+    }
+    let source = match modified {
+        Some(_) => fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?,
+        None => built_in(name)
+            .ok_or_else(|| format!("no template {}", name))?
+            .to_string(),
+    };
+    let mut tera = Tera::default();
+    tera.add_raw_template(name, &source)
+        .map_err(|e| format!("invalid template {}: {}", name, e))?;
+    let tera = Arc::new(tera);
+    cache.insert(name.to_string(), (modified, tera.clone()));
+    Ok(tera)
+}
+
+pub fn built_in(name: &str) -> Option<&'static str> {
+    BUILT_IN
+        .iter()
+        .find(|(built_in, _)| *built_in == name)
+        .map(|(_, content)| *content)
+}
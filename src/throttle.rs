@@ -0,0 +1,102 @@
+use crate::filter;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::{
+    io,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+// --limit-rate for all downloads together and --limit-rate-per-connection for every one of them,
+// in bytes per second
+struct Limits {
+    all: Option<Mutex<Bucket>>,
+    each: Option<u64>,
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+pub fn set_limits(all: Option<u64>, each: Option<u64>) {
+    if all.is_none() && each.is_none() {
+        return;
+    }
+    let _ = LIMITS.set(Limits {
+        all: all.map(|rate| Mutex::new(Bucket::new(rate))),
+        each,
+    });
+}
+
+pub fn enabled() -> bool {
+    LIMITS.get().is_some()
+}
+
+// rates like 500K/s or 5MB/s, the /s can be left out
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let size = s
+        .strip_suffix("/s")
+        .or_else(|| s.strip_suffix("/S"))
+        .unwrap_or(s);
+    match filter::parse_size(size)? {
+        0 => Err(format!("rate {} must be greater than 0", s)),
+        rate => Ok(rate),
+    }
+}
+
+// a quarter of a second worth of bytes goes out at once, the rest is paced
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    at: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate as f64;
+        let burst = (rate / 4.0).max(64.0 * 1024.0);
+        Bucket {
+            rate,
+            burst,
+            tokens: burst,
+            at: Instant::now(),
+        }
+    }
+
+    // takes `len` bytes, going into debt when there are not enough, and tells how long the
+    // chunk has to wait for the debt to be paid off
+    fn take(&mut self, len: usize) -> Duration {
+        let now = Instant::now();
+        self.tokens =
+            (self.tokens + now.duration_since(self.at).as_secs_f64() * self.rate).min(self.burst);
+        self.at = now;
+        self.tokens -= len as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+// holds every chunk of a download back until the limits allow it, only used when a limit is set
+pub fn limit<S>(stream: S) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+{
+    let limits = LIMITS.get();
+    let mut own = limits.and_then(|limits| limits.each).map(Bucket::new);
+    stream.then(move |chunk| {
+        let len = chunk.as_ref().map_or(0, Bytes::len);
+        let mut wait = own.as_mut().map_or(Duration::ZERO, |own| own.take(len));
+        if let Some(all) = limits.and_then(|limits| limits.all.as_ref()) {
+            wait = wait.max(all.lock().unwrap_or_else(|e| e.into_inner()).take(len));
+        }
+        async move {
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            chunk
+        }
+    })
+}
@@ -0,0 +1,166 @@
+use crate::{
+    auth::User, conditional::Validators, errors::error_response, exclude, symlinks, AppState,
+    Located,
+};
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use image::{DynamicImage, ImageFormat, ImageReader};
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::UNIX_EPOCH,
+};
+
+// longest side of a thumbnail in pixels
+const SIZE: u32 = 256;
+
+// --thumbnails, the folder the generated previews are kept in
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// turns the thumbnails on, creating their cache folder
+pub fn enable() -> io::Result<&'static Path> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir)?;
+    Ok(DIR.get_or_init(|| dir))
+}
+
+pub fn enabled() -> bool {
+    DIR.get().is_some()
+}
+
+// $XDG_CACHE_HOME/file-serve/thumbnails, ~/.cache without it and the temp folder without a home
+fn cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir)
+        .join("file-serve")
+        .join("thumbnails")
+}
+
+// the files a preview can be made of, by their name
+pub fn is_image(name: &str) -> bool {
+    matches!(
+        ImageFormat::from_path(name),
+        Ok(ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP)
+    )
+}
+
+// /thumb/<path>, a small JPEG of the image. it is made on the first request and kept in the
+// cache folder under the hash of the path, size and modification time, so a changed image
+// gets a new one
+pub async fn thumbnail(
+    State(state): State<AppState>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    if !is_image(&path) || !state.within_depth(&path, 1) || state.exclude.matches(&path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let Some(dir) = DIR.get() else {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    };
+    let file = match state.locate(&headers, user.as_deref(), &path) {
+        Ok(Located::Disk(root, below)) => {
+            match symlinks::resolve(root, &root.join(below), "File") {
+                Ok(file) => file,
+                Err((status, msg)) => return error_response(status, &msg),
+            }
+        }
+        // the entries of --archive are not read for previews
+        Ok(Located::Mounts(_)) | Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::NOT_FOUND, "File not found")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    // files outside the size and age filters are not shared, nor hidden ones
+    let meta = match fs::metadata(&file) {
+        Ok(meta)
+            if meta.is_file()
+                && state.filter.matches(meta.len(), meta.modified().ok())
+                && (state.exclude.show_hidden || !exclude::has_hidden_attribute(&meta)) =>
+        {
+            meta
+        }
+        _ => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    // the preview changes with the image only
+    let validators = Validators::of(&meta);
+    if validators.fresh(&headers) {
+        return validators.not_modified();
+    }
+
+    let made = tokio::task::spawn_blocking(move || cached(dir, &file))
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)));
+    match made {
+        Ok(jpeg) => {
+            let mut res = jpeg.into_response();
+            let headers = res.headers_mut();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("image/jpeg"));
+            headers.insert(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("private, max-age=3600"),
+            );
+            validators.apply(&mut res);
+            res
+        }
+        Err(err) => {
+            log::warn!("[THUMB] cannot make a preview of {}: {}", path, err);
+            error_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "No preview of this file",
+            )
+        }
+    }
+}
+
+// the thumbnail from the cache folder, made and stored there when it has none yet
+fn cached(dir: &Path, file: &Path) -> io::Result<Vec<u8>> {
+    let meta = fs::metadata(file)?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    let mut hash = Sha256::new();
+    hash.update(file.to_string_lossy().as_bytes());
+    hash.update(meta.len().to_le_bytes());
+    hash.update(modified.to_le_bytes());
+    let name: String = hash
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let cache = dir.join(format!("{}.jpg", name));
+    if let Ok(jpeg) = fs::read(&cache) {
+        return Ok(jpeg);
+    }
+
+    let image = ImageReader::open(file)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(io::Error::other)?;
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(image.thumbnail(SIZE, SIZE).into_rgb8())
+        .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .map_err(io::Error::other)?;
+    // written next to it first, a request at the same time never reads half a file
+    let partial = dir.join(format!("{}.part", name));
+    fs::write(&partial, &jpeg)?;
+    fs::rename(&partial, &cache)?;
+    Ok(jpeg)
+}
@@ -0,0 +1,81 @@
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// where the certificate of the HTTPS listener comes from
+pub enum TlsSource {
+    // --cert and --key
+    Pem(PathBuf, PathBuf),
+    // --tls-self-signed, for these names
+    SelfSigned(Vec<String>),
+}
+
+impl TlsSource {
+    // the server config and, for a generated certificate, its fingerprint
+    pub fn load(&self) -> Result<(Arc<ServerConfig>, Option<String>), Box<dyn Error>> {
+        match self {
+            TlsSource::Pem(cert, key) => Ok((load(cert, key)?, None)),
+            TlsSource::SelfSigned(names) => {
+                let (config, fingerprint) = self_signed(names.clone())?;
+                Ok((config, Some(fingerprint)))
+            }
+        }
+    }
+}
+
+// reads the PEM certificate chain and private key of --cert and --key, before the sandbox
+// is enabled so the files can live anywhere
+fn load(cert_path: &Path, key_path: &Path) -> Result<Arc<ServerConfig>, Box<dyn Error>> {
+    let cert_pem = fs::read(cert_path)
+        .map_err(|e| format!("cannot read certificate {}: {}", cert_path.display(), e))?;
+    let key_pem =
+        fs::read(key_path).map_err(|e| format!("cannot read key {}: {}", key_path.display(), e))?;
+
+    let certs = CertificateDer::pem_slice_iter(&cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate {}: {}", cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", cert_path.display()).into());
+    }
+    let key = PrivateKeyDer::from_pem_slice(&key_pem)
+        .map_err(|e| format!("invalid key {}: {}", key_path.display(), e))?;
+
+    config(certs, key)
+}
+
+// generates a certificate for the names that only lives as long as the process, for
+// --tls-self-signed, and returns its SHA-256 fingerprint to compare in the browser
+fn self_signed(names: Vec<String>) -> Result<(Arc<ServerConfig>, String), Box<dyn Error>> {
+    let generated = rcgen::generate_simple_self_signed(names)?;
+    let cert = generated.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+        generated.signing_key.serialize_der(),
+    ));
+
+    let fingerprint: Vec<String> = Sha256::digest(&cert)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    Ok((config(vec![cert], key)?, fingerprint.join(":")))
+}
+
+fn config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<Arc<ServerConfig>, Box<dyn Error>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
@@ -0,0 +1,255 @@
+use crate::{
+    auth::User, base_path, errors::error_response, exclude, symlinks, utils, video, AppState,
+    Located,
+};
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Mutex, OnceLock},
+    time::{Duration, UNIX_EPOCH},
+};
+use tokio::process::{Child, Command};
+
+// ffmpeg runs for this many videos at once, more wait until one is done
+const MAX_RUNNING: usize = 2;
+// how long a player waits for the first segments of a video
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+const PLAYLIST: &str = "index.m3u8";
+
+// --transcode, videos browsers cannot play are converted to HLS by ffmpeg as they are watched.
+// the segments are kept in a temporary folder of this run, one folder per video
+struct Transcoder {
+    dir: PathBuf,
+    // the ffmpeg of every video converted so far by its folder, finished ones included
+    jobs: Mutex<HashMap<PathBuf, Child>>,
+}
+
+static TRANSCODER: OnceLock<Transcoder> = OnceLock::new();
+
+// turns the transcoding on, creating the folder of the segments
+pub fn enable() -> io::Result<&'static Path> {
+    let dir = env::temp_dir().join(format!("file-serve-hls-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let transcoder = TRANSCODER.get_or_init(|| Transcoder {
+        dir,
+        jobs: Mutex::new(HashMap::new()),
+    });
+    Ok(&transcoder.dir)
+}
+
+pub fn enabled() -> bool {
+    TRANSCODER.get().is_some()
+}
+
+// removes the segments once the server stopped
+pub fn clean_up() {
+    let Some(transcoder) = TRANSCODER.get() else {
+        return;
+    };
+    if let Ok(mut jobs) = transcoder.jobs.lock() {
+        for child in jobs.values_mut() {
+            let _ = child.start_kill();
+        }
+    }
+    if let Err(err) = fs::remove_dir_all(&transcoder.dir) {
+        log::warn!("[HLS] cannot remove {}: {}", transcoder.dir.display(), err);
+    }
+}
+
+// the stream of a video as HLS, for the ones browsers do not play themselves
+pub fn playlist_url(path: &str) -> Option<String> {
+    (enabled() && video::is_video(path) && !video::plays_natively(path))
+        .then(|| base_path::link(&format!("/hls/{}/{}", utils::encode_path(path), PLAYLIST)))
+}
+
+// /hls/<video>/index.m3u8 and the segments next to it. the conversion starts with the first
+// request of the playlist, which waits for its first segments
+pub async fn stream(
+    State(state): State<AppState>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    AxumPath(path): AxumPath<String>,
+) -> Response {
+    let Some(transcoder) = TRANSCODER.get() else {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    };
+    let Some((path, part)) = path.rsplit_once('/') else {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    };
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    if !video::is_video(path) || !state.within_depth(path, 1) || state.exclude.matches(path) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    let file = match state.locate(&headers, user.as_deref(), path) {
+        Ok(Located::Disk(root, below)) => {
+            match symlinks::resolve(root, &root.join(below), "File") {
+                Ok(file) => file,
+                Err((status, msg)) => return error_response(status, &msg),
+            }
+        }
+        // the entries of --archive have no path ffmpeg could read
+        Ok(Located::Mounts(_)) | Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::NOT_FOUND, "File not found")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    // files outside the size and age filters are not shared, nor hidden ones
+    let meta = match fs::metadata(&file) {
+        Ok(meta)
+            if meta.is_file()
+                && state.filter.matches(meta.len(), meta.modified().ok())
+                && (state.exclude.show_hidden || !exclude::has_hidden_attribute(&meta)) =>
+        {
+            meta
+        }
+        _ => return error_response(StatusCode::NOT_FOUND, "File not found"),
+    };
+    let out = transcoder.dir.join(key(&file, &meta));
+
+    if part == PLAYLIST {
+        if let Err((status, msg)) = transcoder.start(&file, &out) {
+            return error_response(status, &msg);
+        }
+        if let Err((status, msg)) = transcoder.wait_for_start(&out).await {
+            return error_response(status, &msg);
+        }
+        // rewritten while ffmpeg goes on, players must not keep it
+        return send(
+            &out.join(PLAYLIST),
+            "application/vnd.apple.mpegurl",
+            "no-cache",
+        )
+        .await;
+    }
+    if !is_segment(part) {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+    send(&out.join(part), "video/mp2t", "private, max-age=3600").await
+}
+
+impl Transcoder {
+    // runs ffmpeg for the video unless it already did
+    fn start(&self, file: &Path, out: &Path) -> Result<(), (StatusCode, String)> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if jobs.contains_key(out) {
+            return Ok(());
+        }
+        let running = jobs
+            .values_mut()
+            .filter(|child| matches!(child.try_wait(), Ok(None)))
+            .count();
+        if running >= MAX_RUNNING {
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Other videos are being converted, try again in a moment".to_string(),
+            ));
+        }
+        let failed = |err: io::Error| {
+            log::error!("[HLS] cannot run ffmpeg for {}: {}", file.display(), err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Cannot convert the video, is ffmpeg installed?".to_string(),
+            )
+        };
+        fs::create_dir_all(out).map_err(failed)?;
+        let child = Command::new("ffmpeg")
+            .args(["-nostdin", "-loglevel", "error", "-i"])
+            .arg(file)
+            .args(["-map", "0:v:0", "-map", "0:a:0?"])
+            .args([
+                "-c:v", "libx264", "-preset", "veryfast", "-pix_fmt", "yuv420p",
+            ])
+            .args(["-c:a", "aac", "-ac", "2"])
+            .args(["-f", "hls", "-hls_time", "6", "-hls_playlist_type", "event"])
+            .args(["-hls_flags", "temp_file", "-hls_segment_filename"])
+            .arg(out.join("seg%05d.ts"))
+            .arg(out.join(PLAYLIST))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(failed)?;
+        log::info!("[HLS] converting {}", file.display());
+        jobs.insert(out.to_path_buf(), child);
+        Ok(())
+    }
+
+    // waits until ffmpeg wrote the playlist, or stopped without it
+    async fn wait_for_start(&self, out: &Path) -> Result<(), (StatusCode, String)> {
+        let playlist = out.join(PLAYLIST);
+        let started = tokio::time::Instant::now();
+        while !playlist.exists() {
+            let exited = self
+                .jobs
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_mut(out)
+                .and_then(|child| child.try_wait().ok().flatten());
+            if let Some(status) = exited {
+                log::warn!("[HLS] ffmpeg stopped with {} for {}", status, out.display());
+                return Err((
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "The video could not be converted".to_string(),
+                ));
+            }
+            if started.elapsed() > START_TIMEOUT {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "The video is still being prepared, try again in a moment".to_string(),
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        Ok(())
+    }
+}
+
+// the folder of a video, named by the hash of its path, size and modification time so a changed
+// video is converted again
+fn key(file: &Path, meta: &fs::Metadata) -> String {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    let mut hash = Sha256::new();
+    hash.update(file.to_string_lossy().as_bytes());
+    hash.update(meta.len().to_le_bytes());
+    hash.update(modified.to_le_bytes());
+    hash.finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// seg00042.ts, the only other names in the folder of a video
+fn is_segment(name: &str) -> bool {
+    name.strip_prefix("seg")
+        .and_then(|rest| rest.strip_suffix(".ts"))
+        .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+async fn send(file: &Path, mime: &'static str, cache: &'static str) -> Response {
+    match tokio::fs::read(file).await {
+        Ok(content) => {
+            let mut res = content.into_response();
+            let headers = res.headers_mut();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(cache));
+            res
+        }
+        Err(_) => error_response(StatusCode::NOT_FOUND, "File not found"),
+    }
+}
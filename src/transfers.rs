@@ -0,0 +1,242 @@
+use crate::{
+    base_path,
+    cache_control::FileContent,
+    stats,
+    webhook::{self, Kind},
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use futures_util::StreamExt;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+// the downloads being sent and the bytes sent so far, for the dashboard of --tui, /admin,
+// --stats and --webhook. nothing is tracked unless one of them is on
+struct Registry {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<u64, Active>>,
+    // bytes of every download since the start, finished and interrupted ones included
+    served: AtomicU64,
+    // requests whose response is not ready yet
+    in_flight: AtomicUsize,
+}
+
+struct Active {
+    client: IpAddr,
+    path: String,
+    total: Option<u64>,
+    sent: Arc<AtomicU64>,
+    started: Instant,
+}
+
+// a download as it is at the moment
+pub struct Transfer {
+    pub id: u64,
+    pub client: IpAddr,
+    pub path: String,
+    // the length announced to the client, none for streamed archives
+    pub total: Option<u64>,
+    pub sent: u64,
+    pub started: Instant,
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+pub fn enable() {
+    let _ = REGISTRY.set(Registry {
+        next_id: AtomicU64::new(1),
+        active: Mutex::new(HashMap::new()),
+        served: AtomicU64::new(0),
+        in_flight: AtomicUsize::new(0),
+    });
+}
+
+pub fn enabled() -> bool {
+    REGISTRY.get().is_some()
+}
+
+// the downloads running, the oldest first
+pub fn active() -> Vec<Transfer> {
+    let Some(registry) = REGISTRY.get() else {
+        return Vec::new();
+    };
+    let mut transfers: Vec<Transfer> = registry
+        .active
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(id, active)| Transfer {
+            id: *id,
+            client: active.client,
+            path: active.path.clone(),
+            total: active.total,
+            sent: active.sent.load(Ordering::Relaxed),
+            started: active.started,
+        })
+        .collect();
+    transfers.sort_by_key(|transfer| transfer.id);
+    transfers
+}
+
+pub fn served() -> u64 {
+    REGISTRY
+        .get()
+        .map_or(0, |registry| registry.served.load(Ordering::Relaxed))
+}
+
+pub fn in_flight() -> usize {
+    REGISTRY
+        .get()
+        .map_or(0, |registry| registry.in_flight.load(Ordering::Relaxed))
+}
+
+// counts the bytes of the files and archives sent, a download is listed until its body is
+// done or dropped by a client going away
+pub async fn track(req: Request, next: Next) -> Response {
+    let Some(registry) = REGISTRY.get() else {
+        return next.run(req).await;
+    };
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(addr)| {
+            addr.ip()
+        });
+    let head = req.method() == Method::HEAD;
+    let path = percent_encoding::percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .to_string();
+
+    registry.in_flight.fetch_add(1, Ordering::Relaxed);
+    let res = next.run(req).await;
+    registry.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+    let download = res.extensions().get::<FileContent>().is_some()
+        || res.headers().contains_key(header::CONTENT_DISPOSITION);
+    if head || !download || !res.status().is_success() {
+        return res;
+    }
+    let total = res
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<u64>().ok());
+    let whole = res.status() == StatusCode::OK;
+    let file = file_of(&path);
+    if whole {
+        webhook::notify(Kind::DownloadStarted, client, Some(&file), total, None);
+    }
+    let sent = Arc::new(AtomicU64::new(0));
+    let id = registry.next_id.fetch_add(1, Ordering::Relaxed);
+    registry
+        .active
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(
+            id,
+            Active {
+                client,
+                path,
+                total,
+                sent: sent.clone(),
+                started: Instant::now(),
+            },
+        );
+
+    let listed = Listed {
+        registry,
+        id,
+        client,
+        file,
+        sent,
+        whole,
+        complete: false,
+    };
+    let (parts, body) = res.into_parts();
+    let stream = futures_util::stream::unfold(
+        (body.into_data_stream(), Some(listed)),
+        |(mut body, mut listed)| async move {
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    if let Some(listed) = &listed {
+                        listed.count(chunk.len() as u64);
+                    }
+                    Some((Ok(chunk), (body, listed)))
+                }
+                // a failed body is no complete download, it is taken off the list right away
+                Some(Err(err)) => Some((Err(err), (body, None))),
+                None => {
+                    if let Some(listed) = &mut listed {
+                        listed.complete = true;
+                    }
+                    None
+                }
+            }
+        },
+    );
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+// the file sent by a download, by its path below the root whether it was downloaded or viewed
+fn file_of(path: &str) -> String {
+    let path = path.strip_prefix(base_path::get()).unwrap_or(path);
+    ["/download/", "/view/"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .map_or(path.to_string(), |below| format!("/{}", below))
+}
+
+// takes the download off the list once its body is gone, counts it in the --stats and tells
+// the --webhook about the whole files sent
+struct Listed {
+    registry: &'static Registry,
+    id: u64,
+    client: IpAddr,
+    file: String,
+    sent: Arc<AtomicU64>,
+    // the whole file, no part of it
+    whole: bool,
+    // the body was sent to its end
+    complete: bool,
+}
+
+impl Listed {
+    fn count(&self, bytes: u64) {
+        self.sent.fetch_add(bytes, Ordering::Relaxed);
+        self.registry.served.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Listed {
+    fn drop(&mut self) {
+        self.registry
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+        let sent = self.sent.load(Ordering::Relaxed);
+        let complete = self.whole && self.complete;
+        stats::record(&self.file, self.client, sent, complete);
+        if complete {
+            webhook::notify(
+                Kind::DownloadCompleted,
+                self.client,
+                Some(&self.file),
+                Some(sent),
+                None,
+            );
+        }
+    }
+}
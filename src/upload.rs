@@ -0,0 +1,240 @@
+use crate::{
+    auth::User,
+    base_path,
+    errors::{error_response, io_error_status},
+    receive::DropBox,
+    safe_dir, upload_hook,
+    webhook::{self, Kind},
+    AppState, Located,
+};
+use axum::{
+    extract::{multipart::Field, ConnectInfo, Multipart, Path as AxumPath, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::{
+    io::ErrorKind,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+// the upload form of a listing, only for users with the --write-role
+pub async fn upload_files(
+    state: State<AppState>,
+    addr: ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+    multipart: Multipart,
+) -> Response {
+    if !state.may_write(user.as_deref()) {
+        return error_response(StatusCode::FORBIDDEN, "Your account cannot upload files");
+    }
+    store_files(state, addr, user, headers, path, multipart).await
+}
+
+// stores the files of the upload form in the folder, existing files are never replaced. upload
+// links come here directly, the link stands for the role
+pub async fn store_files(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user: Option<Extension<User>>,
+    headers: HeaderMap,
+    path: Option<AxumPath<String>>,
+    mut multipart: Multipart,
+) -> Response {
+    // turned off on /admin since the page was loaded
+    if !state.settings.upload() {
+        return error_response(StatusCode::FORBIDDEN, "Uploads are turned off");
+    }
+    let folder = path.as_deref().map_or("", |v| v);
+    // Security check: prevent directory traversal attacks
+    if folder.contains("..") || folder.starts_with('/') || folder.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid folder path");
+    }
+    if !state.within_depth(folder, 0) {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "Folder is deeper than the allowed browse depth",
+        );
+    }
+    if state.exclude.matches(folder) {
+        return error_response(StatusCode::NOT_FOUND, "Folder not found");
+    }
+
+    let (root, below) = match state.locate(&headers, user.as_deref(), folder) {
+        Ok(Located::Disk(root, below)) => (root, below),
+        Ok(Located::Mounts(_)) => {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "Choose one of the folders to upload into",
+            )
+        }
+        Ok(Located::Packed(..)) => {
+            return error_response(StatusCode::FORBIDDEN, "The archive cannot be changed")
+        }
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+    let dir = match safe_dir(root, &root.join(below)) {
+        Ok(dir) => dir,
+        Err((status, msg)) => return error_response(status, &msg),
+    };
+
+    let mut stored = 0;
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                let msg = format!("Invalid upload: {}", e.body_text());
+                return error_response(e.status(), &msg);
+            }
+        };
+        // form fields without a file are ignored
+        let Some(name) = field.file_name().and_then(upload_name) else {
+            continue;
+        };
+
+        let target = match store(&mut field, &dir, &name, state.drop_box.as_deref()).await {
+            Ok(target) => target,
+            Err((status, msg)) => return error_response(status, &msg),
+        };
+        log::info!(
+            "[UPLOAD] Client: {} | User: {} | {}",
+            addr,
+            user.as_ref()
+                .map_or("-".to_string(), |Extension(user)| user.to_string()),
+            target.display()
+        );
+        let uploaded = if folder.is_empty() {
+            format!("/{}", name)
+        } else {
+            format!("/{}/{}", folder.trim_matches('/'), name)
+        };
+        webhook::notify(
+            Kind::UploadReceived,
+            addr.ip(),
+            Some(&uploaded),
+            fs::metadata(&target).await.ok().map(|meta| meta.len()),
+            user.as_ref().map(|Extension(user)| user.name.as_str()),
+        );
+        upload_hook::uploaded(&target);
+        stored += 1;
+    }
+
+    if stored == 0 {
+        return error_response(StatusCode::BAD_REQUEST, "No file in the upload");
+    }
+
+    let listing = if folder.is_empty() {
+        "/".to_string()
+    } else {
+        let encoded: Vec<String> = folder
+            .split('/')
+            .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+            .collect();
+        format!("/browse/{}", encoded.join("/"))
+    };
+    Redirect::to(&base_path::link(&listing)).into_response()
+}
+
+// the last component of the name sent by the browser, some still send C:\fakepath\name
+fn upload_name(raw: &str) -> Option<String> {
+    let name = raw.rsplit(['/', '\\']).next()?.trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+// streams the field to disk, a partial file is removed when the upload fails
+async fn store(
+    field: &mut Field<'_>,
+    dir: &Path,
+    name: &str,
+    drop_box: Option<&DropBox>,
+) -> Result<PathBuf, (StatusCode, String)> {
+    let (target, mut file) = create(dir, name, drop_box.is_some()).await?;
+
+    let mut reserved = 0;
+    let written = async {
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| (e.status(), format!("Upload interrupted: {}", e.body_text())))?
+        {
+            if let Some(drop_box) = drop_box {
+                if !drop_box.reserve(chunk.len() as u64) {
+                    return Err((
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "The drop box is full".to_string(),
+                    ));
+                }
+                reserved += chunk.len() as u64;
+            }
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| (io_error_status(&e), format!("Failed to write file: {}", e)))?;
+        }
+        file.flush()
+            .await
+            .map_err(|e| (io_error_status(&e), format!("Failed to write file: {}", e)))
+    }
+    .await;
+
+    if written.is_err() {
+        drop(file);
+        let _ = fs::remove_file(&target).await;
+        if let Some(drop_box) = drop_box {
+            drop_box.release(reserved);
+        }
+    }
+    written.map(|()| target)
+}
+
+// a new file for the upload, existing files are never replaced. with `rename` a clashing
+// name gets a number like browsers give downloads, report (1).pdf
+async fn create(
+    dir: &Path,
+    name: &str,
+    rename: bool,
+) -> Result<(PathBuf, fs::File), (StatusCode, String)> {
+    let mut attempt = 0;
+    loop {
+        let target = if attempt == 0 {
+            dir.join(name)
+        } else {
+            dir.join(numbered(name, attempt))
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .await
+        {
+            Ok(file) => return Ok((target, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && rename && attempt < 1000 => {
+                attempt += 1;
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                return Err((
+                    StatusCode::CONFLICT,
+                    "A file with this name already exists".to_string(),
+                ));
+            }
+            Err(e) => {
+                return Err((io_error_status(&e), format!("Failed to create file: {}", e)));
+            }
+        }
+    }
+}
+
+fn numbered(name: &str, n: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
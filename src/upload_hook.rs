@@ -0,0 +1,134 @@
+use std::{
+    path::Path,
+    process::Stdio,
+    sync::{Arc, OnceLock},
+};
+use tokio::{process::Command, sync::Semaphore};
+
+// --on-upload, a command run for every file stored by the upload form or the drop box. it is
+// split into words once at startup and run without a shell, so a file name cannot add commands
+struct Hook {
+    program: String,
+    args: Vec<String>,
+    // --on-upload-jobs, uploads beyond it wait for a running command to end
+    jobs: Arc<Semaphore>,
+}
+
+static HOOK: OnceLock<Hook> = OnceLock::new();
+
+// commands running at once without --on-upload-jobs
+pub const JOBS: usize = 2;
+
+pub fn enable(command: &str, jobs: usize) -> Result<(), String> {
+    let mut words = split(command)?;
+    if words.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+    // without a placeholder the file is the last argument
+    if !words.iter().any(|word| word.contains("{path}")) {
+        words.push("{path}".to_string());
+    }
+    let program = words.remove(0);
+    let _ = HOOK.set(Hook {
+        program,
+        args: words,
+        jobs: Arc::new(Semaphore::new(jobs.max(1))),
+    });
+    Ok(())
+}
+
+// runs the command for the file in the background, its result only shows up in the log
+pub fn uploaded(file: &Path) {
+    let Some(hook) = HOOK.get() else {
+        return;
+    };
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    let jobs = hook.jobs.clone();
+    tokio::spawn(async move {
+        let Ok(_job) = jobs.acquire_owned().await else {
+            return;
+        };
+        run(hook, &file).await;
+    });
+}
+
+async fn run(hook: &Hook, file: &Path) {
+    let path = file.to_string_lossy();
+    let name = file
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    let args = hook
+        .args
+        .iter()
+        .map(|arg| arg.replace("{path}", &path).replace("{name}", &name));
+    let output = Command::new(&hook.program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            log::info!("[UPLOAD] hook done for {}", file.display());
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            log::warn!(
+                "[UPLOAD] hook for {} failed with {}: {}",
+                file.display(),
+                output.status,
+                stderr.trim()
+            );
+        }
+        Err(err) => {
+            log::warn!("[UPLOAD] cannot run {}: {}", hook.program, err);
+        }
+    }
+}
+
+// the words of a command line the way a shell splits them: blanks separate words, quotes keep
+// them together and a backslash takes the next character as it is
+fn split(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unclosed double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
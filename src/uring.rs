@@ -0,0 +1,73 @@
+use bytes::Bytes;
+use futures_util::Stream;
+use std::{fs::File, io, sync::OnceLock, thread};
+use tokio::sync::mpsc;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+// chunks read ahead of the client for each download
+const READ_AHEAD: usize = 4;
+
+struct ReadJob {
+    // the file the handler opened and checked, the path is not looked up again
+    file: File,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+}
+
+static JOBS: OnceLock<mpsc::UnboundedSender<ReadJob>> = OnceLock::new();
+
+// starts the thread owning the io_uring runtime, every file read is submitted to it
+pub fn start() -> io::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<ReadJob>();
+    thread::Builder::new()
+        .name("io-uring".to_string())
+        .spawn(move || {
+            tokio_uring::start(async move {
+                while let Some(job) = rx.recv().await {
+                    tokio_uring::spawn(read_file(job));
+                }
+            })
+        })?;
+    let _ = JOBS.set(tx);
+    Ok(())
+}
+
+// streams the file through the io_uring thread, the file comes back when the backend is not
+// running
+pub fn stream(file: File) -> Result<impl Stream<Item = io::Result<Bytes>>, File> {
+    let Some(jobs) = JOBS.get() else {
+        return Err(file);
+    };
+    let (tx, rx) = mpsc::channel(READ_AHEAD);
+    jobs.send(ReadJob { file, tx }).map_err(|err| err.0.file)?;
+
+    Ok(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }))
+}
+
+async fn read_file(job: ReadJob) {
+    let file = tokio_uring::fs::File::from_std(job.file);
+
+    let mut offset = 0u64;
+    loop {
+        let (res, buf) = file.read_at(Vec::with_capacity(CHUNK_SIZE), offset).await;
+        match res {
+            Ok(0) => break,
+            Ok(n) => {
+                offset += n as u64;
+                // client went away
+                if job.tx.send(Ok(Bytes::from(buf))).await.is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                let _ = job.tx.send(Err(err)).await;
+                break;
+            }
+        }
+    }
+
+    if let Err(err) = file.close().await {
+        log::error!("cannot close file\n{}", err);
+    }
+}
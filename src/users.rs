@@ -0,0 +1,43 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// accounts given on the command line with --auth user:pass
+pub struct Users {
+    // password digests, comparing them does not leak how much of a password matched
+    passwords: HashMap<String, [u8; 32]>,
+}
+
+impl Users {
+    pub fn parse<'a>(specs: impl Iterator<Item = &'a String>) -> Result<Self, String> {
+        let mut passwords = HashMap::new();
+        for spec in specs {
+            let (user, password) = spec
+                .split_once(':')
+                .filter(|(user, password)| !user.is_empty() && !password.is_empty())
+                .ok_or_else(|| "expected user:pass".to_string())?;
+            if passwords
+                .insert(user.to_string(), digest(password))
+                .is_some()
+            {
+                return Err(format!("user {} is given twice", user));
+            }
+        }
+        Ok(Users { passwords })
+    }
+
+    pub fn authenticate(&self, user: &str, password: &str) -> Result<(), String> {
+        let expected = self
+            .passwords
+            .get(user)
+            .ok_or_else(|| format!("{} is not a known user", user))?;
+        if *expected == digest(password) {
+            Ok(())
+        } else {
+            Err(format!("wrong password for {}", user))
+        }
+    }
+}
+
+fn digest(password: &str) -> [u8; 32] {
+    Sha256::digest(password.as_bytes()).into()
+}
@@ -1,4 +1,8 @@
-use base64::{engine::general_purpose::STANDARD, Engine};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use qrcode::{render::svg, render::unicode, QrCode};
 use std::env;
 pub fn get_qr_code(text: &str) -> String {
@@ -25,12 +29,27 @@ pub fn get_qr_code(text: &str) -> String {
         }
         _ => {
             // Fallback to ASCII QR for others terminal
-            let ascii_qr = code.render::<unicode::Dense1x2>().quiet_zone(true).build();
-            format!("{}", ascii_qr)
+            code.render::<unicode::Dense1x2>().quiet_zone(true).build()
         }
     }
 }
 
+// the host of an address as it goes into a link, IPv6 in brackets with the % of a zone encoded
+// (fe80::1%eth0 becomes [fe80::1%25eth0])
+pub fn url_host(address: &str) -> String {
+    if !address.contains(':') {
+        return address.to_string();
+    }
+    format!("[{}]", address.replace('%', "%25"))
+}
+
+// random url safe token, used for sessions and login state
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("Failed to get random bytes");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
 pub fn html_escape(s: &str) -> String {
     s.chars()
         .map(|c| match c {
@@ -58,6 +77,15 @@ fn terminal_supports_images() -> Option<&'static str> {
     None
 }
 
+// percent-encodes every segment of a relative path, keeping the slashes between them
+pub fn encode_path(path: &str) -> String {
+    let encoded: Vec<String> = path
+        .split('/')
+        .map(|part| utf8_percent_encode(part, NON_ALPHANUMERIC).to_string())
+        .collect();
+    encoded.join("/")
+}
+
 pub fn bytes_to_human_size(bytes: u64) -> String {
     const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
     let mut size = bytes as f64;
@@ -72,24 +100,3 @@ pub fn bytes_to_human_size(bytes: u64) -> String {
         format!("{:.2} {}", size, UNITS[unit])
     }
 }
-
-pub fn start_logging(output_path: &str) {
-    use log::LevelFilter;
-    use log4rs::append::file::FileAppender;
-    use log4rs::config::{Appender, Config, Root};
-    use log4rs::encode::pattern::PatternEncoder;
-
-    let logfile = FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "[{d(%d-%m-%y %H:%M:%S)}] {l} - {m}{n}",
-        )))
-        .build(output_path)
-        .unwrap();
-
-    let config = Config::builder()
-        .appender(Appender::builder().build("logfile", Box::new(logfile)))
-        .build(Root::builder().appender("logfile").build(LevelFilter::Info))
-        .unwrap();
-
-    log4rs::init_config(config).unwrap();
-}
@@ -0,0 +1,106 @@
+use crate::{
+    auth::User,
+    base_path,
+    errors::error_response,
+    templates::{self, Theme},
+    utils, AppState, Located,
+};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use serde::Serialize;
+use std::path::Path;
+
+// fields of the video page
+#[derive(Serialize)]
+struct VideoPage<'a> {
+    name: &'a str,
+    // the file itself, sent in parts as the player asks for them
+    src: String,
+    // the video converted by --transcode, for the ones browsers do not play themselves
+    hls: Option<String>,
+    // the details of the video, /api/meta
+    meta: String,
+    download: String,
+    // the listing of the folder the file is in
+    back: String,
+}
+
+// the types browsers play, mime_guess knows some of them under older names
+pub fn mime(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "mp4" | "m4v" => Some("video/mp4"),
+        "webm" => Some("video/webm"),
+        "mkv" => Some("video/x-matroska"),
+        "mov" => Some("video/quicktime"),
+        "ogv" => Some("video/ogg"),
+        "avi" => Some("video/x-msvideo"),
+        "mts" | "m2ts" => Some("video/mp2t"),
+        "wmv" => Some("video/x-ms-wmv"),
+        _ => None,
+    }
+}
+
+// the containers every browser plays, the others only some or none
+#[cfg(feature = "transcode")]
+pub fn plays_natively(path: &str) -> bool {
+    matches!(
+        mime(Path::new(path)),
+        Some("video/mp4" | "video/webm" | "video/ogg")
+    )
+}
+
+pub fn is_video(path: &str) -> bool {
+    mime(Path::new(path)).is_some()
+}
+
+// /view of a video file, a page playing it. the file goes through the checks of a download
+// when the player fetches it, the page only tells whether there is one
+pub fn view(state: &AppState, user: Option<&User>, headers: &HeaderMap, path: &str) -> Response {
+    // Security check: prevent directory traversal attacks
+    if path.contains("..") || path.starts_with('/') || path.starts_with('\\') {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid file path");
+    }
+    let exists = state.within_depth(path, 1)
+        && !state.exclude.matches(path)
+        && match state.locate(headers, user, path) {
+            Ok(Located::Disk(root, below)) => root.join(below).is_file(),
+            Ok(Located::Packed(packed, below)) => packed.is_dir(below) == Some(false),
+            Ok(Located::Mounts(_)) | Err(_) => false,
+        };
+    if !exists {
+        return error_response(StatusCode::NOT_FOUND, "File not found");
+    }
+
+    #[cfg(feature = "transcode")]
+    let hls = crate::transcode::playlist_url(path);
+    #[cfg(not(feature = "transcode"))]
+    let hls = None;
+
+    let encoded = utils::encode_path(path);
+    let page = VideoPage {
+        name: path.rsplit('/').next().unwrap_or(path),
+        src: base_path::link(&format!("/view/{}?raw=1", encoded)),
+        hls,
+        meta: base_path::link(&format!("/api/meta/{}", encoded)),
+        download: base_path::link(&format!("/download/{}", encoded)),
+        back: match path.rsplit_once('/') {
+            Some((folder, _)) => {
+                base_path::link(&format!("/browse/{}", utils::encode_path(folder)))
+            }
+            None => base_path::link("/"),
+        },
+    };
+    match templates::render("video.html", Theme::of(headers), &page) {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            log::error!("Error loading video template: {}", e);
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load video template",
+            )
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{dav, AppState, Located};
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, Uri},
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Visibility {
+    // reachable without a login even when the server requires one
+    Public,
+    // needs a login, the default when a login is configured
+    Login,
+    // left out of the parent listing, still reachable by its address
+    Hidden,
+}
+
+// marks requests to public folders, the login middleware lets them through
+#[derive(Clone)]
+pub struct PublicAccess;
+
+// set by the login middleware on marked requests without a session, the login-only entries
+// below a public folder are left out for them
+#[derive(Clone)]
+pub struct Anonymous;
+
+// folder visibility from --visibility PATH=public|login|hidden, paths relative to the root
+pub struct VisibilityRules {
+    rules: Vec<(PathBuf, Visibility)>,
+}
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Visibility::Public),
+            "login" => Ok(Visibility::Login),
+            "hidden" => Ok(Visibility::Hidden),
+            _ => Err(format!("unknown visibility {}", s)),
+        }
+    }
+}
+
+impl VisibilityRules {
+    pub fn new(rules: Vec<(String, Visibility)>) -> Self {
+        VisibilityRules {
+            rules: rules
+                .into_iter()
+                .map(|(path, visibility)| (PathBuf::from(path.trim_matches('/')), visibility))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // public and login apply to the whole folder, the deepest configured folder wins
+    pub fn is_public(&self, path: &str) -> bool {
+        self.levels_below_public(path).is_some()
+    }
+
+    // how many levels the path is below the public folder it is in, None when it needs a login
+    fn levels_below_public(&self, path: &str) -> Option<usize> {
+        let path = Path::new(path.trim_matches('/'));
+        // never decide on paths that could leave the configured folder
+        if path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        self.rules
+            .iter()
+            .filter(|(folder, visibility)| {
+                *visibility != Visibility::Hidden && path.starts_with(folder)
+            })
+            .max_by_key(|(folder, _)| folder.components().count())
+            .filter(|(_, visibility)| *visibility == Visibility::Public)
+            .map(|(folder, _)| path.components().count() - folder.components().count())
+    }
+
+    // hidden only applies to the entry itself, its content is listed when browsed directly
+    pub fn is_hidden(&self, path: &str) -> bool {
+        let path = Path::new(path.trim_matches('/'));
+        self.rules
+            .iter()
+            .any(|(folder, visibility)| *visibility == Visibility::Hidden && path == folder)
+    }
+}
+
+// the folder picked by the query of /search and /qr.svg, the root when there is none
+#[derive(Deserialize)]
+struct FolderQuery {
+    #[serde(default)]
+    path: String,
+}
+
+// routes serving a file or folder named by the rest of their path
+const PREFIXES: [&str; 11] = [
+    "/browse/",
+    "/download/",
+    "/view/",
+    "/thumb/",
+    "/api/meta/",
+    "/api/hash/",
+    "/qr/file/",
+    "/hls/",
+    "/events/",
+    "/archive/",
+    "/dav/",
+];
+
+// relative path of the routes that serve a file or folder, None for the other routes
+fn served_path(uri: &Uri) -> Option<String> {
+    let path = percent_decode_str(uri.path()).decode_utf8_lossy();
+    // the selection of the root folder is posted to /archive
+    if path == "/" || path == "/archive" || path == "/dav" || path == "/events" {
+        return Some(String::new());
+    }
+    if path == "/search" || path == "/qr.svg" {
+        return Query::<FolderQuery>::try_from_uri(uri)
+            .ok()
+            .map(|Query(query)| query.path);
+    }
+    PREFIXES
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .map(str::to_string)
+}
+
+// whether the path is public without going through a symbolic link below its public folder.
+// links there need a login, they can lead anywhere the --follow-symlinks policy allows
+fn stays_public(state: &AppState, headers: &HeaderMap, path: &str) -> bool {
+    let Some(levels) = state.visibility.levels_below_public(path) else {
+        return false;
+    };
+    // the archive and the list of the mounts have no links
+    let Ok(Located::Disk(root, below)) = state.locate(headers, None, path) else {
+        return true;
+    };
+    let mut target = root.join(below.trim_matches('/'));
+    (0..levels).all(|_| {
+        let link = fs::symlink_metadata(&target).is_ok_and(|meta| meta.file_type().is_symlink());
+        target.pop();
+        !link
+    })
+}
+
+pub async fn mark_public(State(state): State<AppState>, mut req: Request, next: Next) -> Response {
+    // changes through WebDAV always need the login
+    let dav_write = req.uri().path().starts_with("/dav") && !dav::is_read(req.method());
+    if !dav_write
+        && served_path(req.uri()).is_some_and(|path| stays_public(&state, req.headers(), &path))
+    {
+        req.extensions_mut().insert(PublicAccess);
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[(&str, Visibility)]) -> VisibilityRules {
+        VisibilityRules::new(
+            rules
+                .iter()
+                .map(|(path, visibility)| (path.to_string(), *visibility))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn deepest_rule_wins() {
+        let rules = rules(&[
+            ("/", Visibility::Public),
+            ("staff", Visibility::Login),
+            ("staff/handouts", Visibility::Public),
+            ("staff/handouts/drafts", Visibility::Hidden),
+        ]);
+        assert!(rules.is_public(""));
+        assert!(rules.is_public("photos/2024/beach.jpg"));
+        assert!(!rules.is_public("staff"));
+        assert!(!rules.is_public("staff/minutes.pdf"));
+        assert!(rules.is_public("/staff/handouts/"));
+        // hidden only leaves it out of the listing, the public folder above still decides
+        assert!(rules.is_public("staff/handouts/drafts/week1.pdf"));
+        assert!(rules.is_hidden("staff/handouts/drafts"));
+        assert!(!rules.is_hidden("staff/handouts/drafts/week1.pdf"));
+    }
+
+    #[test]
+    fn login_without_rule() {
+        let rules = rules(&[("public", Visibility::Public)]);
+        assert!(!rules.is_public(""));
+        assert!(!rules.is_public("publications"));
+        assert!(rules.is_public("public/notes.txt"));
+    }
+
+    #[test]
+    fn rejects_paths_leaving_the_folder() {
+        let rules = rules(&[("public", Visibility::Public)]);
+        assert!(!rules.is_public("public/../private"));
+        assert!(!rules.is_public("public/.."));
+        assert!(!rules.is_public("../public"));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::utils;
+use chrono::{SecondsFormat, Utc};
+use reqwest::Url;
+use serde::Serialize;
+use std::{net::IpAddr, sync::OnceLock, time::Duration};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+
+// a hook that does not answer in this time is given up on, the event is not sent again
+const TIMEOUT: Duration = Duration::from_secs(10);
+// events waiting for a slow or unreachable hook, the ones beyond are dropped
+const QUEUE: usize = 256;
+
+// what a --webhook is told about
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    DownloadStarted,
+    DownloadCompleted,
+    UploadReceived,
+    AuthFailed,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 4] = [
+        Kind::DownloadStarted,
+        Kind::DownloadCompleted,
+        Kind::UploadReceived,
+        Kind::AuthFailed,
+    ];
+
+    pub fn parse(name: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::DownloadStarted => "download-started",
+            Kind::DownloadCompleted => "download-completed",
+            Kind::UploadReceived => "upload-received",
+            Kind::AuthFailed => "auth-failed",
+        }
+    }
+}
+
+// the JSON posted to every hook. `text` makes it a message of its own for chat webhooks like
+// the ones of Slack or Mattermost
+#[derive(Serialize)]
+pub struct Event {
+    event: &'static str,
+    time: String,
+    client: IpAddr,
+    path: Option<String>,
+    bytes: Option<u64>,
+    user: Option<String>,
+    text: String,
+}
+
+// --webhook URL, the events are posted one after the other by a task of their own, a slow hook
+// never holds up a request
+struct Hooks {
+    urls: Vec<Url>,
+    kinds: Vec<Kind>,
+    events: Sender<Event>,
+}
+
+static HOOKS: OnceLock<Hooks> = OnceLock::new();
+
+// the events to hand to `deliver` once the runtime runs
+pub fn enable(urls: Vec<Url>, kinds: Vec<Kind>) -> Receiver<Event> {
+    let (events, receiver) = mpsc::channel(QUEUE);
+    let _ = HOOKS.set(Hooks {
+        urls,
+        kinds,
+        events,
+    });
+    receiver
+}
+
+pub fn enabled() -> bool {
+    HOOKS.get().is_some()
+}
+
+// queues the event for the hooks that asked for its kind
+pub fn notify(
+    kind: Kind,
+    client: IpAddr,
+    path: Option<&str>,
+    bytes: Option<u64>,
+    user: Option<&str>,
+) {
+    let Some(hooks) = HOOKS.get() else {
+        return;
+    };
+    if !hooks.kinds.contains(&kind) {
+        return;
+    }
+    let size = bytes.map_or(String::new(), |bytes| {
+        format!(" ({})", utils::bytes_to_human_size(bytes))
+    });
+    let text = match kind {
+        Kind::DownloadStarted => format!(
+            "{} started downloading {}{}",
+            client,
+            path.unwrap_or("-"),
+            size
+        ),
+        Kind::DownloadCompleted => format!("{} downloaded {}{}", client, path.unwrap_or("-"), size),
+        Kind::UploadReceived => format!("{} uploaded {}{}", client, path.unwrap_or("-"), size),
+        Kind::AuthFailed => format!("Failed login of {} from {}", user.unwrap_or("-"), client),
+    };
+    let sent = hooks.events.try_send(Event {
+        event: kind.name(),
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        client,
+        path: path.map(str::to_string),
+        bytes,
+        user: user.map(str::to_string),
+        text: format!("file-serve: {}", text),
+    });
+    // a download never waits for the hooks, nor piles up events for them in memory
+    if let Err(TrySendError::Full(event)) = sent {
+        log::warn!(
+            "[WEBHOOK] {} events are waiting, {} dropped",
+            QUEUE,
+            event.event
+        );
+    }
+}
+
+pub async fn deliver(mut events: Receiver<Event>) {
+    let Some(hooks) = HOOKS.get() else {
+        return;
+    };
+    let http = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    while let Some(event) = events.recv().await {
+        for url in &hooks.urls {
+            let sent = http
+                .post(url.clone())
+                .json(&event)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status());
+            // the address of a chat hook is its secret, only the host is logged
+            if let Err(err) = sent {
+                log::warn!(
+                    "[WEBHOOK] {} to {} failed: {}",
+                    event.event,
+                    url.host_str().unwrap_or("-"),
+                    err.without_url()
+                );
+            }
+        }
+    }
+}